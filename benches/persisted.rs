@@ -0,0 +1,81 @@
+//! Benchmarks for the overhead of the persisted wrappers. The store used here
+//! does as little work as possible, so the numbers reflect the wrappers
+//! themselves rather than any real persistence mechanism.
+
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, BenchmarkId,
+    Criterion,
+};
+use persisted::{
+    Persisted, PersistedContainer, PersistedKey, PersistedLazy, PersistedStore,
+};
+
+/// Value sizes to benchmark, in bytes
+const SIZES: &[usize] = &[8, 1024, 64 * 1024];
+
+/// A store that never has a value, and discards writes
+struct Store;
+
+impl<K: PersistedKey> PersistedStore<K> for Store {
+    fn load_persisted(_key: &K) -> Option<K::Value> {
+        None
+    }
+
+    fn store_persisted(_key: &K, value: &K::Value) {
+        black_box(value);
+    }
+}
+
+#[derive(PersistedKey)]
+#[persisted(Vec<u8>)]
+struct BytesKey;
+
+/// A container whose persisted value is a copy of its contents
+#[derive(PersistedContainer)]
+struct Bytes(#[persist] Vec<u8>);
+
+fn bench_eager(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Persisted");
+    for &size in SIZES {
+        group.bench_with_input(BenchmarkId::new("new", size), &size, |b, _| {
+            b.iter_batched(
+                || vec![0; size],
+                |value| Persisted::<Store, _>::new(black_box(BytesKey), value),
+                BatchSize::SmallInput,
+            )
+        });
+
+        let mut persisted = Persisted::<Store, _>::new(BytesKey, vec![0; size]);
+        group.bench_with_input(
+            BenchmarkId::new("get_mut", size),
+            &size,
+            |b, _| b.iter(|| persisted.get_mut()[0] ^= 1),
+        );
+    }
+    group.finish();
+}
+
+fn bench_lazy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("PersistedLazy::get_mut");
+    for &size in SIZES {
+        let container = Bytes(vec![0; size]);
+        let mut lazy = PersistedLazy::<Store, _, _>::new(BytesKey, container);
+        // Persist once so there's a baseline to compare against
+        lazy.persist();
+
+        group.bench_with_input(
+            BenchmarkId::new("unchanged", size),
+            &size,
+            |b, _| b.iter(|| drop(lazy.get_mut())),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("changed", size),
+            &size,
+            |b, _| b.iter(|| lazy.get_mut().0[0] ^= 1),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_eager, bench_lazy);
+criterion_main!(benches);
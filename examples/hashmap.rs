@@ -3,15 +3,16 @@
 //! pattern is effective if you want to persist values between multiple life
 //! cycles of some subsection of your program, within the span of one process.
 
-use persisted::{Persisted, PersistedKey, PersistedStore};
+use persisted::{KeyCodec, Persisted, PersistedKey, PersistedStore};
 use std::{
     cell::RefCell,
     collections::HashMap,
-    fmt::{self, Debug, Display},
+    fmt::{self, Display},
     str::FromStr,
 };
 
-/// The key is a pair of the key's type and content. The value is a stringified
+/// The key is a pair of the key's type and content, as given by
+/// [KeyCodec::storage_key]. The value is a stringified
 /// version of the value. Typically you would want to replacing stringification
 /// and parsing with a more robust form of serialization/deserialization, but
 /// this example is simplified to not rely on dependencies.
@@ -29,29 +30,48 @@ impl Store {
 
 impl<K> PersistedStore<K> for Store
 where
-    K: Display + PersistedKey,
+    K: KeyCodec,
     K::Value: Display + FromStr,
-    <K::Value as FromStr>::Err: Debug,
 {
     fn load_persisted(key: &K) -> Option<K::Value> {
-        Self::INSTANCE.with(|store| {
+        let value_str = Self::INSTANCE.with(|store| {
             let map = store.0.borrow();
-            let value_str = map.get(&(K::type_name(), key.to_string()));
-            value_str.map(|value| value.parse().expect("Error parsing value"))
-        })
+            map.get(&key.storage_key()).cloned()
+        })?;
+        // If the value type changed since this was stored, it may not parse.
+        // Report it and fall back to the default, rather than panicking
+        match value_str.parse() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                Self::on_load_error(key);
+                None
+            }
+        }
     }
 
     fn store_persisted(key: &K, value: &K::Value) {
         Self::INSTANCE.with(|store| {
             let mut map = store.0.borrow_mut();
-            map.insert((K::type_name(), key.to_string()), value.to_string());
+            map.insert(key.storage_key(), value.to_string());
         })
     }
+
+    fn on_load_error(key: &K) {
+        // You can replace this with logging, tracing, etc.
+        let (type_name, key_id) = key.storage_key();
+        println!("Invalid value for key {type_name}/{key_id}");
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 struct PersonId(u64);
 
+impl Display for PersonId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 #[derive(Debug)]
 #[allow(unused)]
 struct Person {
@@ -95,27 +115,16 @@ impl<T> SelectList<T> {
 /// Persist the selected value in the list by storing its index. This is simple
 /// but relies on the list keeping the same items, in the same order, between
 /// sessions.
-#[derive(PersistedKey)]
+/// Unit keys have an empty ID
+#[derive(KeyCodec, PersistedKey)]
 #[persisted(usize)]
 struct SelectedIndexKey;
 
-impl Display for SelectedIndexKey {
-    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // Nothing to include here, so serialize as the empty string
-        Ok(())
-    }
-}
-
-#[derive(Debug, PersistedKey)]
+/// The ID is the person's ID
+#[derive(Debug, KeyCodec, PersistedKey)]
 #[persisted(bool)]
 struct ToggleKey(PersonId);
 
-impl Display for ToggleKey {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", (self.0).0)
-    }
-}
-
 fn main() {
     let make_list = || {
         vec![
@@ -135,4 +144,16 @@ fn main() {
     assert_eq!(*people.selected_index, 1);
     assert!(!*people.values[1].enabled);
     println!("Selected: {:?}", people.selected());
+
+    // Simulate a change in value type between versions, by storing a value
+    // that can't be parsed as the current type. Instead of panicking, the
+    // store reports the error and the default is used
+    Store::INSTANCE.with(|store| {
+        store.0.borrow_mut().insert(
+            (SelectedIndexKey::type_name(), String::new()),
+            "Susan".into(),
+        )
+    });
+    let people = SelectList::new(make_list());
+    assert_eq!(*people.selected_index, 0);
 }
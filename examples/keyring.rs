@@ -0,0 +1,43 @@
+//! Persist a secret API token to the OS secret store, e.g. the macOS Keychain
+//! or Windows Credential Manager. Run with `--features keyring`.
+
+use persisted::{
+    KeyCodec, KeyringService, KeyringStore, Persisted, PersistedKey,
+    PersistedStore, Secret,
+};
+
+/// All values for this app are stored under this service
+struct ExampleApp;
+
+impl KeyringService for ExampleApp {
+    fn service() -> &'static str {
+        "persisted-keyring-example"
+    }
+}
+
+type Store = KeyringStore<ExampleApp>;
+
+/// An API token for a particular account
+#[derive(KeyCodec, PersistedKey)]
+#[persisted(Secret<String>)]
+struct ApiTokenKey<'a> {
+    account: &'a str,
+}
+
+fn main() {
+    {
+        let mut token =
+            Persisted::<Store, _>::new_default(ApiTokenKey { account: "fred" });
+        *token.get_mut() = Secret::new("hunter2".into());
+    }
+
+    let token =
+        Persisted::<Store, _>::new_default(ApiTokenKey { account: "fred" });
+    assert!(token.was_loaded());
+    assert_eq!(token.value().expose(), "hunter2");
+    // The token is hidden from debug output
+    println!("Loaded token: {:?}", token.value());
+
+    // Clean up, so the example doesn't leave anything behind
+    Store::remove_persisted(token.key());
+}
@@ -1,23 +1,21 @@
 //! Lazy persistence allows storing and loading values when some amount of work
 //! needs to be done to retrieve and/or restore the value on the data side. In
 //! this example, the persisted value is the selected index in a list. Rather
-//! than persisting the index directly, we can define `SelectList` as a
-//! [PersistedContainer], then persist the entire container. The ID of the
-//! selected item the only thing stored in the database; this is merely a
-//! convenience to change where in our data tree we declare the persistence.
+//! than persisting the index directly, we persist the entire list as an
+//! [IdSelect]. The ID of the selected item is the only thing stored in the
+//! database; this is merely a convenience to change where in our data tree we
+//! declare the persistence.
 //!
-//! This is useful when you have a generic container (in this case
-//! `SelectList`) that may be used multiple times with different persistence
-//! keys (or not persisted at all in some cases).
+//! This is useful when you have a generic container (in this case [IdSelect])
+//! that may be used multiple times with different persistence keys (or not
+//! persisted at all in some cases).
 
-use persisted::{
-    PersistedContainer, PersistedKey, PersistedLazy, PersistedStore,
-};
+use persisted::{IdSelect, PersistedKey, PersistedLazy, PersistedStore};
 use std::cell::Cell;
 
 /// Persist just the stored ID
 #[derive(Default)]
-struct Store(Cell<Option<PersonId>>);
+struct Store(Cell<Option<Option<PersonId>>>);
 
 impl Store {
     thread_local! {
@@ -26,11 +24,11 @@ impl Store {
 }
 
 impl PersistedStore<SelectedIdKey> for Store {
-    fn load_persisted(_key: &SelectedIdKey) -> Option<PersonId> {
+    fn load_persisted(_key: &SelectedIdKey) -> Option<Option<PersonId>> {
         Self::INSTANCE.with(|store| store.0.get())
     }
 
-    fn store_persisted(_key: &SelectedIdKey, value: &PersonId) {
+    fn store_persisted(_key: &SelectedIdKey, value: &Option<PersonId>) {
         Self::INSTANCE.with(|store| store.0.set(Some(*value)))
     }
 }
@@ -47,40 +45,9 @@ struct Person {
 }
 
 #[derive(Debug, PersistedKey)]
-#[persisted(PersonId)]
+#[persisted(Option<PersonId>)]
 struct SelectedIdKey;
 
-/// A list of items, with one item selected
-struct SelectList {
-    values: Vec<Person>,
-    selected_index: usize,
-}
-
-impl SelectList {
-    fn selected(&self) -> &Person {
-        &self.values[self.selected_index]
-    }
-}
-
-impl PersistedContainer for SelectList {
-    type Value = PersonId;
-
-    fn get_to_persist(&self) -> Self::Value {
-        self.selected().id
-    }
-
-    fn restore_persisted(&mut self, value: Self::Value) {
-        // Find selected person by ID
-        self.selected_index = self
-            .values
-            .iter()
-            .enumerate()
-            .find(|(_, person)| person.id == value)
-            .map(|(i, _)| i)
-            .unwrap_or_default();
-    }
-}
-
 fn main() {
     let person_list = vec![
         Person {
@@ -102,22 +69,16 @@ fn main() {
 
     let mut people = PersistedLazy::<Store, _, _>::new(
         SelectedIdKey,
-        SelectList {
-            values: person_list.clone(),
-            selected_index: 0,
-        },
+        IdSelect::new(person_list.clone(), |person| person.id),
     );
-    people.get_mut().selected_index = 1;
+    people.get_mut().select(1);
     println!("Selected: {:?}", people.selected());
 
     let people = PersistedLazy::<Store, _, _>::new(
         SelectedIdKey,
-        SelectList {
-            values: person_list,
-            selected_index: 0,
-        },
+        IdSelect::new(person_list, |person| person.id),
     );
     // The previous value was restored
-    assert_eq!(people.selected_index, 1);
+    assert_eq!(people.selected_index(), 1);
     println!("Selected: {:?}", people.selected());
 }
@@ -2,20 +2,21 @@
 
 use persisted::{Persisted, PersistedKey, PersistedStore};
 use rusqlite::{named_params, Connection, OptionalExtension};
+use std::env;
 
 /// Persist data in a SQLite database
 struct Store(Connection);
 
 impl Store {
-    const DB_PATH: &'static str = "persisted.sqlite";
-
     thread_local! {
         static INSTANCE: Store = Store::new();
     }
 
     fn new() -> Self {
-        let connection =
-            Connection::open(Self::DB_PATH).expect("Error opening DB");
+        // Use the temp dir so running the example doesn't litter the working
+        // directory
+        let path = env::temp_dir().join("persisted.sqlite");
+        let connection = Connection::open(path).expect("Error opening DB");
         connection
             .execute(
                 "CREATE TABLE IF NOT EXISTS persisted (
@@ -43,6 +44,16 @@ impl PersistedStore<SelectedIndexKey> for Store {
         });
         match result {
             Ok(option) => option,
+            // The stored value isn't the expected type, e.g. because the
+            // value type changed. Fall back to the default
+            Err(
+                rusqlite::Error::InvalidColumnType(..)
+                | rusqlite::Error::FromSqlConversionFailure(..)
+                | rusqlite::Error::IntegralValueOutOfRange(..),
+            ) => {
+                Self::on_load_error(key);
+                None
+            }
             // You can replace this with logging, tracing, etc.
             Err(error) => {
                 println!(
@@ -73,6 +84,10 @@ impl PersistedStore<SelectedIndexKey> for Store {
             println!("Error occured persisting {key:?}={value:?}: {error}");
         }
     }
+
+    fn on_load_error(key: &SelectedIndexKey) {
+        println!("Invalid value stored for key {key:?}");
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -0,0 +1,259 @@
+//! Derive logic for `PersistedContainer`
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_quote,
+    spanned::Spanned,
+    Data, DataEnum, DataStruct, DeriveInput, Fields, Index, Member, Token,
+};
+
+pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
+    let attr = input
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("persisted"))
+        .map(|attr| attr.parse_args::<ContainerAttr>())
+        .transpose()?;
+
+    match &input.data {
+        Data::Enum(data) => {
+            let Some(ContainerAttr { enum_repr }) = attr else {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "`PersistedContainer` derive on an enum requires \
+                    `#[persisted(enum_repr = <type>)]` attribute",
+                ));
+            };
+            derive_enum(&input, data, &enum_repr)
+        }
+        Data::Struct(data) => {
+            if attr.is_some() {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "`enum_repr` is only supported on enums",
+                ));
+            }
+            derive_struct(&input, data)
+        }
+        Data::Union(_) => Err(syn::Error::new(
+            input.ident.span(),
+            "`PersistedContainer` cannot be derived on unions",
+        )),
+    }
+}
+
+/// Persist the fields of a struct marked with `#[persist]`. A single field is
+/// persisted verbatim; multiple fields are persisted as a tuple, in
+/// declaration order.
+fn derive_struct(
+    input: &DeriveInput,
+    data: &DataStruct,
+) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+
+    let (members, field_types): (Vec<_>, Vec<_>) = data
+        .fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| {
+            field
+                .attrs
+                .iter()
+                .any(|attr| attr.path().is_ident("persist"))
+        })
+        .map(|(index, field)| {
+            let member = match &field.ident {
+                Some(ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(Index::from(index)),
+            };
+            (member, &field.ty)
+        })
+        .unzip();
+    if members.is_empty() {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "`PersistedContainer` derive on a struct requires at least one \
+            field marked `#[persist]`",
+        ));
+    }
+
+    // Fields are cloned to be persisted
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for field_type in &field_types {
+        where_clause
+            .predicates
+            .push(parse_quote!(#field_type: ::core::clone::Clone));
+    }
+    let (impl_generics, type_generics, where_clause) =
+        generics.split_for_impl();
+
+    let (value_type, get_to_persist, restore_persisted, if_changed) =
+        if let ([member], [field_type]) =
+            (members.as_slice(), field_types.as_slice())
+        {
+            // A single field can be compared in place, so we only clone it
+            // when it actually changed
+            let if_changed = quote! {
+                fn get_to_persist_if_changed(
+                    &self,
+                    last_persisted: &Self::Value,
+                ) -> ::core::option::Option<Self::Value>
+                where
+                    Self::Value: ::core::cmp::PartialEq,
+                {
+                    if self.#member == *last_persisted {
+                        ::core::option::Option::None
+                    } else {
+                        ::core::option::Option::Some(
+                            ::core::clone::Clone::clone(&self.#member),
+                        )
+                    }
+                }
+            };
+            (
+                quote!(#field_type),
+                quote!(::core::clone::Clone::clone(&self.#member)),
+                quote!(self.#member = value;),
+                if_changed,
+            )
+        } else {
+            let indexes = (0..members.len()).map(Index::from);
+            (
+                quote!((#(#field_types,)*)),
+                quote!((#(::core::clone::Clone::clone(&self.#members),)*)),
+                quote!(#(self.#members = value.#indexes;)*),
+                quote!(),
+            )
+        };
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics persisted::PersistedContainer
+            for #name #type_generics #where_clause
+        {
+            type Value = #value_type;
+
+            fn get_to_persist(&self) -> Self::Value {
+                #get_to_persist
+            }
+
+            fn restore_persisted(&mut self, value: Self::Value) {
+                #restore_persisted
+            }
+
+            #if_changed
+        }
+    })
+}
+
+/// Persist a fieldless enum as its integer discriminant
+fn derive_enum(
+    input: &DeriveInput,
+    data: &DataEnum,
+    repr: &syn::Type,
+) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) =
+        input.generics.split_for_impl();
+
+    let variants = data
+        .variants
+        .iter()
+        .map(|variant| match variant.fields {
+            Fields::Unit => Ok(&variant.ident),
+            Fields::Named(_) | Fields::Unnamed(_) => Err(syn::Error::new(
+                variant.fields.span(),
+                "`enum_repr` only supports variants without fields",
+            )),
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics persisted::PersistedContainer
+            for #name #type_generics #where_clause
+        {
+            type Value = #repr;
+
+            fn get_to_persist(&self) -> Self::Value {
+                match self {
+                    #(Self::#variants => Self::#variants as #repr,)*
+                }
+            }
+
+            fn restore_persisted(&mut self, value: Self::Value) {
+                // Unknown discriminants are ignored, so the container keeps
+                // its initial value
+                self.try_restore_persisted(value);
+            }
+
+            fn try_restore_persisted(&mut self, value: Self::Value) -> bool {
+                match <Self as ::core::convert::TryFrom<#repr>>::try_from(value)
+                {
+                    ::core::result::Result::Ok(value) => {
+                        *self = value;
+                        true
+                    }
+                    ::core::result::Result::Err(_) => false,
+                }
+            }
+        }
+
+        #[automatically_derived]
+        impl #impl_generics ::core::convert::From<#name #type_generics>
+            for #repr #where_clause
+        {
+            fn from(value: #name #type_generics) -> Self {
+                value as #repr
+            }
+        }
+
+        #[automatically_derived]
+        impl #impl_generics ::core::convert::TryFrom<#repr>
+            for #name #type_generics #where_clause
+        {
+            /// The unknown discriminant
+            type Error = #repr;
+
+            fn try_from(
+                value: #repr,
+            ) -> ::core::result::Result<Self, Self::Error> {
+                match value {
+                    #(
+                        value if value == Self::#variants as #repr => {
+                            ::core::result::Result::Ok(Self::#variants)
+                        }
+                    )*
+                    _ => ::core::result::Result::Err(value),
+                }
+            }
+        }
+    })
+}
+
+/// Contents of the `#[persisted(...)]` attribute on a container
+struct ContainerAttr {
+    enum_repr: syn::Type,
+}
+
+impl Parse for ContainerAttr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: syn::Ident = input.parse()?;
+        if ident != "enum_repr" {
+            return Err(syn::Error::new(
+                ident.span(),
+                format!("Unknown `persisted` argument `{ident}`"),
+            ));
+        }
+        input.parse::<Token![=]>()?;
+        let enum_repr = input.parse()?;
+        // Allow trailing comma
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+        Ok(Self { enum_repr })
+    }
+}
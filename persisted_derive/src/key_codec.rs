@@ -0,0 +1,73 @@
+//! Derive logic for `KeyCodec`
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields, Index, Member};
+
+pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) =
+        input.generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let members = data.fields.iter().enumerate().map(|(i, field)| {
+                match &field.ident {
+                    Some(ident) => Member::Named(ident.clone()),
+                    None => Member::Unnamed(Index::from(i)),
+                }
+            });
+            quote! {
+                persisted::__join_key_id(&[#(&self.#members),*])
+            }
+        }
+        Data::Enum(data) => {
+            let arms = data.variants.iter().map(|variant| {
+                let ident = &variant.ident;
+                let variant_name = ident.to_string();
+                // Bind each field to a local so we can display it
+                let bindings: Vec<_> = (0..variant.fields.len())
+                    .map(|i| format_ident!("field{i}"))
+                    .collect();
+                let pattern = match &variant.fields {
+                    Fields::Named(fields) => {
+                        let names = fields
+                            .named
+                            .iter()
+                            .map(|field| field.ident.as_ref().unwrap());
+                        quote! { { #(#names: #bindings),* } }
+                    }
+                    Fields::Unnamed(_) => quote! { (#(#bindings),*) },
+                    Fields::Unit => quote! {},
+                };
+                quote! {
+                    Self::#ident #pattern => persisted::__join_key_id(
+                        &[&#variant_name, #(#bindings),*]
+                    ),
+                }
+            });
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                name.span(),
+                "`KeyCodec` cannot be derived on unions",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics persisted::KeyCodec
+            for #name #type_generics #where_clause
+        {
+            fn key_id(&self) -> persisted::__alloc::string::String {
+                #body
+            }
+        }
+    })
+}
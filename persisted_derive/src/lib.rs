@@ -1,11 +1,19 @@
+mod container;
+mod key_codec;
+
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input, DeriveInput, Token,
+};
 
 #[proc_macro_derive(PersistedKey, attributes(persisted))]
 pub fn persisted_key_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let name = input.ident;
+    let (impl_generics, type_generics, where_clause) =
+        input.generics.split_for_impl();
 
     // Load type from #[persisted(...)] attribute
     let attr = input
@@ -25,17 +33,324 @@ pub fn persisted_key_derive(input: TokenStream) -> TokenStream {
         );
     let attr_tokens: TokenStream =
         attr.meta.require_list().unwrap().tokens.clone().into();
-    let value_type = parse_macro_input!(attr_tokens as syn::Type);
+    let KeyAttr {
+        value_type,
+        validate,
+        skip_if_default,
+        store_selector,
+        group,
+        id,
+        ttl_secs,
+        serde,
+        detect_collisions,
+        transparent,
+    } = parse_macro_input!(attr_tokens as KeyAttr);
+    let value_type = match value_type.resolve(&input.data) {
+        Ok(value_type) => value_type,
+        Err(error) => return error.into_compile_error().into(),
+    };
+
+    // Optional methods are only generated when requested, so we fall back to
+    // the trait's default implementation
+    let validate = validate.map(|validate| {
+        quote! {
+            fn validate(value: Self::Value) -> Self::Value {
+                #validate(value)
+            }
+        }
+    });
+    let store_selector = store_selector.map(|store_selector| {
+        quote! {
+            fn store_selector() -> persisted::StoreSelector {
+                persisted::StoreSelector::#store_selector
+            }
+        }
+    });
+    let group = group.map(|group| {
+        quote! {
+            fn group() -> ::core::option::Option<&'static str> {
+                ::core::option::Option::Some(#group)
+            }
+        }
+    });
+    let id = id.map(|id| {
+        quote! {
+            fn type_id() -> u64 {
+                #id
+            }
+        }
+    });
+    let ttl = ttl_secs.map(|ttl_secs| {
+        quote! {
+            fn ttl() -> ::core::option::Option<::core::time::Duration> {
+                ::core::option::Option::Some(
+                    ::core::time::Duration::from_secs(#ttl_secs),
+                )
+            }
+        }
+    });
+    let detect_collisions = detect_collisions.then(|| {
+        quote! {
+            fn key_hash(&self) -> ::core::option::Option<u64> {
+                ::core::option::Option::Some(persisted::hash_key(self))
+            }
+        }
+    });
+    let skip_if_default = skip_if_default.then(|| {
+        quote! {
+            fn skip_if_default() -> bool {
+                true
+            }
+
+            fn is_default(value: &Self::Value) -> bool {
+                *value == <Self::Value as ::core::default::Default>::default()
+            }
+        }
+    });
+
+    // Fail fast if the value type can't be round-tripped through serde, so
+    // the error points at the key rather than a store
+    let serde = serde.then(|| {
+        quote! {
+            const _: () = {
+                fn assert_serde<
+                    T: ::serde::Serialize + ::serde::de::DeserializeOwned,
+                >() {
+                }
+                // Generic so the value type can refer to the key's params
+                #[allow(dead_code)]
+                fn assert_value #impl_generics () #where_clause {
+                    assert_serde::<#value_type>();
+                }
+            };
+        }
+    });
+
+    // The name can't distinguish between instantiations of a generic key, so
+    // fall back to the default type_name, which includes type params.
+    // Lifetimes aren't part of the name, so they're fine. Transparent keys
+    // have no name at all, so generics don't matter.
+    let type_name = if transparent {
+        Some(quote! {
+            const TYPE_NAME: ::core::option::Option<&'static str> =
+                ::core::option::Option::Some("");
+        })
+    } else {
+        input.generics.type_params().next().is_none().then(|| {
+            quote! {
+                const TYPE_NAME: ::core::option::Option<&'static str> =
+                    ::core::option::Option::Some(::core::concat!(
+                        ::core::module_path!(),
+                        "::",
+                        ::core::stringify!(#name),
+                    ));
+            }
+        })
+    };
+
+    // Generic keys can't be registered, because there's no single type to
+    // register. Transparent keys all share the empty name, so registering them
+    // would report collisions.
+    let register =
+        (!transparent && input.generics.params.is_empty()).then(|| {
+            quote! {
+                persisted::register_key!(#name);
+            }
+        });
 
     quote! {
+        #serde
+        #register
+
         #[automatically_derived]
-        impl persisted::PersistedKey for #name {
+        impl #impl_generics persisted::PersistedKey
+            for #name #type_generics #where_clause
+        {
             type Value = #value_type;
 
-            fn type_name() -> &'static str {
-                std::any::type_name::<Self>()
-            }
+            #type_name
+            #validate
+            #skip_if_default
+            #store_selector
+            #group
+            #id
+            #ttl
+            #detect_collisions
         }
     }
     .into()
 }
+
+#[proc_macro_derive(PersistedContainer, attributes(persisted, persist))]
+pub fn persisted_container_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    container::derive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(KeyCodec)]
+pub fn key_codec_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    key_codec::derive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Contents of the `#[persisted(...)]` attribute. The first argument is always
+/// the value type, either given directly or as `value = <field>` to use the
+/// type of one of the key's fields. Optional arguments follow, separated by
+/// commas:
+///
+/// - `validate = <path>`: Function used to validate/sanitize loaded values
+/// - `skip_if_default`: Remove default values from the store instead of
+///   persisting them
+/// - `store_selector = <Left|Right>`: Inner store to use with `EitherStore`
+/// - `group = "<name>"`: Group name, for stores to bucket related keys
+/// - `id = <u64>`: Explicit numeric ID for the key type
+/// - `ttl_secs = <u64>`: Expiry time for values, used with `TtlStore`
+/// - `serde`: Assert that the value type implements `Serialize` and
+///   `DeserializeOwned`
+/// - `detect_collisions`: Detect live wrappers with equal keys, in debug builds
+/// - `transparent`: Use an empty type name, for stores whose format already
+///   includes the type
+struct KeyAttr {
+    value_type: ValueType,
+    validate: Option<syn::Path>,
+    skip_if_default: bool,
+    store_selector: Option<syn::Ident>,
+    group: Option<syn::LitStr>,
+    id: Option<syn::LitInt>,
+    ttl_secs: Option<syn::LitInt>,
+    serde: bool,
+    detect_collisions: bool,
+    transparent: bool,
+}
+
+impl Parse for KeyAttr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // `value = ...` can't be a type, so it's unambiguous
+        let value_type = if input.peek(syn::Ident)
+            && input.peek2(Token![=])
+            && input.fork().parse::<syn::Ident>()? == "value"
+        {
+            input.parse::<syn::Ident>()?;
+            input.parse::<Token![=]>()?;
+            ValueType::Field(input.parse()?)
+        } else {
+            ValueType::Type(input.parse()?)
+        };
+        let mut validate = None;
+        let mut skip_if_default = false;
+        let mut store_selector = None;
+        let mut group = None;
+        let mut id = None;
+        let mut ttl_secs = None;
+        let mut serde = false;
+        let mut detect_collisions = false;
+        let mut transparent = false;
+
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            // Allow trailing comma
+            if input.is_empty() {
+                break;
+            }
+
+            let ident: syn::Ident = input.parse()?;
+            if ident == "validate" {
+                input.parse::<Token![=]>()?;
+                validate = Some(input.parse()?);
+            } else if ident == "skip_if_default" {
+                skip_if_default = true;
+            } else if ident == "store_selector" {
+                input.parse::<Token![=]>()?;
+                let selector: syn::Ident = input.parse()?;
+                if selector != "Left" && selector != "Right" {
+                    return Err(syn::Error::new(
+                        selector.span(),
+                        "Expected `Left` or `Right`",
+                    ));
+                }
+                store_selector = Some(selector);
+            } else if ident == "group" {
+                input.parse::<Token![=]>()?;
+                group = Some(input.parse()?);
+            } else if ident == "id" {
+                input.parse::<Token![=]>()?;
+                let lit: syn::LitInt = input.parse()?;
+                // Make sure it fits in a u64
+                lit.base10_parse::<u64>()?;
+                id = Some(lit);
+            } else if ident == "ttl_secs" {
+                input.parse::<Token![=]>()?;
+                let lit: syn::LitInt = input.parse()?;
+                lit.base10_parse::<u64>()?;
+                ttl_secs = Some(lit);
+            } else if ident == "serde" {
+                serde = true;
+            } else if ident == "detect_collisions" {
+                detect_collisions = true;
+            } else if ident == "transparent" {
+                transparent = true;
+            } else {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    format!("Unknown `persisted` argument `{ident}`"),
+                ));
+            }
+        }
+
+        Ok(Self {
+            value_type,
+            validate,
+            skip_if_default,
+            store_selector,
+            group,
+            id,
+            ttl_secs,
+            serde,
+            detect_collisions,
+            transparent,
+        })
+    }
+}
+
+/// Value type of a key, from the first argument of `#[persisted(...)]`
+enum ValueType {
+    /// The value type is given explicitly
+    Type(syn::Type),
+    /// The value type is the type of a field in the key, given by name or
+    /// index
+    Field(syn::Member),
+}
+
+impl ValueType {
+    /// Get the value type, looking up the referenced field if necessary
+    fn resolve(self, data: &syn::Data) -> syn::Result<syn::Type> {
+        let member = match self {
+            Self::Type(value_type) => return Ok(value_type),
+            Self::Field(member) => member,
+        };
+        let syn::Data::Struct(data) = data else {
+            return Err(syn::Error::new_spanned(
+                member,
+                "`value = <field>` is only supported on structs",
+            ));
+        };
+        data.fields
+            .iter()
+            .enumerate()
+            .find(|(i, field)| match &member {
+                syn::Member::Named(name) => field.ident.as_ref() == Some(name),
+                syn::Member::Unnamed(index) => index.index as usize == *i,
+            })
+            .map(|(_, field)| field.ty.clone())
+            .ok_or_else(|| {
+                syn::Error::new_spanned(
+                    &member,
+                    format!("Field `{}` does not exist", quote! { #member }),
+                )
+            })
+    }
+}
@@ -0,0 +1,238 @@
+//! Builders for the persisted wrappers, for combining options that don't have
+//! dedicated constructors
+
+use crate::{
+    Persisted, PersistedContainer, PersistedKey, PersistedLazy, PersistedStore,
+};
+use core::{fmt::Debug, marker::PhantomData};
+
+/// Builder for [Persisted], created via [Persisted::builder]. Use this to
+/// combine options that don't have a dedicated constructor. For the common
+/// cases, [Persisted::new] and [Persisted::new_default] are simpler.
+///
+/// If no default value is given via [Self::default], the value type's
+/// [Default] implementation will be used.
+///
+/// ```
+/// use persisted::{Persisted, PersistedKey, PersistedStore};
+/// # struct Store;
+/// # impl<K: PersistedKey> PersistedStore<K> for Store {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(u32)]
+/// struct CountKey;
+///
+/// let count = Persisted::<Store, _>::builder(CountKey)
+///     .default(3)
+///     .deferred()
+///     .on_persist(|value| println!("Saved count {value}"))
+///     .build();
+/// assert_eq!(count.get(), 3);
+/// ```
+#[derive(derive_more::Debug)]
+#[debug(bound(K: Debug, D: Debug))]
+pub struct PersistedBuilder<S, K, D = NoDefault>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+{
+    #[debug(skip)] // Omit bound on S
+    backend: PhantomData<S>,
+    key: K,
+    default: D,
+    deferred: bool,
+    #[debug(skip)]
+    on_persist: Option<fn(&K::Value)>,
+}
+
+impl<S, K> PersistedBuilder<S, K>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+{
+    pub(crate) fn new(key: K) -> Self {
+        Self {
+            backend: PhantomData,
+            key,
+            default: NoDefault,
+            deferred: false,
+            on_persist: None,
+        }
+    }
+
+    /// Set the value to use if the store has no value for the key
+    pub fn default(
+        self,
+        default: K::Value,
+    ) -> PersistedBuilder<S, K, WithDefault<K::Value>> {
+        PersistedBuilder {
+            backend: self.backend,
+            key: self.key,
+            default: WithDefault(default),
+            deferred: self.deferred,
+            on_persist: self.on_persist,
+        }
+    }
+
+    /// Build the wrapper, using the value type's [Default] implementation if
+    /// the store has no value for the key
+    pub fn build(self) -> Persisted<S, K>
+    where
+        K::Value: Default,
+    {
+        self.default(K::Value::default()).build()
+    }
+}
+
+impl<S, K> PersistedBuilder<S, K, WithDefault<K::Value>>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+{
+    /// Build the wrapper
+    pub fn build(self) -> Persisted<S, K> {
+        let mut persisted = Persisted::new_deferred(self.key, self.default.0);
+        persisted.set_on_persist(self.on_persist);
+        if !self.deferred {
+            persisted.ensure_loaded();
+        }
+        persisted
+    }
+}
+
+impl<S, K, D> PersistedBuilder<S, K, D>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+{
+    /// Don't load from the store until the value is first accessed. See
+    /// [Persisted::new_deferred]
+    pub fn deferred(mut self) -> Self {
+        self.deferred = true;
+        self
+    }
+
+    /// Call a function with the new value whenever the wrapper persists
+    pub fn on_persist(mut self, on_persist: fn(&K::Value)) -> Self {
+        self.on_persist = Some(on_persist);
+        self
+    }
+}
+
+/// Builder for [PersistedLazy], created via [PersistedLazy::builder]. Use this
+/// to combine options that don't have a dedicated constructor. For the common
+/// cases, [PersistedLazy::new] and [PersistedLazy::new_default] are simpler.
+///
+/// If no container is given via [Self::container], the container type's
+/// [Default] implementation will be used.
+#[derive(derive_more::Debug)]
+#[debug(bound(K: Debug, D: Debug))]
+pub struct PersistedLazyBuilder<S, K, C, D = NoDefault>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    C: PersistedContainer<Value = K::Value>,
+{
+    #[debug(skip)] // Omit bound on S
+    backend: PhantomData<(S, C)>,
+    key: K,
+    container: D,
+    deferred: bool,
+    #[debug(skip)]
+    on_persist: Option<fn(&K::Value)>,
+}
+
+impl<S, K, C> PersistedLazyBuilder<S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    C: PersistedContainer<Value = K::Value>,
+{
+    pub(crate) fn new(key: K) -> Self {
+        Self {
+            backend: PhantomData,
+            key,
+            container: NoDefault,
+            deferred: false,
+            on_persist: None,
+        }
+    }
+
+    /// Set the initial container. If the store has a value for the key, it
+    /// will be restored into this container.
+    pub fn container(
+        self,
+        container: C,
+    ) -> PersistedLazyBuilder<S, K, C, WithDefault<C>> {
+        PersistedLazyBuilder {
+            backend: self.backend,
+            key: self.key,
+            container: WithDefault(container),
+            deferred: self.deferred,
+            on_persist: self.on_persist,
+        }
+    }
+
+    /// Build the wrapper, using the container type's [Default] implementation
+    /// as the initial container
+    pub fn build(self) -> PersistedLazy<S, K, C>
+    where
+        C: Default,
+    {
+        self.container(C::default()).build()
+    }
+}
+
+impl<S, K, C> PersistedLazyBuilder<S, K, C, WithDefault<C>>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    C: PersistedContainer<Value = K::Value>,
+{
+    /// Build the wrapper
+    pub fn build(self) -> PersistedLazy<S, K, C> {
+        let mut lazy = PersistedLazy::new_deferred(self.key, self.container.0);
+        lazy.set_on_persist(self.on_persist);
+        if !self.deferred {
+            lazy.ensure_loaded();
+        }
+        lazy
+    }
+}
+
+impl<S, K, C, D> PersistedLazyBuilder<S, K, C, D>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    C: PersistedContainer<Value = K::Value>,
+{
+    /// Don't load from the store until the container is first accessed. See
+    /// [PersistedLazy::new_deferred]
+    pub fn deferred(mut self) -> Self {
+        self.deferred = true;
+        self
+    }
+
+    /// Call a function with the new value whenever the wrapper persists
+    pub fn on_persist(mut self, on_persist: fn(&K::Value)) -> Self {
+        self.on_persist = Some(on_persist);
+        self
+    }
+}
+
+/// Builder state: no default value has been given. This is the initial state
+/// of [PersistedBuilder] and [PersistedLazyBuilder], and it only appears in
+/// type signatures; it can't be used to configure the builder. Building from
+/// this state uses the [Default] implementation of the value or container.
+#[derive(Debug)]
+pub struct NoDefault;
+
+/// Builder state: a default value has been given, via
+/// [PersistedBuilder::default] or [PersistedLazyBuilder::container]. This
+/// can't be constructed outside the builders; it only appears in type
+/// signatures, e.g. to store a configured builder in a struct field.
+#[derive(Debug)]
+pub struct WithDefault<T>(T);
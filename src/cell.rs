@@ -0,0 +1,129 @@
+use crate::{
+    collision::KeyRegistration, load, persist, PersistedKey, PersistedStore,
+};
+use core::{cell::Cell, fmt::Debug, marker::PhantomData};
+
+/// A persisted [Copy] value that can be mutated through a shared reference.
+/// The value is held in a [Cell], so reads copy the value out with
+/// [Self::get], and writes replace it with [Self::set], which persists the new
+/// value immediately. There's no guard and no borrow tracking, making this a
+/// lightweight option for primitives that need to be updated from `&self`
+/// contexts, such as a selected index in a UI callback.
+///
+/// Like [Cell], this is not [Sync], so it can't be shared between threads.
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{PersistedCopyCell, PersistedKey, PersistedStore};
+/// # struct Store;
+/// # impl<K: PersistedKey> PersistedStore<K> for Store {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(usize)]
+/// struct SelectedIndexKey;
+///
+/// struct SelectList {
+///     values: Vec<&'static str>,
+///     selected_index: PersistedCopyCell<Store, SelectedIndexKey>,
+/// }
+///
+/// impl SelectList {
+///     /// Only needs `&self`
+///     fn select_next(&self) {
+///         let index = self.selected_index.get();
+///         self.selected_index.set((index + 1) % self.values.len());
+///     }
+/// }
+///
+/// let list = SelectList {
+///     values: vec!["a", "b", "c"],
+///     selected_index: PersistedCopyCell::new(SelectedIndexKey, 0),
+/// };
+/// list.select_next();
+/// assert_eq!(list.selected_index.get(), 1);
+/// ```
+#[derive(derive_more::Debug)]
+#[debug(bound(K::Value: Debug))]
+pub struct PersistedCopyCell<S, K>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    K::Value: Copy,
+{
+    #[debug(skip)] // Omit bound on S
+    backend: PhantomData<S>,
+    key: K,
+    value: Cell<K::Value>,
+    #[debug(skip)]
+    registration: KeyRegistration,
+}
+
+impl<S, K> PersistedCopyCell<S, K>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    K::Value: Copy,
+{
+    /// Initialize a new persisted cell. The latest persisted value will be
+    /// loaded from the store and passed through [PersistedKey::validate]. If
+    /// missing, use the given default instead.
+    pub fn new(key: K, default: K::Value) -> Self {
+        let registration = KeyRegistration::new(&key);
+        let value = load::<S, K>(&key).unwrap_or(default);
+        Self {
+            backend: PhantomData,
+            key,
+            value: Cell::new(value),
+            registration,
+        }
+    }
+
+    /// Initialize a new persisted cell. The latest persisted value will be
+    /// loaded from the store. If missing, use the value type's [Default]
+    /// implementation instead.
+    pub fn new_default(key: K) -> Self
+    where
+        K::Value: Default,
+    {
+        Self::new(key, K::Value::default())
+    }
+
+    /// Get the key identifying this value in the store
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Get a copy of the inner value
+    #[inline]
+    pub fn get(&self) -> K::Value {
+        self.value.get()
+    }
+
+    /// Replace the inner value, and persist the new value to the store
+    pub fn set(&self, value: K::Value) {
+        let old = self.value.replace(value);
+        persist::<S, K>(&self.key, Some(&old), &value);
+    }
+
+    /// Unwrap the inner value
+    pub fn into_inner(self) -> K::Value {
+        self.registration.release();
+        self.value.into_inner()
+    }
+}
+
+// Needed to omit Default bound on S
+impl<S, K> Default for PersistedCopyCell<S, K>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey + Default,
+    K::Value: Copy + Default,
+{
+    fn default() -> Self {
+        Self::new_default(K::default())
+    }
+}
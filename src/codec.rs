@@ -0,0 +1,354 @@
+use crate::{PersistedKey, PersistedStore};
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+/// A serialization format, used by [CodecStore] to convert keys and values to
+/// bytes. A codec is generic over the type being encoded, so one codec can
+/// handle every key and value type that its format supports. Implement this
+/// on a marker type.
+///
+/// Provided codecs (each behind the feature of the same name):
+/// - `serde-json`: [JsonCodec]
+/// - `ron`: [RonCodec]
+/// - `bincode`: [BincodeCodec]
+pub trait Codec<T> {
+    /// Encode a key or value to bytes. Return `None` if the value can't be
+    /// represented in this format. Encoding typically runs while a guard is
+    /// dropped, so this should never panic.
+    fn encode(value: &T) -> Option<Vec<u8>>;
+
+    /// Decode a value from bytes. Return `None` if the bytes are invalid,
+    /// e.g. because the value type changed since it was stored.
+    fn decode(bytes: &[u8]) -> Option<T>;
+}
+
+/// A key-value store of raw bytes, used as the backend of a [CodecStore].
+/// Values are identified by [PersistedKey::type_name] plus the encoded key.
+pub trait ByteStore {
+    /// Load the bytes for a key. Return `None` if the value isn't present.
+    fn load_bytes(type_name: &str, key: &[u8]) -> Option<Vec<u8>>;
+
+    /// Persist the bytes for a key
+    fn store_bytes(type_name: &str, key: &[u8], value: Vec<u8>);
+
+    /// Remove the bytes for a key. See [PersistedStore::remove_persisted].
+    /// The default implementation does nothing.
+    fn remove_bytes(_type_name: &str, _key: &[u8]) {}
+
+    /// Called by [CodecStore] when the stored bytes for a key can't be
+    /// decoded, e.g. because the value type changed since it was stored. The
+    /// value is treated as missing. The default implementation does nothing.
+    fn on_decode_error(_type_name: &str, _key: &[u8]) {}
+
+    /// Called by [CodecStore] when a key or value can't be encoded. The load
+    /// or write is skipped. The default implementation does nothing.
+    fn on_encode_error(_type_name: &str) {}
+}
+
+/// A store that separates the storage mechanism from the serialization
+/// format. Keys and values are encoded to bytes with the codec `C`, then
+/// stored in the byte store `B`. To switch formats, just switch the codec.
+///
+/// If a stored value fails to decode, it's reported via
+/// [ByteStore::on_decode_error] and treated as missing. If a key or value
+/// fails to encode, it's reported via [ByteStore::on_encode_error] and the
+/// write is skipped.
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{ByteStore, Codec, CodecStore, Persisted, PersistedKey};
+/// use std::{cell::RefCell, collections::HashMap};
+///
+/// /// Encode little-endian integers
+/// struct LeCodec;
+///
+/// impl Codec<u32> for LeCodec {
+///     fn encode(value: &u32) -> Option<Vec<u8>> {
+///         Some(value.to_le_bytes().to_vec())
+///     }
+///
+///     fn decode(bytes: &[u8]) -> Option<u32> {
+///         Some(u32::from_le_bytes(bytes.try_into().ok()?))
+///     }
+/// }
+///
+/// struct MemoryStore;
+///
+/// thread_local! {
+///     static BYTES: RefCell<HashMap<(String, Vec<u8>), Vec<u8>>> =
+///         Default::default();
+/// }
+///
+/// impl ByteStore for MemoryStore {
+///     fn load_bytes(type_name: &str, key: &[u8]) -> Option<Vec<u8>> {
+///         BYTES.with(|map| {
+///             map.borrow().get(&(type_name.into(), key.into())).cloned()
+///         })
+///     }
+///
+///     fn store_bytes(type_name: &str, key: &[u8], value: Vec<u8>) {
+///         BYTES.with(|map| {
+///             map.borrow_mut().insert((type_name.into(), key.into()), value)
+///         });
+///     }
+/// }
+///
+/// /// Volume for an audio channel
+/// #[derive(PersistedKey)]
+/// #[persisted(u32)]
+/// struct VolumeKey(u32);
+///
+/// impl Codec<VolumeKey> for LeCodec {
+///     fn encode(key: &VolumeKey) -> Option<Vec<u8>> {
+///         Self::encode(&key.0)
+///     }
+///
+///     fn decode(bytes: &[u8]) -> Option<VolumeKey> {
+///         Self::decode(bytes).map(VolumeKey)
+///     }
+/// }
+///
+/// type Store = CodecStore<MemoryStore, LeCodec>;
+///
+/// let mut volume = Persisted::<Store, _>::new(VolumeKey(1), 50);
+/// *volume.get_mut() = 80;
+/// let volume = Persisted::<Store, _>::new(VolumeKey(1), 50);
+/// assert_eq!(volume.get(), 80);
+/// ```
+#[derive(Debug)]
+pub struct CodecStore<B, C> {
+    phantom: PhantomData<(B, C)>,
+}
+
+impl<K, B, C> PersistedStore<K> for CodecStore<B, C>
+where
+    K: PersistedKey,
+    B: ByteStore,
+    C: Codec<K> + Codec<K::Value>,
+{
+    fn load_persisted(key: &K) -> Option<K::Value> {
+        let bytes = B::load_bytes(K::type_name(), &Self::encode_key(key)?)?;
+        let value = <C as Codec<K::Value>>::decode(&bytes);
+        if value.is_none() {
+            Self::on_load_error(key);
+        }
+        value
+    }
+
+    fn store_persisted(key: &K, value: &K::Value) {
+        let Some(key) = Self::encode_key(key) else {
+            return;
+        };
+        match C::encode(value) {
+            Some(value) => B::store_bytes(K::type_name(), &key, value),
+            None => B::on_encode_error(K::type_name()),
+        }
+    }
+
+    fn remove_persisted(key: &K) {
+        if let Some(key) = Self::encode_key(key) {
+            B::remove_bytes(K::type_name(), &key);
+        }
+    }
+
+    fn on_load_error(key: &K) {
+        if let Some(key) = Self::encode_key(key) {
+            B::on_decode_error(K::type_name(), &key);
+        }
+    }
+}
+
+impl<B: ByteStore, C> CodecStore<B, C> {
+    /// Encode a key, reporting an error to the byte store if it fails
+    fn encode_key<K>(key: &K) -> Option<Vec<u8>>
+    where
+        K: PersistedKey,
+        C: Codec<K>,
+    {
+        let encoded = C::encode(key);
+        if encoded.is_none() {
+            B::on_encode_error(K::type_name());
+        }
+        encoded
+    }
+}
+
+/// A [Codec] for JSON, via `serde_json` (requires the `serde-json` feature)
+#[cfg(feature = "serde-json")]
+#[derive(Debug)]
+pub struct JsonCodec;
+
+#[cfg(feature = "serde-json")]
+impl<T> Codec<T> for JsonCodec
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    fn encode(value: &T) -> Option<Vec<u8>> {
+        serde_json::to_vec(value).ok()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<T> {
+        serde_json::from_slice(bytes).ok()
+    }
+}
+
+/// A [Codec] for [RON](https://github.com/ron-rs/ron) (requires the `ron`
+/// feature)
+#[cfg(feature = "ron")]
+#[derive(Debug)]
+pub struct RonCodec;
+
+#[cfg(feature = "ron")]
+impl<T> Codec<T> for RonCodec
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    fn encode(value: &T) -> Option<Vec<u8>> {
+        ron::to_string(value)
+            .ok()
+            .map(alloc::string::String::into_bytes)
+    }
+
+    fn decode(bytes: &[u8]) -> Option<T> {
+        ron::de::from_bytes(bytes).ok()
+    }
+}
+
+/// A [Codec] for [bincode](https://github.com/bincode-org/bincode), a compact
+/// binary format (requires the `bincode` feature)
+#[cfg(feature = "bincode")]
+#[derive(Debug)]
+pub struct BincodeCodec;
+
+#[cfg(feature = "bincode")]
+impl<T> Codec<T> for BincodeCodec
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    fn encode(value: &T) -> Option<Vec<u8>> {
+        bincode::serialize(value).ok()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<T> {
+        bincode::deserialize(bytes).ok()
+    }
+}
+
+#[cfg(all(test, feature = "serde-json", feature = "ron", feature = "bincode"))]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use std::{
+        cell::{Cell, RefCell},
+        collections::HashMap,
+        string::{String, ToString},
+        thread_local,
+    };
+
+    #[derive(Serialize, Deserialize)]
+    struct NameKey(u32);
+
+    impl PersistedKey for NameKey {
+        type Value = Name;
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Name {
+        first: String,
+        last: String,
+    }
+
+    struct MemoryStore;
+
+    type ByteMap = HashMap<(String, Vec<u8>), Vec<u8>>;
+
+    thread_local! {
+        static BYTES: RefCell<ByteMap> = RefCell::default();
+        static DECODE_ERRORS: Cell<usize> = const { Cell::new(0) };
+        static ENCODE_ERRORS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    impl ByteStore for MemoryStore {
+        fn load_bytes(type_name: &str, key: &[u8]) -> Option<Vec<u8>> {
+            BYTES.with(|map| {
+                map.borrow()
+                    .get(&(type_name.to_string(), key.to_vec()))
+                    .cloned()
+            })
+        }
+
+        fn store_bytes(type_name: &str, key: &[u8], value: Vec<u8>) {
+            BYTES.with(|map| {
+                map.borrow_mut()
+                    .insert((type_name.to_string(), key.to_vec()), value)
+            });
+        }
+
+        fn on_decode_error(_type_name: &str, _key: &[u8]) {
+            DECODE_ERRORS.set(DECODE_ERRORS.get() + 1);
+        }
+
+        fn on_encode_error(_type_name: &str) {
+            ENCODE_ERRORS.set(ENCODE_ERRORS.get() + 1);
+        }
+    }
+
+    /// Round trip a value through a codec
+    fn round_trip<C>()
+    where
+        C: Codec<NameKey> + Codec<Name>,
+    {
+        type Store<C> = CodecStore<MemoryStore, C>;
+        let name = Name {
+            first: "Susan".into(),
+            last: "Smith".into(),
+        };
+        Store::<C>::store_persisted(&NameKey(1), &name);
+        assert_eq!(Store::<C>::load_persisted(&NameKey(1)), Some(name));
+        assert_eq!(Store::<C>::load_persisted(&NameKey(2)), None);
+    }
+
+    #[test]
+    fn test_codecs() {
+        round_trip::<JsonCodec>();
+        round_trip::<RonCodec>();
+        round_trip::<BincodeCodec>();
+    }
+
+    #[test]
+    fn test_decode_error() {
+        BYTES.with(|map| {
+            map.borrow_mut().insert(
+                (
+                    NameKey::type_name().to_string(),
+                    JsonCodec::encode(&3u32).unwrap(),
+                ),
+                b"not json".to_vec(),
+            )
+        });
+        assert_eq!(
+            CodecStore::<MemoryStore, JsonCodec>::load_persisted(&NameKey(3)),
+            None
+        );
+        assert_eq!(DECODE_ERRORS.get(), 1);
+    }
+
+    /// A value that can't be encoded is reported, and the write is skipped
+    #[test]
+    fn test_encode_error() {
+        #[derive(Serialize, Deserialize)]
+        struct TableKey;
+
+        impl PersistedKey for TableKey {
+            /// JSON maps must have string keys
+            type Value = HashMap<(u8, u8), u8>;
+        }
+
+        type Store = CodecStore<MemoryStore, JsonCodec>;
+        Store::store_persisted(&TableKey, &HashMap::from([((1, 2), 3)]));
+        assert_eq!(ENCODE_ERRORS.get(), 1);
+        assert_eq!(Store::load_persisted(&TableKey), None);
+        assert_eq!(DECODE_ERRORS.get(), 0);
+    }
+}
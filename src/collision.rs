@@ -0,0 +1,122 @@
+//! Detection of live wrappers with equal keys. See [PersistedKey::key_hash].
+
+use crate::PersistedKey;
+#[cfg(all(debug_assertions, feature = "std"))]
+use std::{
+    collections::HashSet,
+    sync::{Mutex, OnceLock, PoisonError},
+    thread::{self, ThreadId},
+};
+
+/// Registration of a live wrapper's key. Each wrapper holds one of these for
+/// its lifetime, and the key is unregistered when it's dropped. Collisions are
+/// only detected between wrappers created on the same thread, but a wrapper
+/// can be dropped on any thread. Detection only runs in debug builds with the
+/// `std` feature. Otherwise, this is a no-op.
+#[derive(Debug, Default)]
+pub(crate) struct KeyRegistration {
+    /// Creating thread, type name and hash of the registered key. `None` if
+    /// the key opted out
+    #[cfg(all(debug_assertions, feature = "std"))]
+    id: Option<KeyId>,
+}
+
+/// Creating thread, type name and hash of a registered key
+#[cfg(all(debug_assertions, feature = "std"))]
+type KeyId = (ThreadId, &'static str, u64);
+
+impl KeyRegistration {
+    /// Register a key. Panic if an equal key is already registered
+    #[cfg_attr(
+        not(all(debug_assertions, feature = "std")),
+        allow(unused_variables)
+    )]
+    pub(crate) fn new<K: PersistedKey>(key: &K) -> Self {
+        #[cfg(all(debug_assertions, feature = "std"))]
+        {
+            let id = key
+                .key_hash()
+                .map(|hash| (thread::current().id(), K::type_name(), hash));
+            if let Some(id) = id {
+                // Release the lock before panicking
+                let is_new = live_keys().insert(id);
+                assert!(
+                    is_new,
+                    "Multiple live persisted wrappers with equal keys of type \
+                    `{}`. They will overwrite each other's values in the store",
+                    id.1
+                );
+            }
+            Self { id }
+        }
+        #[cfg(not(all(debug_assertions, feature = "std")))]
+        Self {}
+    }
+
+    /// Unregister the key, so another wrapper can claim it
+    pub(crate) fn release(self) {}
+}
+
+#[cfg(all(debug_assertions, feature = "std"))]
+impl Drop for KeyRegistration {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            // The wrapper may have been sent to another thread, so this
+            // unregisters by the creating thread rather than the current one
+            live_keys().remove(&id);
+        }
+    }
+}
+
+/// Get every registered key, across all threads. Wrappers are `Send`, so
+/// this can't be thread-local
+#[cfg(all(debug_assertions, feature = "std"))]
+fn live_keys() -> std::sync::MutexGuard<'static, HashSet<KeyId>> {
+    static LIVE_KEYS: OnceLock<Mutex<HashSet<KeyId>>> = OnceLock::new();
+    LIVE_KEYS
+        .get_or_init(Mutex::default)
+        .lock()
+        // A collision panic doesn't leave the set in a bad state
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(all(test, debug_assertions, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::hash_key;
+
+    #[derive(Hash)]
+    struct ToggleKey(u32);
+
+    impl PersistedKey for ToggleKey {
+        type Value = bool;
+
+        fn key_hash(&self) -> Option<u64> {
+            Some(hash_key(self))
+        }
+    }
+
+    #[test]
+    fn test_key_registration() {
+        let first = KeyRegistration::new(&ToggleKey(1));
+        let _other = KeyRegistration::new(&ToggleKey(2));
+        // Slot is freed when the first wrapper is dropped
+        drop(first);
+        let _first = KeyRegistration::new(&ToggleKey(1));
+    }
+
+    #[test]
+    fn test_release_on_other_thread() {
+        let first = KeyRegistration::new(&ToggleKey(1));
+        thread::spawn(move || drop(first)).join().unwrap();
+        // Slot was freed on this thread
+        let _first = KeyRegistration::new(&ToggleKey(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Multiple live persisted wrappers")]
+    fn test_key_collision() {
+        let _first = KeyRegistration::new(&ToggleKey(1));
+        let _second = KeyRegistration::new(&ToggleKey(1));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{global::lock, KeyCodec, PersistedStore};
+use core::any::Any;
+use std::{boxed::Box, collections::BTreeMap, string::String, sync::Mutex};
+
+/// Type-erased values of every key, keyed by [KeyCodec::storage_key]
+type Values = BTreeMap<(&'static str, String), Box<dyn Any + Send>>;
+
+/// All values in the [DynStore]
+static VALUES: Mutex<Values> = Mutex::new(BTreeMap::new());
+
+/// An in-memory store that holds values of *any* type, without the store
+/// needing to know the value types up front. This is useful in plugin
+/// architectures, where key and value types are defined at runtime by code the
+/// store doesn't know about. Values are kept in memory for the life of the
+/// process, so this doesn't persist anything between runs on its own.
+///
+/// Values are stored as [Any], keyed by [KeyCodec::storage_key], and
+/// downcast when loaded. If the stored value has a different type than the
+/// key's value type, e.g. because two plugins used the same key name, it's
+/// treated as missing. All `DynStore`s share the same global storage, which
+/// can be accessed from any thread.
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{DynStore, KeyCodec, Persisted, PersistedKey};
+///
+/// #[derive(KeyCodec, PersistedKey)]
+/// #[persisted(u32)]
+/// struct VolumeKey;
+///
+/// #[derive(KeyCodec, PersistedKey)]
+/// #[persisted(String)]
+/// struct NameKey;
+///
+/// *Persisted::<DynStore, _>::new(VolumeKey, 50).get_mut() = 80;
+/// *Persisted::<DynStore, _>::new_default(NameKey).get_mut() = "Fred".into();
+///
+/// assert_eq!(Persisted::<DynStore, _>::new(VolumeKey, 50).get(), 80);
+/// assert_eq!(
+///     Persisted::<DynStore, _>::new_default(NameKey).value(),
+///     "Fred"
+/// );
+/// ```
+#[derive(Debug)]
+pub struct DynStore;
+
+impl<K> PersistedStore<K> for DynStore
+where
+    K: KeyCodec,
+    K::Value: Clone + Send + 'static,
+{
+    fn load_persisted(key: &K) -> Option<K::Value> {
+        let values = lock(&VALUES);
+        let value = values.get(&key.storage_key())?;
+        value.downcast_ref::<K::Value>().cloned()
+    }
+
+    fn store_persisted(key: &K, value: &K::Value) {
+        lock(&VALUES).insert(key.storage_key(), Box::new(value.clone()));
+    }
+
+    fn remove_persisted(key: &K) {
+        lock(&VALUES).remove(&key.storage_key());
+    }
+
+    fn contains(key: &K) -> bool {
+        lock(&VALUES)
+            .get(&key.storage_key())
+            .is_some_and(|value| value.is::<K::Value>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Persisted, PersistedKey};
+    use std::{string::ToString, vec::Vec};
+
+    struct CountKey(u8);
+
+    impl PersistedKey for CountKey {
+        type Value = u32;
+
+        fn type_name() -> &'static str {
+            "dyn::CountKey"
+        }
+    }
+
+    impl KeyCodec for CountKey {
+        fn key_id(&self) -> String {
+            self.0.to_string()
+        }
+    }
+
+    /// Same name as [CountKey], but a different value type
+    struct ListKey(u8);
+
+    impl PersistedKey for ListKey {
+        type Value = Vec<&'static str>;
+
+        fn type_name() -> &'static str {
+            "dyn::CountKey"
+        }
+    }
+
+    impl KeyCodec for ListKey {
+        fn key_id(&self) -> String {
+            self.0.to_string()
+        }
+    }
+
+    #[test]
+    fn test_dyn_store() {
+        *Persisted::<DynStore, _>::new(CountKey(1), 0).get_mut() = 3;
+        *Persisted::<DynStore, _>::new_default(ListKey(2)).get_mut() =
+            ["a", "b"].into();
+
+        assert_eq!(DynStore::load_persisted(&CountKey(1)), Some(3));
+        assert_eq!(
+            DynStore::load_persisted(&ListKey(2)),
+            Some(["a", "b"].into())
+        );
+
+        // Mismatched types are treated as missing
+        assert_eq!(DynStore::load_persisted(&CountKey(2)), None);
+        assert!(!DynStore::contains(&ListKey(1)));
+        let list = Persisted::<DynStore, _>::new_default(ListKey(1));
+        assert!(list.value().is_empty());
+
+        DynStore::remove_persisted(&CountKey(1));
+        assert_eq!(DynStore::load_persisted(&CountKey(1)), None);
+    }
+}
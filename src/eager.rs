@@ -1,5 +1,16 @@
-use crate::{PersistedKey, PersistedStore};
-use core::{fmt::Debug, marker::PhantomData, ops::DerefMut};
+use crate::{
+    collision::KeyRegistration, compare_and_swap, load, persist,
+    PersistedBuilder, PersistedContainer, PersistedKey, PersistedLazy,
+    PersistedStore,
+};
+use core::{
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    mem,
+    ops::DerefMut,
+    ptr,
+};
 use derive_more::{Deref, Display};
 
 /// A wrapper that will automatically persist its contained value to the
@@ -16,12 +27,18 @@ use derive_more::{Deref, Display};
 ///
 /// ## Accessing
 ///
-/// The inner value can be accessed immutably via [Deref]. To get mutable
-/// access, use [Persisted::get_mut]. This wrapper method returns a guard that
-/// implements [DerefMut] (similar to [RefMut](std::cell::RefMut) or
-/// [MutexGuard](std::sync::MutexGuard), without the internal mutability). When
-/// your mutable access is complete, this wrapper will be dropped and the value,
-/// which presumably was changed, will be persisted to the store.
+/// The inner value can be accessed immutably via [Persisted::value] (or
+/// [Persisted::get] for [Copy] values). [Deref] and [AsRef] are also
+/// implemented, but the explicit accessors are preferred for clarity, since
+/// deref coercion can make it ambiguous whether a method is being called on the
+/// wrapper or the inner value.
+///
+/// To get mutable access, use [Persisted::get_mut]. This wrapper method returns
+/// a guard that implements [DerefMut] (similar to
+/// [RefMut](core::cell::RefMut) or `MutexGuard`, without
+/// the internal mutability). When your mutable access is complete, this
+/// wrapper will be dropped and the value, which presumably was changed, will be
+/// persisted to the store.
 ///
 /// ## Cloning
 ///
@@ -43,6 +60,18 @@ where
     key: K,
     #[deref]
     value: K::Value,
+    /// Did the initial value come from the store?
+    was_loaded: bool,
+    /// Has the store not been checked yet? Only `true` for deferred values
+    /// that haven't been loaded yet.
+    load_pending: bool,
+    /// Called with the new value after each persist
+    #[debug(skip)]
+    on_persist: Option<fn(&K::Value)>,
+    /// Only persist on explicit calls to [Self::persist], not after mutation
+    manual: bool,
+    #[debug(skip)]
+    registration: KeyRegistration,
 }
 
 impl<S, K> Persisted<S, K>
@@ -51,18 +80,100 @@ where
     K: PersistedKey,
 {
     /// Initialize a new persisted value. The latest persisted value will be
-    /// loaded from the store. If missing, use the given default instead.
+    /// loaded from the store and passed through [PersistedKey::validate]. If
+    /// missing, use the given default instead.
     pub fn new(key: K, default: K::Value) -> Self {
-        // Fetch persisted value from the backend
-        let value = S::load_persisted(&key).unwrap_or(default);
+        Self::new_with(key, || default)
+    }
 
+    /// Initialize a new persisted value. The latest persisted value will be
+    /// loaded from the store and passed through [PersistedKey::validate]. If
+    /// missing, call the given function to compute a default instead. The
+    /// function is *only* called if the store has no value, so this is useful
+    /// for defaults that are expensive to build.
+    pub fn new_with(key: K, default: impl FnOnce() -> K::Value) -> Self {
+        let registration = KeyRegistration::new(&key);
+        let loaded = load::<S, K>(&key);
+        let was_loaded = loaded.is_some();
         Self {
             backend: PhantomData,
+            registration,
             key,
-            value,
+            value: loaded.unwrap_or_else(default),
+            was_loaded,
+            load_pending: false,
+            on_persist: None,
+            manual: false,
+        }
+    }
+
+    /// Initialize a new persisted value *without* loading from the store. The
+    /// value will be the given default until the persisted value is loaded,
+    /// which happens on the first call to either [Self::ensure_loaded] or
+    /// [Self::get_mut]. This is useful when creating many values, of which
+    /// only a few will actually be accessed, to avoid unnecessary loads.
+    ///
+    /// Note that [Deref] access does **not** trigger a load, because it only
+    /// has immutable access to the wrapper.
+    pub fn new_deferred(key: K, default: K::Value) -> Self {
+        Self {
+            backend: PhantomData,
+            registration: KeyRegistration::new(&key),
+            key,
+            value: default,
+            was_loaded: false,
+            load_pending: true,
+            on_persist: None,
+            manual: false,
         }
     }
 
+    /// Initialize a persisted value that **must** already exist in the store.
+    /// The latest persisted value will be loaded from the store and passed
+    /// through [PersistedKey::validate]. If missing, return `None` instead of
+    /// falling back to a default. This is useful when a missing value
+    /// indicates a bug, e.g. a migration that should have already run. Use
+    /// [Option::expect] to panic on a missing value.
+    pub fn new_loaded(key: K) -> Option<Self> {
+        let value = load::<S, K>(&key)?;
+        Some(Self::from_parts(key, value, true, None))
+    }
+
+    /// Initialize a new persisted value that is **only** persisted by explicit
+    /// calls to [Self::persist]. The latest persisted value is loaded just as
+    /// in [Self::new], but mutations via [Self::get_mut] don't write to the
+    /// store. This is useful for mutating a value many times in a hot loop,
+    /// then persisting once at the end.
+    ///
+    /// **Any changes made since the last call to [Self::persist] are lost if
+    /// the wrapper is dropped without persisting.**
+    pub fn new_manual(key: K, default: K::Value) -> Self {
+        let mut persisted = Self::new(key, default);
+        persisted.manual = true;
+        persisted
+    }
+
+    /// Initialize a new persisted value with the key type's [Default], using
+    /// the given value as the default. This is equivalent to [Self::new], so
+    /// **if the store has a value for the key, it will be loaded and the given
+    /// value discarded.** This is mostly useful for unit keys, to cut down on
+    /// noise when initializing structs.
+    ///
+    /// This isn't provided as a `From<K::Value>` impl because it would conflict
+    /// with the blanket `From<T> for T` impl.
+    pub fn from_value(default: K::Value) -> Self
+    where
+        K: Default,
+    {
+        Self::new(K::default(), default)
+    }
+
+    /// Create a builder, to combine options that don't have a dedicated
+    /// constructor. See [PersistedBuilder].
+    pub fn builder(key: K) -> PersistedBuilder<S, K> {
+        PersistedBuilder::new(key)
+    }
+
     /// Initialize a new persisted value. The latest persisted value will be
     /// loaded from the store. If missing, use the value type's [Default]
     /// implementation instead.
@@ -70,16 +181,320 @@ where
     where
         K::Value: Default,
     {
-        Self::new(key, K::Value::default())
+        Self::new_with(key, K::Value::default)
+    }
+
+    /// Assemble a loaded value from its parts, without accessing the store
+    pub(crate) fn from_parts(
+        key: K,
+        value: K::Value,
+        was_loaded: bool,
+        on_persist: Option<fn(&K::Value)>,
+    ) -> Self {
+        Self {
+            backend: PhantomData,
+            registration: KeyRegistration::new(&key),
+            key,
+            value,
+            was_loaded,
+            load_pending: false,
+            on_persist,
+            manual: false,
+        }
+    }
+
+    pub(crate) fn set_on_persist(&mut self, on_persist: Option<fn(&K::Value)>) {
+        self.on_persist = on_persist;
+    }
+
+    /// Get the key identifying this value in the store
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Get the [type name](PersistedKey::type_name) of the key. This is
+    /// equivalent to `K::type_name()`, but doesn't require naming the key
+    /// type.
+    pub fn type_name(&self) -> &'static str {
+        K::type_name()
+    }
+
+    /// Get a reference to the inner value. This is equivalent to [Deref], but
+    /// more explicit.
+    pub fn value(&self) -> &K::Value {
+        &self.value
+    }
+
+    /// Get a copy of the inner value
+    #[inline]
+    pub fn get(&self) -> K::Value
+    where
+        K::Value: Copy,
+    {
+        self.value
+    }
+
+    /// Get a clone of the inner value. For [Copy] values, [Self::get] is
+    /// equivalent.
+    pub fn get_cloned(&self) -> K::Value
+    where
+        K::Value: Clone,
+    {
+        self.value.clone()
+    }
+
+    /// Was the initial value of this wrapper loaded from the store? If `false`,
+    /// the store had no value for the key at initialization, so the given
+    /// default was used instead. Mutations after initialization don't affect
+    /// this.
+    pub fn was_loaded(&self) -> bool {
+        self.was_loaded
+    }
+
+    /// Load the latest persisted value from the store, if it hasn't been
+    /// loaded yet. This only has an effect for values created with
+    /// [Self::new_deferred]; all other constructors load immediately.
+    pub fn ensure_loaded(&mut self) {
+        if self.load_pending {
+            self.load_pending = false;
+            // Fetch persisted value from the backend
+            if let Some(value) = load::<S, K>(&self.key) {
+                self.value = value;
+                self.was_loaded = true;
+            }
+        }
+    }
+
+    /// Persist the current value to the store immediately, regardless of
+    /// whether it has changed. Typically persistence is handled automatically
+    /// by [Self::get_mut], but this is useful for explicit "save now" flows.
+    ///
+    /// If this value was created with [Self::new_deferred] and hasn't been
+    /// loaded yet, this will persist the default value, overwriting whatever
+    /// is in the store.
+    pub fn persist(&self) {
+        if !persist::<S, K>(&self.key, None, &self.value) {
+            return;
+        }
+        if let Some(on_persist) = self.on_persist {
+            on_persist(&self.value);
+        }
+    }
+
+    /// Reload the latest value from the store, modify it, then persist it. If
+    /// the store has no value, the current in-memory value is modified
+    /// instead. Use this when other processes may write to the same store, to
+    /// avoid overwriting their changes with a stale in-memory value.
+    ///
+    /// Note that this is **not** atomic: another writer could still modify the
+    /// value between the load and the store. True atomicity requires locking
+    /// at the store level, which this crate can't provide.
+    pub fn update_from_store(&mut self, f: impl FnOnce(&mut K::Value)) {
+        self.load_pending = false;
+        if let Some(value) = load::<S, K>(&self.key) {
+            self.value = value;
+        }
+        f(&mut self.value);
+        self.persist();
+    }
+
+    /// Persist a new value, but only if the value currently in the store
+    /// equals `expected` (`None` meaning the store has no value). If the
+    /// value was persisted, it also replaces the in-memory value and this
+    /// returns `true`. Otherwise, nothing changes and this returns `false`;
+    /// use [Self::revert] to pick up the stored value before trying again.
+    /// Whether this is atomic depends on the store. See
+    /// [PersistedStore::compare_and_swap].
+    ///
+    /// ```
+    /// use persisted::{Persisted, PersistedKey, PersistedStore};
+    /// # struct Store;
+    /// # impl<K: PersistedKey> PersistedStore<K> for Store {
+    /// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+    /// #     fn store_persisted(_: &K, _: &K::Value) {}
+    /// # }
+    ///
+    /// #[derive(PersistedKey)]
+    /// #[persisted(u32)]
+    /// struct LaunchCountKey;
+    ///
+    /// let mut count = Persisted::<Store, _>::new(LaunchCountKey, 0);
+    /// let expected = count.peek_stored();
+    /// let next = expected.unwrap_or_default() + 1;
+    /// assert!(count.try_set_if(expected.as_ref(), next));
+    /// assert_eq!(count.get(), 1);
+    /// ```
+    pub fn try_set_if(
+        &mut self,
+        expected: Option<&K::Value>,
+        new: K::Value,
+    ) -> bool
+    where
+        K::Value: PartialEq,
+    {
+        if !compare_and_swap::<S, K>(&self.key, expected, &new) {
+            return false;
+        }
+        self.value = new;
+        self.load_pending = false;
+        if let Some(on_persist) = self.on_persist {
+            on_persist(&self.value);
+        }
+        true
+    }
+
+    /// Get the value currently in the store for this key, without modifying
+    /// the in-memory value. The loaded value is passed through
+    /// [PersistedKey::validate]. Return `None` if the store has no value.
+    /// This is useful for comparing unsaved changes against the stored value.
+    pub fn peek_stored(&self) -> Option<K::Value> {
+        load::<S, K>(&self.key)
+    }
+
+    /// Discard the in-memory value, replacing it with the value currently in
+    /// the store (see [Self::peek_stored]). Nothing is persisted. If the store
+    /// has no value, the in-memory value is left unchanged and this returns
+    /// `false`.
+    pub fn revert(&mut self) -> bool {
+        match self.peek_stored() {
+            Some(value) => {
+                self.value = value;
+                self.load_pending = false;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Swap values with another wrapper of the same key type, then persist
+    /// both under their respective keys. Both values are loaded first, if
+    /// deferred. If the two wrappers have the same key (which you should
+    /// avoid anyway), the value from `other` will be the one that remains in
+    /// the store.
+    pub fn swap(&mut self, other: &mut Self) {
+        self.ensure_loaded();
+        other.ensure_loaded();
+        mem::swap(&mut self.value, &mut other.value);
+        self.persist();
+        other.persist();
+    }
+
+    /// Convert into a [PersistedLazy] with the given container, without
+    /// accessing the store (unless this value is deferred and hasn't been
+    /// loaded yet). The current value is restored into the container and used
+    /// as the baseline for change detection, as in
+    /// [PersistedLazy::from_snapshot].
+    pub fn into_lazy<C>(mut self, container: C) -> PersistedLazy<S, K, C>
+    where
+        C: PersistedContainer<Value = K::Value>,
+    {
+        self.ensure_loaded();
+        // Release the key before the new wrapper claims it
+        self.registration.release();
+        let mut lazy =
+            PersistedLazy::from_snapshot(self.key, container, Some(self.value));
+        lazy.set_on_persist(self.on_persist);
+        lazy
     }
 
     /// Get a mutable reference to the value. This is wrapped by a guard, so
     /// that after mutation when the guard is dropped, the value can be saved.
+    /// If the value was created with [Self::new_deferred] and hasn't been
+    /// loaded yet, it will be loaded first. If the value was created with
+    /// [Self::new_manual], nothing is saved.
     pub fn get_mut(&mut self) -> PersistedRefMut<'_, S, K> {
+        self.ensure_loaded();
         PersistedRefMut {
             backend: self.backend,
             key: &self.key,
             value: &mut self.value,
+            snapshot: None,
+            on_persist: self.on_persist,
+            manual: self.manual,
+        }
+    }
+
+    /// Get a mutable reference to the value, like [Self::get_mut], but clone
+    /// the value first. When the guard persists, the store receives the
+    /// snapshot as the `old` value in [PersistedStore::store_persisted_diff].
+    /// Use this with stores that track changes, e.g. for an audit log or undo
+    /// stack. [Self::get_mut] avoids the clone, but the store doesn't get the
+    /// previous value.
+    pub fn get_mut_diff(&mut self) -> PersistedRefMut<'_, S, K>
+    where
+        K::Value: Clone,
+    {
+        let mut guard = self.get_mut();
+        guard.snapshot = Some((guard.value.clone(), Clone::clone));
+        guard
+    }
+
+    /// Get a mutable reference to the value, **without** persisting afterward.
+    /// Changes made through this reference are not written to the store, but
+    /// will be persisted along with the next mutation via [Self::get_mut]. Use
+    /// this for transient changes that shouldn't be saved, e.g. previewing a
+    /// setting before the user confirms it. If the value was created with
+    /// [Self::new_deferred] and hasn't been loaded yet, it will be loaded
+    /// first.
+    pub fn as_mut_unpersisted(&mut self) -> &mut K::Value {
+        self.ensure_loaded();
+        &mut self.value
+    }
+
+    /// Get a mutable reference to part of the value, such as a single field,
+    /// via the projections `get` and `get_mut`, which must select the same
+    /// part of the value. Like [Self::get_mut], the *entire* value is persisted
+    /// when the returned guard is dropped, so this is just sugar for editing a
+    /// nested field without exposing the rest of the value to the edit site.
+    /// If the value was created with [Self::new_deferred] and hasn't been
+    /// loaded yet, it will be loaded first.
+    ///
+    /// The guard can't hold a reference into the value it persists, so the
+    /// part is projected on every access: `get` for reads and `get_mut` for
+    /// writes. The two projections **must** select the same part of the value.
+    /// If they don't, reads and writes through the guard will silently go to
+    /// different places. This is checked with a debug assertion when the guard
+    /// is created.
+    ///
+    /// ```
+    /// use persisted::{Persisted, PersistedKey, PersistedStore};
+    /// # struct Store;
+    /// # impl<K: PersistedKey> PersistedStore<K> for Store {
+    /// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+    /// #     fn store_persisted(_: &K, _: &K::Value) {}
+    /// # }
+    ///
+    /// #[derive(Default)]
+    /// struct Settings {
+    ///     volume: u32,
+    ///     muted: bool,
+    /// }
+    ///
+    /// #[derive(PersistedKey)]
+    /// #[persisted(Settings)]
+    /// struct SettingsKey;
+    ///
+    /// let mut settings = Persisted::<Store, _>::new_default(SettingsKey);
+    /// *settings.lens_mut(|s| &s.volume, |s| &mut s.volume) = 80;
+    /// assert_eq!(settings.volume, 80);
+    /// ```
+    pub fn lens_mut<U>(
+        &mut self,
+        get: fn(&K::Value) -> &U,
+        get_mut: fn(&mut K::Value) -> &mut U,
+    ) -> LensRefMut<'_, S, K, U> {
+        let mut guard = self.get_mut();
+        // Zero-sized parts can't be told apart by address
+        debug_assert!(
+            mem::size_of::<U>() == 0
+                || ptr::eq(get(&guard), get_mut(&mut guard)),
+            "lens_mut projections for `{}` select different parts of the value",
+            K::type_name(),
+        );
+        LensRefMut {
+            guard,
+            get,
+            get_mut,
         }
     }
 }
@@ -96,9 +511,54 @@ where
     }
 }
 
+/// Note: [Borrow](core::borrow::Borrow) is *not* implemented, because it would
+/// conflict with the blanket `Borrow<T> for T` impl.
+impl<S, K> AsRef<K::Value> for Persisted<S, K>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+{
+    fn as_ref(&self) -> &K::Value {
+        &self.value
+    }
+}
+
+/// Wrappers are compared by key only. A wrapper's identity *is* its key, so two
+/// wrappers with equal keys are equal even if their in-memory values have
+/// diverged. Having two live wrappers with the same key is itself a misuse
+/// though, since they'll overwrite each other's persisted values.
+impl<S, K> PartialEq for Persisted<S, K>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey + PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<S, K> Eq for Persisted<S, K>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey + Eq,
+{
+}
+
+/// Hash the key only, consistent with [PartialEq]
+impl<S, K> Hash for Persisted<S, K>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey + Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
 /// A guard encompassing the lifespan of a mutable reference to a persisted
 /// value. The purpose of this is to save the value immediately after it is
 /// mutated.
+#[must_use = "the guard must be held to mutate; a bare get_mut() call just persists the current value"]
 #[derive(derive_more::Debug)]
 #[debug(bound(K::Value: Debug))]
 pub struct PersistedRefMut<'a, S, K>
@@ -106,9 +566,52 @@ where
     S: PersistedStore<K>,
     K: PersistedKey,
 {
+    #[debug(skip)] // Omit bound on S
     backend: PhantomData<S>,
     key: &'a K,
     value: &'a mut K::Value,
+    /// The value as of the last persist. Only set by [Persisted::get_mut_diff]
+    #[debug(skip)]
+    snapshot: Option<Snapshot<K::Value>>,
+    #[debug(skip)]
+    on_persist: Option<fn(&K::Value)>,
+    /// Skip persisting on drop. See [Persisted::new_manual]
+    manual: bool,
+}
+
+/// A copy of a value, and the function to take a new copy after each persist.
+/// The function is stored so the guard doesn't need a [Clone] bound.
+type Snapshot<V> = (V, fn(&V) -> V);
+
+impl<'a, S, K> PersistedRefMut<'a, S, K>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+{
+    /// Get the key of the value being mutated
+    pub fn key(&self) -> &K {
+        self.key
+    }
+
+    /// Persist the current value immediately, without releasing the guard.
+    /// This is useful for checkpointing partway through a long series of
+    /// mutations. Because [Persisted] doesn't track changes, the value will
+    /// still be persisted again when the guard is dropped.
+    ///
+    /// This persists even for values created with [Persisted::new_manual],
+    /// since it's an explicit request.
+    pub fn commit(&mut self) {
+        let old = self.snapshot.as_ref().map(|(old, _)| old);
+        if !persist::<S, K>(self.key, old, self.value) {
+            return;
+        }
+        if let Some((old, clone)) = &mut self.snapshot {
+            *old = clone(self.value);
+        }
+        if let Some(on_persist) = self.on_persist {
+            on_persist(self.value);
+        }
+    }
 }
 
 impl<'a, S, K> Deref for PersistedRefMut<'a, S, K>
@@ -141,6 +644,74 @@ where
     K: PersistedKey,
 {
     fn drop(&mut self) {
-        S::store_persisted(self.key, self.value);
+        if !self.manual {
+            self.commit();
+        }
+    }
+}
+
+/// A guard encompassing the lifespan of a mutable reference to part of a
+/// persisted value, created by [Persisted::lens_mut]. The entire value is
+/// persisted when the guard is dropped.
+#[must_use = "the guard must be held to mutate; a bare lens_mut() call just persists the current value"]
+pub struct LensRefMut<'a, S, K, U>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+{
+    /// Guard for the entire value, which persists it on drop
+    guard: PersistedRefMut<'a, S, K>,
+    /// Project the part of the value being mutated. This runs on every
+    /// access, rather than holding a reference to the part. Both projections
+    /// select the same part; see [Persisted::lens_mut].
+    get: fn(&K::Value) -> &U,
+    get_mut: fn(&mut K::Value) -> &mut U,
+}
+
+impl<'a, S, K, U> LensRefMut<'a, S, K, U>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+{
+    /// Get the key of the value being mutated
+    pub fn key(&self) -> &K {
+        self.guard.key()
+    }
+}
+
+impl<'a, S, K, U> Deref for LensRefMut<'a, S, K, U>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+{
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        (self.get)(&self.guard)
+    }
+}
+
+impl<'a, S, K, U> DerefMut for LensRefMut<'a, S, K, U>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        (self.get_mut)(&mut self.guard)
+    }
+}
+
+// Hand-written to omit bounds on S and K::Value
+impl<'a, S, K, U> Debug for LensRefMut<'a, S, K, U>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey + Debug,
+    U: Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("LensRefMut")
+            .field("key", self.key())
+            .field("field", &**self)
+            .finish()
     }
 }
@@ -0,0 +1,247 @@
+use crate::PersistedKey;
+use core::{
+    fmt::{self, Debug},
+    marker::PhantomData,
+};
+use serde::{
+    de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor},
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+/// Field names, in serialization order
+const FIELDS: &[&str] = &["type", "key", "value"];
+
+/// A key and its value, bundled together for stores that persist
+/// self-describing records rather than using [PersistedKey::type_name] as an
+/// out-of-band identifier. Serializes as a struct with three fields:
+///
+/// - `type`: [PersistedKey::type_name] of the key
+/// - `key`: The key
+/// - `value`: The value
+///
+/// For example, in JSON:
+///
+/// ```json
+/// {"type": "my_crate::ToggleKey", "key": 3, "value": true}
+/// ```
+///
+/// To deserialize an entry back, use [PersistedEntryOwned].
+#[derive(derive_more::Debug)]
+#[debug(bound(K: Debug, K::Value: Debug))]
+pub struct PersistedEntry<'a, K: PersistedKey> {
+    /// Key identifying the value
+    pub key: &'a K,
+    /// Persisted value
+    pub value: &'a K::Value,
+}
+
+impl<'a, K: PersistedKey> PersistedEntry<'a, K> {
+    /// Bundle a key and value
+    pub fn new(key: &'a K, value: &'a K::Value) -> Self {
+        Self { key, value }
+    }
+}
+
+impl<'a, K> Serialize for PersistedEntry<'a, K>
+where
+    K: PersistedKey + Serialize,
+    K::Value: Serialize,
+{
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("PersistedEntry", 3)?;
+        state.serialize_field("type", K::type_name())?;
+        state.serialize_field("key", self.key)?;
+        state.serialize_field("value", self.value)?;
+        state.end()
+    }
+}
+
+/// The owned equivalent of [PersistedEntry], used to deserialize entries. The
+/// `type` field must match [PersistedKey::type_name] of `K`, otherwise
+/// deserialization fails. This makes it safe to attempt to deserialize an
+/// entry of unknown type as each possible key type.
+#[derive(derive_more::Debug)]
+#[debug(bound(K: Debug, K::Value: Debug))]
+pub struct PersistedEntryOwned<K: PersistedKey> {
+    /// Key identifying the value
+    pub key: K,
+    /// Persisted value
+    pub value: K::Value,
+}
+
+impl<'de, K> Deserialize<'de> for PersistedEntryOwned<K>
+where
+    K: PersistedKey + Deserialize<'de>,
+    K::Value: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        deserializer.deserialize_struct(
+            "PersistedEntry",
+            FIELDS,
+            EntryVisitor(PhantomData),
+        )
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum Field {
+    Type,
+    Key,
+    Value,
+}
+
+struct EntryVisitor<K>(PhantomData<K>);
+
+impl<'de, K> Visitor<'de> for EntryVisitor<K>
+where
+    K: PersistedKey + Deserialize<'de>,
+    K::Value: Deserialize<'de>,
+{
+    type Value = PersistedEntryOwned<K>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "persisted entry for key type `{}`", K::type_name())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(
+        self,
+        mut seq: A,
+    ) -> Result<Self::Value, A::Error> {
+        seq.next_element_seed(TypeNameSeed::<K>(PhantomData))?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let key = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let value = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        Ok(PersistedEntryOwned { key, value })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(
+        self,
+        mut map: A,
+    ) -> Result<Self::Value, A::Error> {
+        let mut has_type = false;
+        let mut key = None;
+        let mut value = None;
+        while let Some(field) = map.next_key()? {
+            match field {
+                Field::Type => {
+                    if has_type {
+                        return Err(de::Error::duplicate_field("type"));
+                    }
+                    map.next_value_seed(TypeNameSeed::<K>(PhantomData))?;
+                    has_type = true;
+                }
+                Field::Key => {
+                    if key.is_some() {
+                        return Err(de::Error::duplicate_field("key"));
+                    }
+                    key = Some(map.next_value()?);
+                }
+                Field::Value => {
+                    if value.is_some() {
+                        return Err(de::Error::duplicate_field("value"));
+                    }
+                    value = Some(map.next_value()?);
+                }
+            }
+        }
+
+        if !has_type {
+            return Err(de::Error::missing_field("type"));
+        }
+        Ok(PersistedEntryOwned {
+            key: key.ok_or_else(|| de::Error::missing_field("key"))?,
+            value: value.ok_or_else(|| de::Error::missing_field("value"))?,
+        })
+    }
+}
+
+/// Deserialize a type name and check that it matches the expected key type.
+/// This avoids allocating for the name.
+pub(crate) struct TypeNameSeed<K>(pub(crate) PhantomData<K>);
+
+impl<'de, K: PersistedKey> DeserializeSeed<'de> for TypeNameSeed<K> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de, K: PersistedKey> Visitor<'de> for TypeNameSeed<K> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "key type `{}`", K::type_name())
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        if v == K::type_name() {
+            Ok(())
+        } else {
+            Err(de::Error::invalid_value(de::Unexpected::Str(v), &self))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct ToggleKey(u32);
+
+    impl PersistedKey for ToggleKey {
+        type Value = bool;
+
+        fn type_name() -> &'static str {
+            "ToggleKey"
+        }
+    }
+
+    #[test]
+    fn test_entry_round_trip() {
+        let entry = PersistedEntry::new(&ToggleKey(3), &true);
+        let serialized = serde_json::to_value(&entry).unwrap();
+        assert_eq!(
+            serialized,
+            json!({"type": "ToggleKey", "key": 3, "value": true})
+        );
+
+        let deserialized: PersistedEntryOwned<ToggleKey> =
+            serde_json::from_value(serialized).unwrap();
+        assert_eq!(deserialized.key, ToggleKey(3));
+        assert!(deserialized.value);
+
+        // Sequence form works too
+        let deserialized: PersistedEntryOwned<ToggleKey> =
+            serde_json::from_value(json!(["ToggleKey", 4, false])).unwrap();
+        assert_eq!(deserialized.key, ToggleKey(4));
+    }
+
+    #[test]
+    fn test_entry_type_mismatch() {
+        let result = serde_json::from_value::<PersistedEntryOwned<ToggleKey>>(
+            json!({"type": "OtherKey", "key": 3, "value": true}),
+        );
+        assert!(result.is_err());
+        let result = serde_json::from_value::<PersistedEntryOwned<ToggleKey>>(
+            json!({"key": 3, "value": true}),
+        );
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,338 @@
+use std::{
+    sync::{Mutex, MutexGuard, OnceLock, PoisonError, RwLock},
+    thread::{self, ThreadId},
+    vec::Vec,
+};
+
+/// Define a global, thread-safe store instance. This generates a `static` of
+/// type [GlobalStore], which lazily initializes the given value on first
+/// access and guards it with a lock. Access the value with
+/// [GlobalStore::with]. This saves you from writing the static and locking
+/// plumbing yourself, so you only have to write the load/store logic.
+///
+/// ```
+/// use persisted::{global_store, Persisted, PersistedKey, PersistedStore};
+/// use std::collections::HashMap;
+///
+/// global_store! {
+///     /// All persisted values, keyed by type name
+///     static STATE: HashMap<&'static str, u32> = HashMap::new();
+/// }
+///
+/// struct Store;
+///
+/// impl<K: PersistedKey<Value = u32>> PersistedStore<K> for Store {
+///     fn load_persisted(_key: &K) -> Option<u32> {
+///         STATE.with(|state| state.get(K::type_name()).copied())
+///     }
+///
+///     fn store_persisted(_key: &K, value: &u32) {
+///         STATE.with(|state| state.insert(K::type_name(), *value));
+///     }
+/// }
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(u32)]
+/// struct CountKey;
+///
+/// let mut count = Persisted::<Store, _>::new(CountKey, 0);
+/// *count.get_mut() += 1;
+/// ```
+#[macro_export]
+macro_rules! global_store {
+    (
+        $(#[$attr:meta])*
+        $vis:vis static $name:ident: $ty:ty = $init:expr;
+    ) => {
+        $(#[$attr])*
+        $vis static $name: $crate::GlobalStore<$ty> =
+            $crate::GlobalStore::new(|| $init);
+    };
+}
+
+/// A lazily initialized, lock-guarded value, intended to hold the state of a
+/// global store. Generally you should create this via [global_store!].
+///
+/// ## Reentrancy
+///
+/// The value is locked for the duration of each [GlobalStore::with] call.
+/// Calling [GlobalStore::with] again on the same thread while the lock is held
+/// would deadlock, so it panics instead. In practice, this means you should not
+/// load or persist any values *inside* the closure passed to `with`. For
+/// example, don't construct a [Persisted](crate::Persisted) or drop the guard
+/// returned by [Persisted::get_mut](crate::Persisted::get_mut) within the
+/// closure, as either may call back into the store.
+#[derive(Debug)]
+pub struct GlobalStore<T> {
+    init: fn() -> T,
+    value: OnceLock<Mutex<T>>,
+    /// Thread currently holding the lock, used to detect reentrant access
+    owner: Mutex<Option<ThreadId>>,
+}
+
+impl<T> GlobalStore<T> {
+    /// Create a new store. The value will be initialized on first access
+    pub const fn new(init: fn() -> T) -> Self {
+        Self {
+            init,
+            value: OnceLock::new(),
+            owner: Mutex::new(None),
+        }
+    }
+
+    /// Get mutable access to the value. The value is locked for the duration
+    /// of the given closure, so accesses from other threads will block.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if called reentrantly, i.e. from within another call to `with`
+    /// on the same thread.
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let current = thread::current().id();
+        if *lock(&self.owner) == Some(current) {
+            panic!(
+                "Reentrant access to global store. Persisted values cannot be \
+                loaded or stored while the store is already being accessed."
+            );
+        }
+
+        let mut value =
+            lock(self.value.get_or_init(|| Mutex::new((self.init)())));
+        *lock(&self.owner) = Some(current);
+        // Release ownership even if the closure panics
+        let _guard = OwnerGuard(&self.owner);
+        f(&mut value)
+    }
+}
+
+/// Clear the owner of a [GlobalStore] on drop
+struct OwnerGuard<'a>(&'a Mutex<Option<ThreadId>>);
+
+impl<'a> Drop for OwnerGuard<'a> {
+    fn drop(&mut self) {
+        *lock(self.0) = None;
+    }
+}
+
+/// Define a global, thread-safe store instance that allows concurrent reads.
+/// This generates a `static` of type [RwLockStore], which lazily initializes
+/// the given value on first access and guards it with a read-write lock. Read
+/// the value with [RwLockStore::read] (typically in
+/// [PersistedStore::load_persisted](crate::PersistedStore::load_persisted))
+/// and modify it with [RwLockStore::write] (typically in
+/// [PersistedStore::store_persisted](crate::PersistedStore::store_persisted)).
+///
+/// Prefer this over [global_store!] when many threads load values
+/// concurrently, since loads won't block each other.
+///
+/// ```
+/// use persisted::{rwlock_store, Persisted, PersistedKey, PersistedStore};
+/// use std::collections::HashMap;
+///
+/// rwlock_store! {
+///     /// All persisted values, keyed by type name
+///     static STATE: HashMap<&'static str, u32> = HashMap::new();
+/// }
+///
+/// struct Store;
+///
+/// impl<K: PersistedKey<Value = u32>> PersistedStore<K> for Store {
+///     fn load_persisted(_key: &K) -> Option<u32> {
+///         STATE.read(|state| state.get(K::type_name()).copied())
+///     }
+///
+///     fn store_persisted(_key: &K, value: &u32) {
+///         STATE.write(|state| state.insert(K::type_name(), *value));
+///     }
+/// }
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(u32)]
+/// struct CountKey;
+///
+/// let mut count = Persisted::<Store, _>::new(CountKey, 0);
+/// *count.get_mut() += 1;
+/// ```
+#[macro_export]
+macro_rules! rwlock_store {
+    (
+        $(#[$attr:meta])*
+        $vis:vis static $name:ident: $ty:ty = $init:expr;
+    ) => {
+        $(#[$attr])*
+        $vis static $name: $crate::RwLockStore<$ty> =
+            $crate::RwLockStore::new(|| $init);
+    };
+}
+
+/// A lazily initialized value guarded by a read-write lock, intended to hold
+/// the state of a global store. Any number of threads can read the value at
+/// once, while writes are exclusive. Generally you should create this via
+/// [rwlock_store!].
+///
+/// ## Reentrancy
+///
+/// The value is locked for the duration of each [RwLockStore::read] or
+/// [RwLockStore::write] call. Accessing the store again on the same thread
+/// while the lock is held can deadlock: a write would wait on its own read
+/// lock, and even a nested read can block behind a writer waiting on another
+/// thread. So *any* reentrant access panics instead. In practice, this means
+/// you should not load or persist any values inside the closure passed to
+/// `read` or `write`. For example, don't construct a
+/// [Persisted](crate::Persisted) or drop the guard returned by
+/// [Persisted::get_mut](crate::Persisted::get_mut) within the closure, as
+/// either may call back into the store.
+#[derive(Debug)]
+pub struct RwLockStore<T> {
+    init: fn() -> T,
+    value: OnceLock<RwLock<T>>,
+    /// Threads currently holding the lock, used to detect reentrant access
+    holders: Mutex<Vec<ThreadId>>,
+}
+
+impl<T> RwLockStore<T> {
+    /// Create a new store. The value will be initialized on first access
+    pub const fn new(init: fn() -> T) -> Self {
+        Self {
+            init,
+            value: OnceLock::new(),
+            holders: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Get shared access to the value. The value is read-locked for the
+    /// duration of the given closure, so writes from other threads will block
+    /// but reads won't.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if called reentrantly, i.e. from within another call to `read`
+    /// or `write` on the same thread.
+    pub fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let _holder = self.enter();
+        // See [lock] for why poisoning is ignored
+        let value =
+            self.rwlock().read().unwrap_or_else(PoisonError::into_inner);
+        f(&value)
+    }
+
+    /// Get exclusive access to the value. The value is write-locked for the
+    /// duration of the given closure, so all accesses from other threads will
+    /// block.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if called reentrantly, i.e. from within another call to `read`
+    /// or `write` on the same thread.
+    pub fn write<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let _holder = self.enter();
+        let mut value = self
+            .rwlock()
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        f(&mut value)
+    }
+
+    fn rwlock(&self) -> &RwLock<T> {
+        self.value.get_or_init(|| RwLock::new((self.init)()))
+    }
+
+    /// Register the current thread as a lock holder, panicking if it already
+    /// is one
+    fn enter(&self) -> HolderGuard<'_> {
+        let current = thread::current().id();
+        let mut holders = lock(&self.holders);
+        if holders.contains(&current) {
+            panic!(
+                "Reentrant access to RwLock store. Persisted values cannot be \
+                loaded or stored while the store is already being accessed."
+            );
+        }
+        holders.push(current);
+        HolderGuard {
+            holders: &self.holders,
+            thread: current,
+        }
+    }
+}
+
+/// Remove a thread from the holders of an [RwLockStore] on drop
+struct HolderGuard<'a> {
+    holders: &'a Mutex<Vec<ThreadId>>,
+    thread: ThreadId,
+}
+
+impl<'a> Drop for HolderGuard<'a> {
+    fn drop(&mut self) {
+        lock(self.holders).retain(|thread| *thread != self.thread);
+    }
+}
+
+/// Lock a mutex, ignoring poisoning. Store state is plain data, so a panic
+/// while the lock was held can't leave it in an invalid state.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_global_store() {
+        global_store! {
+            static STORE: u32 = 3;
+        }
+
+        assert_eq!(STORE.with(|value| *value), 3);
+        STORE.with(|value| *value += 1);
+        // Accessible from other threads
+        thread::spawn(|| STORE.with(|value| *value += 1))
+            .join()
+            .unwrap();
+        assert_eq!(STORE.with(|value| *value), 5);
+    }
+
+    #[test]
+    fn test_rwlock_store() {
+        rwlock_store! {
+            static STORE: u32 = 3;
+        }
+
+        assert_eq!(STORE.read(|value| *value), 3);
+        STORE.write(|value| *value += 1);
+        // Concurrent readers don't block each other
+        let barrier = std::sync::Barrier::new(2);
+        thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(|| STORE.read(|_| barrier.wait()));
+            }
+        });
+        thread::spawn(|| STORE.write(|value| *value += 1))
+            .join()
+            .unwrap();
+        assert_eq!(STORE.read(|value| *value), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "Reentrant access to RwLock store")]
+    fn test_rwlock_store_reentrant() {
+        rwlock_store! {
+            static STORE: u32 = 3;
+        }
+
+        STORE.read(|_| STORE.read(|_| {}));
+    }
+
+    #[test]
+    #[should_panic(expected = "Reentrant access to global store")]
+    fn test_global_store_reentrant() {
+        global_store! {
+            static STORE: u32 = 3;
+        }
+
+        STORE.with(|_| STORE.with(|_| {}));
+    }
+}
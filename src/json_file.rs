@@ -0,0 +1,358 @@
+use crate::{global::lock, NamedStore, PersistedKey, PersistedStore};
+use core::{any::TypeId, marker::PhantomData};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    eprintln, fs, io,
+    path::PathBuf,
+    string::{String, ToString},
+    sync::Mutex,
+};
+
+/// Contents of a single file: `{type_name: {key: value}}`. A `BTreeMap` is
+/// used so the output file is deterministic.
+type Contents = BTreeMap<String, BTreeMap<String, Value>>;
+
+/// All loaded files, keyed by the type of their [JsonFile] definition. Rust
+/// doesn't support generic statics, so all [JsonFileStore]s share this one.
+static FILES: Mutex<BTreeMap<TypeId, LoadedFile>> = Mutex::new(BTreeMap::new());
+
+/// In-memory state of a single file
+struct LoadedFile {
+    contents: Contents,
+    /// Whether the file on disk may be overwritten. This is `false` if the
+    /// file exists but couldn't be loaded, so that writing our (incomplete)
+    /// contents doesn't destroy the user's data.
+    writable: bool,
+}
+
+/// Definition of a JSON file to be used by [JsonFileStore]. Implement this on
+/// a marker type to define where the file is located. Each implementor gets
+/// its own independent file cache, so two implementors should never return the
+/// same path.
+pub trait JsonFile: 'static {
+    /// Path to the file. This is called on every load and save of the file,
+    /// so it should be cheap and always return the same path.
+    fn path() -> PathBuf;
+
+    /// Handle an error that occurred while loading or saving the file. Per the
+    /// [PersistedStore] contract, errors aren't propagated. The default
+    /// implementation prints the error to stderr.
+    fn on_error(error: io::Error) {
+        eprintln!("Error persisting to {:?}: {error}", Self::path());
+    }
+}
+
+/// A store that persists all values to a single JSON file. The file is loaded
+/// into memory on first access, and the entire file is written back to disk on
+/// every write. Values are grouped by [PersistedKey::type_name], then by the
+/// JSON-serialized key. This is intended for the common case of "dump all my
+/// settings to a file", where values are small and writes are infrequent.
+///
+/// The file is defined by the `F` parameter, which must implement [JsonFile].
+/// Keys must implement [Serialize], and values must implement both
+/// [Serialize] and [DeserializeOwned].
+///
+/// If the file is missing, it will be treated as empty and created on the
+/// first write. If the file exists but can't be read or parsed, the error is
+/// reported via [JsonFile::on_error] and the file is never written, so a typo
+/// in a hand-edited file doesn't wipe out the other values. Changes are still
+/// kept in memory for the rest of the process. If a stored value fails to
+/// deserialize (e.g. because the value type changed), it will be treated as
+/// missing.
+///
+/// Writes go to a temporary file next to the target, which is then renamed
+/// into place, so a crash mid-write can't leave a truncated file behind.
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{JsonFile, JsonFileStore, Persisted, PersistedKey};
+/// use std::path::PathBuf;
+///
+/// struct SettingsFile;
+///
+/// impl JsonFile for SettingsFile {
+///     fn path() -> PathBuf {
+///         std::env::temp_dir().join("persisted_doc_settings.json")
+///     }
+/// }
+///
+/// type Store = JsonFileStore<SettingsFile>;
+///
+/// #[derive(PersistedKey, serde::Serialize)]
+/// #[persisted(bool)]
+/// struct DarkModeKey;
+///
+/// let mut dark_mode = Persisted::<Store, _>::new(DarkModeKey, false);
+/// *dark_mode.get_mut() = true;
+/// ```
+#[derive(Debug)]
+pub struct JsonFileStore<F> {
+    phantom: PhantomData<F>,
+}
+
+impl<F: JsonFile> JsonFileStore<F> {
+    /// Access the contents of the file, loading it from disk if this is the
+    /// first access
+    fn with_file<T>(f: impl FnOnce(&mut LoadedFile) -> T) -> T {
+        let mut files = lock(&FILES);
+        let file = files
+            .entry(TypeId::of::<F>())
+            .or_insert_with(Self::load_file);
+        f(file)
+    }
+
+    /// Access the contents of the file, without writing it back
+    fn with_contents<T>(f: impl FnOnce(&Contents) -> T) -> T {
+        Self::with_file(|file| f(&file.contents))
+    }
+
+    fn load_file() -> LoadedFile {
+        let result = fs::read(F::path()).and_then(|bytes| {
+            serde_json::from_slice(&bytes).map_err(io::Error::from)
+        });
+        match result {
+            Ok(contents) => LoadedFile {
+                contents,
+                writable: true,
+            },
+            // Missing file is expected on first run
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                LoadedFile {
+                    contents: Contents::new(),
+                    writable: true,
+                }
+            }
+            Err(error) => {
+                F::on_error(error);
+                LoadedFile {
+                    contents: Contents::new(),
+                    writable: false,
+                }
+            }
+        }
+    }
+
+    /// Write the file to disk, unless it failed to load
+    fn save_file(file: &LoadedFile) {
+        if !file.writable {
+            return;
+        }
+        let path = F::path();
+        let mut temp_path = path.clone().into_os_string();
+        temp_path.push(".tmp");
+        let result = serde_json::to_vec_pretty(&file.contents)
+            .map_err(io::Error::from)
+            .and_then(|bytes| fs::write(&temp_path, bytes))
+            .and_then(|()| fs::rename(&temp_path, &path));
+        if let Err(error) = result {
+            F::on_error(error);
+        }
+    }
+
+    /// Serialize a key to use as the inner map key
+    fn key_string<K: Serialize>(key: &K) -> Option<String> {
+        serde_json::to_string(key)
+            .map_err(|error| F::on_error(error.into()))
+            .ok()
+    }
+}
+
+impl<F, K> PersistedStore<K> for JsonFileStore<F>
+where
+    F: JsonFile,
+    K: PersistedKey + Serialize,
+    K::Value: Serialize + DeserializeOwned,
+{
+    fn load_persisted(key: &K) -> Option<K::Value> {
+        Self::load_named(K::type_name(), key)
+    }
+
+    fn store_persisted(key: &K, value: &K::Value) {
+        let Some(key) = Self::key_string(key) else {
+            return;
+        };
+        let value = match serde_json::to_value(value) {
+            Ok(value) => value,
+            Err(error) => {
+                F::on_error(error.into());
+                return;
+            }
+        };
+        Self::with_file(|file| {
+            file.contents
+                .entry(K::type_name().to_string())
+                .or_default()
+                .insert(key, value);
+            Self::save_file(file);
+        })
+    }
+
+    fn remove_persisted(key: &K) {
+        Self::remove_named(K::type_name(), key);
+    }
+
+    /// Load the file into memory, if it hasn't been loaded yet
+    fn warm_up() {
+        Self::with_contents(|_| {});
+    }
+}
+
+impl<F, K> NamedStore<K> for JsonFileStore<F>
+where
+    F: JsonFile,
+    K: PersistedKey + Serialize,
+    K::Value: Serialize + DeserializeOwned,
+{
+    fn load_named(type_name: &'static str, key: &K) -> Option<K::Value> {
+        let key = Self::key_string(key)?;
+        let value = Self::with_contents(|contents| {
+            contents.get(type_name)?.get(&key).cloned()
+        })?;
+        serde_json::from_value(value)
+            .map_err(|error| F::on_error(error.into()))
+            .ok()
+    }
+
+    fn remove_named(type_name: &'static str, key: &K) {
+        let Some(key) = Self::key_string(key) else {
+            return;
+        };
+        Self::with_file(|file| {
+            if let Some(values) = file.contents.get_mut(type_name) {
+                if values.remove(&key).is_some() {
+                    Self::save_file(file);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Persisted;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize)]
+    struct CountKey(u32);
+
+    impl PersistedKey for CountKey {
+        type Value = u32;
+
+        fn type_name() -> &'static str {
+            "CountKey"
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize)]
+    struct SettingsKey;
+
+    impl PersistedKey for SettingsKey {
+        type Value = Settings;
+
+        fn type_name() -> &'static str {
+            "SettingsKey"
+        }
+    }
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Settings {
+        name: String,
+        enabled: bool,
+    }
+
+    #[test]
+    fn test_json_file_store() {
+        struct File;
+
+        impl JsonFile for File {
+            fn path() -> PathBuf {
+                std::env::temp_dir().join("persisted_test_json_file_store.json")
+            }
+        }
+
+        type Store = JsonFileStore<File>;
+
+        let _ = fs::remove_file(File::path());
+
+        let mut count = Persisted::<Store, _>::new(CountKey(1), 0);
+        let mut settings = Persisted::<Store, _>::new_default(SettingsKey);
+        assert!(!count.was_loaded());
+        *count.get_mut() = 3;
+        settings.get_mut().name = "test".into();
+
+        // Everything is written to disk
+        let contents: Value =
+            serde_json::from_slice(&fs::read(File::path()).unwrap()).unwrap();
+        assert_eq!(
+            contents,
+            serde_json::json!({
+                "CountKey": {"1": 3},
+                "SettingsKey": {"null": {"name": "test", "enabled": false}},
+            })
+        );
+        // Writes go through a temp file, which is renamed into place
+        let mut temp_path = File::path().into_os_string();
+        temp_path.push(".tmp");
+        assert!(!PathBuf::from(temp_path).exists());
+
+        // Values are restored
+        let count = Persisted::<Store, _>::new(CountKey(1), 0);
+        let other_count = Persisted::<Store, _>::new(CountKey(2), 0);
+        assert_eq!(*count, 3);
+        assert_eq!(*other_count, 0);
+    }
+
+    /// Existing file should be loaded on first access. Invalid values are
+    /// treated as missing.
+    #[test]
+    fn test_json_file_store_load() {
+        struct File;
+
+        impl JsonFile for File {
+            fn path() -> PathBuf {
+                std::env::temp_dir().join("persisted_test_json_file_load.json")
+            }
+        }
+
+        type Store = JsonFileStore<File>;
+
+        let contents = serde_json::json!({
+            "CountKey": {"1": 3, "2": "not a number"},
+        });
+        fs::write(File::path(), contents.to_string()).unwrap();
+
+        assert_eq!(Store::load_persisted(&CountKey(1)), Some(3));
+        assert_eq!(Store::load_persisted(&CountKey(2)), None);
+        assert_eq!(Store::load_persisted(&CountKey(3)), None);
+    }
+
+    /// A file that can't be parsed is never overwritten
+    #[test]
+    fn test_json_file_store_invalid() {
+        struct File;
+
+        impl JsonFile for File {
+            fn path() -> PathBuf {
+                std::env::temp_dir()
+                    .join("persisted_test_json_file_invalid.json")
+            }
+
+            fn on_error(_: io::Error) {}
+        }
+
+        type Store = JsonFileStore<File>;
+
+        let contents = r#"{"CountKey": {"1": 3},}"#;
+        fs::write(File::path(), contents).unwrap();
+
+        assert_eq!(Store::load_persisted(&CountKey(1)), None);
+        // The write is kept in memory, but the file is untouched
+        Store::store_persisted(&CountKey(2), &4);
+        assert_eq!(Store::load_persisted(&CountKey(2)), Some(4));
+        assert_eq!(fs::read_to_string(File::path()).unwrap(), contents);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::PersistedKey;
+use alloc::string::String;
+use core::fmt::{Display, Write};
+
+/// A key that can be encoded as a stable string identifier, distinct from its
+/// [type name](PersistedKey::type_name). The identifier distinguishes between
+/// keys of the *same* type, so together with the type name it forms a
+/// complete storage key; see [Self::storage_key]. This is useful for stores
+/// that need a plain string or tuple key, such as a database column or a
+/// [HashMap](std::collections::HashMap).
+///
+/// The identifier should be stable across program versions, and different
+/// keys of the same type should have different identifiers. For unit keys,
+/// the empty string is sufficient.
+///
+/// This can be derived for structs and enums whose fields implement
+/// [Display]. The derived identifier is the `/`-separated list of the key's
+/// fields, prefixed with the variant name for enums. Fields whose `Display`
+/// output contains `/` could produce ambiguous identifiers, so implement the
+/// trait manually for those.
+///
+/// ```
+/// use persisted::{KeyCodec, PersistedKey};
+///
+/// #[derive(KeyCodec, PersistedKey)]
+/// #[persisted(bool)]
+/// struct ToggleKey(u64);
+///
+/// #[derive(KeyCodec, PersistedKey)]
+/// #[persisted(u32)]
+/// enum ScrollKey {
+///     Sidebar,
+///     Tab { id: u32, pane: u8 },
+/// }
+///
+/// assert_eq!(ToggleKey(23).key_id(), "23");
+/// assert_eq!(ScrollKey::Sidebar.key_id(), "Sidebar");
+/// assert_eq!(ScrollKey::Tab { id: 3, pane: 1 }.key_id(), "Tab/3/1");
+/// assert_eq!(
+///     ToggleKey(23).storage_key(),
+///     (ToggleKey::type_name(), "23".into())
+/// );
+/// ```
+pub trait KeyCodec: PersistedKey {
+    /// Get an identifier for this key that is unique among all keys of the
+    /// same type
+    fn key_id(&self) -> String;
+
+    /// Get a complete identifier for this key: its type name, plus the
+    /// instance identifier from [Self::key_id]
+    fn storage_key(&self) -> (&'static str, String) {
+        (Self::type_name(), self.key_id())
+    }
+}
+
+/// Join the parts of a key into an identifier. Used by the `KeyCodec` derive
+#[doc(hidden)]
+pub fn __join_key_id(parts: &[&dyn Display]) -> String {
+    let mut id = String::new();
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 {
+            id.push('/');
+        }
+        // Writing to a String can't fail
+        let _ = write!(id, "{part}");
+    }
+    id
+}
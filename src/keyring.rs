@@ -0,0 +1,142 @@
+use crate::{KeyCodec, PersistedStore, Secret};
+use core::marker::PhantomData;
+use keyring::{Entry, Error};
+use std::{eprintln, format, string::String};
+
+/// Definition of a service in the OS secret store, to be used by
+/// [KeyringStore]. Implement this on a marker type to define the service name
+/// under which all values are stored. The name should be unique to your
+/// application, e.g. its reverse-DNS identifier.
+pub trait KeyringService: 'static {
+    /// Name of the service that owns all stored values
+    fn service() -> &'static str;
+
+    /// Handle an error that occurred while accessing the secret store. Per the
+    /// [PersistedStore] contract, errors aren't propagated. A missing value is
+    /// not an error. The default implementation prints the error to stderr.
+    fn on_error(error: Error) {
+        eprintln!(
+            "Error accessing keyring service {}: {error}",
+            Self::service()
+        );
+    }
+}
+
+/// A store that persists secret strings to the platform's secret store, e.g.
+/// the macOS Keychain, Windows Credential Manager, or Linux kernel keyutils,
+/// via the [keyring] crate. This is appropriate for credentials such as API
+/// tokens, which shouldn't be written to a regular settings file.
+///
+/// The service name is defined by the `A` parameter, which must implement
+/// [KeyringService]. Each value is stored as a separate entry, whose user name
+/// is `<type_name>/<key_id>`, from [KeyCodec::storage_key]. Values must be
+/// [Secret]s, so they're hidden from debug output after being loaded.
+///
+/// ## Example
+///
+/// ```no_run
+/// use persisted::{
+///     KeyCodec, KeyringService, KeyringStore, Persisted, PersistedKey, Secret,
+/// };
+///
+/// struct MyApp;
+///
+/// impl KeyringService for MyApp {
+///     fn service() -> &'static str {
+///         "com.example.my-app"
+///     }
+/// }
+///
+/// #[derive(KeyCodec, PersistedKey)]
+/// #[persisted(Secret<String>)]
+/// struct ApiTokenKey;
+///
+/// let mut token =
+///     Persisted::<KeyringStore<MyApp>, _>::new_default(ApiTokenKey);
+/// *token.get_mut() = Secret::new("hunter2".into());
+/// ```
+#[derive(Debug)]
+pub struct KeyringStore<A> {
+    phantom: PhantomData<A>,
+}
+
+impl<A: KeyringService> KeyringStore<A> {
+    /// Get the secret store entry for a key
+    fn entry<K: KeyCodec>(key: &K) -> Result<Entry, Error> {
+        let (type_name, key_id) = key.storage_key();
+        Entry::new(A::service(), &format!("{type_name}/{key_id}"))
+    }
+}
+
+impl<K, A> PersistedStore<K> for KeyringStore<A>
+where
+    K: KeyCodec<Value = Secret<String>>,
+    A: KeyringService,
+{
+    fn load_persisted(key: &K) -> Option<Secret<String>> {
+        match Self::entry(key).and_then(|entry| entry.get_password()) {
+            Ok(password) => Some(Secret::new(password)),
+            Err(Error::NoEntry) => None,
+            Err(error) => {
+                A::on_error(error);
+                None
+            }
+        }
+    }
+
+    fn store_persisted(key: &K, value: &Secret<String>) {
+        if let Err(error) = Self::entry(key)
+            .and_then(|entry| entry.set_password(value.expose()))
+        {
+            A::on_error(error);
+        }
+    }
+
+    fn remove_persisted(key: &K) {
+        match Self::entry(key).and_then(|entry| entry.delete_credential()) {
+            Ok(()) | Err(Error::NoEntry) => {}
+            Err(error) => A::on_error(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Persisted, PersistedKey};
+
+    struct TestService;
+
+    impl KeyringService for TestService {
+        fn service() -> &'static str {
+            "persisted-smoke-test"
+        }
+    }
+
+    struct TokenKey;
+
+    impl PersistedKey for TokenKey {
+        type Value = Secret<String>;
+    }
+
+    impl KeyCodec for TokenKey {
+        fn key_id(&self) -> String {
+            String::new()
+        }
+    }
+
+    /// This touches the real secret store, so it's only run on request
+    #[test]
+    #[ignore]
+    fn test_keyring_smoke() {
+        type Store = KeyringStore<TestService>;
+
+        let mut token = Persisted::<Store, _>::new_default(TokenKey);
+        *token.get_mut() = Secret::new("hunter2".into());
+        let loaded = Store::load_persisted(&TokenKey);
+        assert_eq!(loaded.as_ref().map(Secret::expose).unwrap(), "hunter2");
+
+        Store::remove_persisted(&TokenKey);
+        assert_eq!(Store::load_persisted(&TokenKey), None);
+    }
+}
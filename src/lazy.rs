@@ -1,8 +1,22 @@
-use crate::{PersistedKey, PersistedStore};
-use core::{fmt::Debug, marker::PhantomData};
+mod ctx;
+
+pub use ctx::{
+    PersistedContainerCtx, PersistedLazyCtx, PersistedLazyCtxRefMut,
+};
+
+use crate::{
+    collision::KeyRegistration, load, persist, remove, Persisted, PersistedKey,
+    PersistedLazyBuilder, PersistedStore,
+};
+use core::{
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    mem,
+};
 use derive_more::{Deref, DerefMut, Display};
 
-/// Similar to [Persisted](crate::eager::Persisted), but the value that's sent
+/// Similar to [Persisted], but the value that's sent
 /// to the store is not the same as the value stored in memory. Instead, the
 /// value is computed at save time by [PersistedContainer::get_to_persist].
 /// Similarly, the persisted value that's loaded at initialization isn't stored
@@ -27,11 +41,12 @@ use derive_more::{Deref, DerefMut, Display};
 ///
 /// ## Accessing
 ///
-/// The inner value can be accessed immutably via [Deref]. To get mutable
-/// access, use [PersistedLazy::get_mut]. This wrapper method returns a guard
-/// that implements [DerefMut] (similar to [RefMut](std::cell::RefMut) or
-/// [MutexGuard](std::sync::MutexGuard), without the internal mutability). When
-/// your mutable access is complete, this wrapper will be dropped and the value
+/// The inner value can be accessed immutably via [PersistedLazy::container] or
+/// [Deref]. To get mutable access, use [PersistedLazy::get_mut]. This wrapper
+/// method returns a guard that implements [DerefMut] (similar to
+/// [RefMut](core::cell::RefMut) or `MutexGuard`, without
+/// the internal mutability). When your mutable access is complete, this
+/// wrapper will be dropped and the value
 /// will be persisted to the store **only if it changed** (according to its
 /// [PartialEq] impl).
 ///
@@ -172,6 +187,19 @@ where
     last_persisted: Option<K::Value>,
     #[deref]
     container: C,
+    /// Was the container restored from a persisted value on initialization?
+    was_restored: bool,
+    /// Has the store not been checked yet? Only `true` for deferred
+    /// containers that haven't been restored yet.
+    load_pending: bool,
+    /// Has a mutation changed the persisted value since initialization (or
+    /// the last [Self::mark_clean])?
+    is_dirty: bool,
+    /// Called with the new value after each persist
+    #[debug(skip)]
+    on_persist: Option<fn(&K::Value)>,
+    #[debug(skip)]
+    registration: KeyRegistration,
 }
 
 impl<S, K, C> PersistedLazy<S, K, C>
@@ -182,19 +210,53 @@ where
 {
     /// Initialize a given container whose value will lazily be loaded and
     /// persisted. If a persisted value is available in the store, it will be
-    /// loaded and used to initialize the container via
-    /// [PersistedContainer::restore_persisted].
+    /// loaded, passed through [PersistedKey::validate], and used to initialize
+    /// the container via [PersistedContainer::try_restore_persisted]. If the
+    /// container rejects the value, it's left in its initial state. A
+    /// restored value becomes the baseline for change detection (see
+    /// [Self::last_persisted]).
     pub fn new(key: K, mut container: C) -> Self {
         // Fetch persisted value from the backend
-        if let Some(value) = S::load_persisted(&key) {
-            container.restore_persisted(value);
+        let was_restored = load::<S, K>(&key)
+            .is_some_and(|value| container.try_restore_persisted(value));
+        // The store already has this value, so it doesn't need to be written
+        // back until it changes
+        let last_persisted = was_restored.then(|| container.get_to_persist());
+
+        Self {
+            backend: PhantomData,
+            registration: KeyRegistration::new(&key),
+            key,
+            container,
+            last_persisted,
+            was_restored,
+            load_pending: false,
+            is_dirty: false,
+            on_persist: None,
         }
+    }
 
+    /// Initialize a given container *without* loading from the store. The
+    /// container will remain in its constructed state until the persisted
+    /// value is loaded and restored, which happens on the first call to
+    /// [Self::ensure_loaded], [Self::get_mut], [Self::container_mut], or
+    /// [Self::modify]. This is useful for containers with an expensive
+    /// [PersistedContainer::restore_persisted] that may never be accessed.
+    ///
+    /// Note that [Deref] access does **not** trigger a load, because it only
+    /// has immutable access to the wrapper. Until the first load, the
+    /// container reflects its constructed state, not the persisted value.
+    pub fn new_deferred(key: K, container: C) -> Self {
         Self {
             backend: PhantomData,
+            registration: KeyRegistration::new(&key),
             key,
             container,
             last_persisted: None,
+            was_restored: false,
+            load_pending: true,
+            is_dirty: false,
+            on_persist: None,
         }
     }
 
@@ -209,6 +271,314 @@ where
         Self::new(key, C::default())
     }
 
+    /// Initialize a container from a known snapshot of its last persisted
+    /// value, **without** accessing the store. If `last_persisted` is given,
+    /// it's restored into the container via
+    /// [PersistedContainer::try_restore_persisted]. If the container accepts
+    /// it, it's used as the baseline for change detection (see
+    /// [Self::last_persisted]). This means a mutable access that doesn't
+    /// change the persisted value won't trigger a write. A rejected snapshot
+    /// is ignored, as if none was given.
+    ///
+    /// This is useful when rebuilding state from a saved session, where the
+    /// store may be slow or unavailable, and in tests.
+    pub fn from_snapshot(
+        key: K,
+        mut container: C,
+        last_persisted: Option<K::Value>,
+    ) -> Self {
+        let was_restored = last_persisted
+            .is_some_and(|value| container.try_restore_persisted(value));
+        let last_persisted = was_restored.then(|| container.get_to_persist());
+        Self {
+            backend: PhantomData,
+            registration: KeyRegistration::new(&key),
+            key,
+            container,
+            last_persisted,
+            was_restored,
+            load_pending: false,
+            is_dirty: false,
+            on_persist: None,
+        }
+    }
+
+    /// Create a builder, to combine options that don't have a dedicated
+    /// constructor. See [PersistedLazyBuilder].
+    pub fn builder(key: K) -> PersistedLazyBuilder<S, K, C> {
+        PersistedLazyBuilder::new(key)
+    }
+
+    pub(crate) fn set_on_persist(&mut self, on_persist: Option<fn(&K::Value)>) {
+        self.on_persist = on_persist;
+    }
+
+    /// Get the [type name](PersistedKey::type_name) of the key. This is
+    /// equivalent to `K::type_name()`, but doesn't require naming the key
+    /// type.
+    pub fn type_name(&self) -> &'static str {
+        K::type_name()
+    }
+
+    /// Was the container restored from a persisted value on initialization? If
+    /// `false`, the store had no value for the key (or the container rejected
+    /// it), so the container was left in its initial state. Mutations after
+    /// initialization don't affect this.
+    pub fn was_restored(&self) -> bool {
+        self.was_restored
+    }
+
+    /// Load the latest persisted value from the store and restore it into the
+    /// container, if it hasn't been loaded yet. This only has an effect for
+    /// containers created with [Self::new_deferred]; all other constructors
+    /// load immediately.
+    pub fn ensure_loaded(&mut self) {
+        if self.load_pending {
+            self.load_pending = false;
+            // Fetch persisted value from the backend
+            self.was_restored = load::<S, K>(&self.key).is_some_and(|value| {
+                self.container.try_restore_persisted(value)
+            });
+            if self.was_restored {
+                self.last_persisted = Some(self.container.get_to_persist());
+            }
+        }
+    }
+
+    /// Load the latest persisted value from the store and restore it into the
+    /// container via [PersistedContainer::try_restore_persisted], regardless
+    /// of whether a value was already loaded. If the container accepts the
+    /// value, it becomes the baseline for change detection (see
+    /// [Self::last_persisted]), so it won't be written back by the next
+    /// mutable access unless it changes. Return whether the container accepted
+    /// the value. If the store has no value or the container rejects it, the
+    /// container and baseline are left unchanged and this returns `false`.
+    ///
+    /// This is useful when another component writes to the same key, and this
+    /// container needs to reflect the change, e.g. a selection that was changed
+    /// in a different view.
+    pub fn restore_latest(&mut self) -> bool {
+        self.load_pending = false;
+        let restored = load::<S, K>(&self.key)
+            .is_some_and(|value| self.container.try_restore_persisted(value));
+        if restored {
+            self.last_persisted = Some(self.container.get_to_persist());
+        }
+        restored
+    }
+
+    /// Has a mutation changed the persisted value since initialization? This
+    /// is set whenever a mutable access (e.g. via [Self::get_mut]) persists a
+    /// changed value, and cleared by [Self::mark_clean]. Explicit calls to
+    /// [Self::persist] don't affect it. This is useful as a dirty indicator
+    /// for UI, e.g. to enable a save button.
+    pub fn is_dirty_since_load(&self) -> bool {
+        self.is_dirty
+    }
+
+    /// Clear the dirty flag. See [Self::is_dirty_since_load].
+    pub fn mark_clean(&mut self) {
+        self.is_dirty = false;
+    }
+
+    /// Get the value that was most recently persisted by this wrapper. This is
+    /// the baseline used for change detection: after each mutable access, the
+    /// value from [PersistedContainer::get_to_persist] is compared against
+    /// this, and only persisted if it differs. When a value is restored from
+    /// the store on load (or by [Self::restore_latest]), it's treated as
+    /// persisted. This will be `None` if nothing has been loaded or persisted
+    /// yet.
+    pub fn last_persisted(&self) -> Option<&K::Value> {
+        self.last_persisted.as_ref()
+    }
+
+    /// Get a reference to the container. This is equivalent to [Deref], but
+    /// more explicit.
+    pub fn container(&self) -> &C {
+        &self.container
+    }
+
+    /// Get a mutable reference to the container, **without** persisting
+    /// afterward. Changes made through this reference are not persisted, and
+    /// will not be detected by the next mutable access via [Self::get_mut]
+    /// unless they change the value returned by
+    /// [PersistedContainer::get_to_persist]. Use this to modify parts of the
+    /// container that don't affect the persisted value, e.g. updating a list
+    /// of items without changing the selection.
+    pub fn container_mut(&mut self) -> &mut C {
+        self.ensure_loaded();
+        &mut self.container
+    }
+
+    /// Persist the container's current value to the store immediately,
+    /// regardless of whether it has changed. Typically persistence is handled
+    /// automatically by [Self::get_mut], but this is useful for explicit "save
+    /// now" flows. This takes `&mut self` so that the persisted value can be
+    /// cached for subsequent change detection.
+    ///
+    /// If this container was created with [Self::new_deferred] and hasn't been
+    /// loaded yet, this will persist its constructed state, overwriting
+    /// whatever is in the store.
+    pub fn persist(&mut self) {
+        let persisted_value = self.container.get_to_persist();
+        self.persist_value(persisted_value);
+    }
+
+    /// Persist a value derived from the container, and make it the baseline
+    /// for change detection. Return whether the store was written. If
+    /// persistence is disabled, nothing changes, so the value will still be
+    /// detected as changed later.
+    fn persist_value(&mut self, persisted_value: K::Value) -> bool {
+        if !persist::<S, K>(
+            &self.key,
+            self.last_persisted.as_ref(),
+            &persisted_value,
+        ) {
+            return false;
+        }
+        if let Some(on_persist) = self.on_persist {
+            on_persist(&persisted_value);
+        }
+        self.last_persisted = Some(persisted_value);
+        true
+    }
+
+    /// Persist the container's current value if it differs from the last
+    /// persisted value, using the same change detection as dropping the guard
+    /// from [Self::get_mut]. Return whether the value was persisted. This is
+    /// useful for "save now" flows (e.g. on window close) after mutating the
+    /// container via [Self::container_mut], which doesn't persist on its own.
+    ///
+    /// If this container was created with [Self::new_deferred] and hasn't been
+    /// loaded yet, nothing has changed, so this does nothing. Note that this
+    /// does *not* call [PersistedStore::flush].
+    pub fn flush(&mut self) -> bool
+    where
+        K::Value: PartialEq,
+    {
+        if self.load_pending {
+            false
+        } else {
+            self.persist_if_changed()
+        }
+    }
+
+    /// Change the key for this container. The container's current value is
+    /// persisted under the new key, *then* the value for the old key is
+    /// removed from the store via [PersistedStore::remove_persisted]. Writing
+    /// before removing means the value can't be lost if the program is
+    /// interrupted in between. If the new key equals the old one, nothing is
+    /// removed. Returns the old key.
+    pub fn rekey(&mut self, key: K) -> K
+    where
+        K: PartialEq,
+    {
+        // Release the old key before claiming the new one, in case they're
+        // equal
+        self.registration = KeyRegistration::default();
+        self.registration = KeyRegistration::new(&key);
+        let old_key = mem::replace(&mut self.key, key);
+        // Nothing has been persisted under the new key by this wrapper
+        self.last_persisted = None;
+        self.persist();
+        // Equal keys share a slot in the store, so removing the old key would
+        // delete the value we just wrote
+        if old_key != self.key {
+            remove::<S, K>(&old_key);
+        }
+        old_key
+    }
+
+    /// Persist the container's current value, if it differs from the last
+    /// persisted value. Return whether the value was persisted.
+    fn persist_if_changed(&mut self) -> bool
+    where
+        K::Value: PartialEq,
+    {
+        // Most accesses don't change the value, so let the container compare
+        // before building a new value to persist
+        let persisted_value = match &self.last_persisted {
+            Some(last) => {
+                match self.container.get_to_persist_if_changed(last) {
+                    Some(value) => value,
+                    None => return false,
+                }
+            }
+            None => self.container.get_to_persist(),
+        };
+        if !self.persist_value(persisted_value) {
+            return false;
+        }
+        self.is_dirty = true;
+        true
+    }
+
+    /// Replace the container with a new one, returning the old container. If
+    /// this wrapper has loaded or persisted a value (see
+    /// [Self::last_persisted]), that value will be restored into the new
+    /// container via
+    /// [PersistedContainer::restore_persisted]. This is useful when the data
+    /// backing the container is rebuilt, e.g. a list is reloaded, and you want
+    /// to retain persisted state such as the selected item. The store is not
+    /// accessed.
+    ///
+    /// If nothing has been loaded or persisted yet, the new container is used
+    /// as-is.
+    pub fn replace_container(&mut self, mut container: C) -> C
+    where
+        K::Value: Clone,
+    {
+        if let Some(value) = &self.last_persisted {
+            container.try_restore_persisted(value.clone());
+        }
+        mem::replace(&mut self.container, container)
+    }
+
+    /// Restore the given value into the container, then persist it if it
+    /// differs from the last persisted value. This is useful for setting the
+    /// container's state in terms of its persisted value, e.g. selecting an
+    /// item in a list by its ID.
+    pub fn set_persisted(&mut self, value: K::Value)
+    where
+        K::Value: PartialEq,
+    {
+        // The restored value supersedes anything in the store
+        self.load_pending = false;
+        self.container.restore_persisted(value);
+        self.persist_if_changed();
+    }
+
+    /// Modify the container, then persist it if the persisted value changed.
+    /// This is equivalent to mutating through [Self::get_mut], but returns
+    /// `true` if the value was persisted and `false` if it was unchanged. Use
+    /// this to trigger downstream effects only when something actually
+    /// changed, e.g. redrawing a UI.
+    pub fn modify(&mut self, f: impl FnOnce(&mut C)) -> bool
+    where
+        K::Value: PartialEq,
+    {
+        self.ensure_loaded();
+        f(&mut self.container);
+        self.persist_if_changed()
+    }
+
+    /// Convert into a [Persisted], whose value is the container's current
+    /// [PersistedContainer::get_to_persist]. The store is not accessed. The
+    /// container is dropped. If the container is deferred and hasn't been
+    /// loaded yet, it's loaded first.
+    pub fn into_eager(mut self) -> Persisted<S, K> {
+        self.ensure_loaded();
+        let value = self.container.get_to_persist();
+        // Release the key before the new wrapper claims it
+        self.registration.release();
+        Persisted::from_parts(
+            self.key,
+            value,
+            self.was_restored,
+            self.on_persist,
+        )
+    }
+
     /// Get a mutable reference to the value. This is wrapped by a guard, so
     /// that after mutation when the guard is dropped, the value can be
     /// persisted. [PersistedStore::store_persisted] will only be called if the
@@ -216,12 +586,43 @@ where
     /// This means [PersistedContainer::get_to_persist] will be called after
     /// event mutable access, but the value will only be written to the store
     /// when it's been modified.
-    pub fn get_mut(&mut self) -> PersistedLazyRefMut<S, K, C>
+    ///
+    /// If the container was created with [Self::new_deferred] and hasn't been
+    /// loaded yet, it will be loaded first.
+    pub fn get_mut(&mut self) -> PersistedLazyRefMut<'_, S, K, C>
     where
         K::Value: PartialEq,
     {
+        self.ensure_loaded();
         PersistedLazyRefMut { lazy: self }
     }
+
+    /// Get a mutable reference to the container, detecting changes by
+    /// comparing the *container* rather than the persisted value. The
+    /// container is cloned when the guard is created, and compared to the
+    /// mutated container when the guard is dropped. Only if they differ is
+    /// [PersistedContainer::get_to_persist] called and the value persisted.
+    ///
+    /// This inverts the strategy of [Self::get_mut], which is useful when the
+    /// container is cheap to clone and compare, but deriving the persisted
+    /// value is expensive. Note that a changed container is always persisted,
+    /// even if its persisted value is the same as before.
+    ///
+    /// If the container was created with [Self::new_deferred] and hasn't been
+    /// loaded yet, it will be loaded first.
+    pub fn get_mut_by_container_eq(
+        &mut self,
+    ) -> PersistedLazyContainerRefMut<'_, S, K, C>
+    where
+        C: Clone + PartialEq,
+    {
+        self.ensure_loaded();
+        let snapshot = self.container.clone();
+        PersistedLazyContainerRefMut {
+            lazy: self,
+            snapshot,
+        }
+    }
 }
 
 // Needed to omit Default bound on S
@@ -236,11 +637,45 @@ where
     }
 }
 
+/// Wrappers are compared by key only, ignoring the container. See the
+/// equivalent impl on [Persisted].
+impl<S, K, C> PartialEq for PersistedLazy<S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey + PartialEq,
+    C: PersistedContainer<Value = K::Value>,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<S, K, C> Eq for PersistedLazy<S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey + Eq,
+    C: PersistedContainer<Value = K::Value>,
+{
+}
+
+/// Hash the key only, consistent with [PartialEq]
+impl<S, K, C> Hash for PersistedLazy<S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey + Hash,
+    C: PersistedContainer<Value = K::Value>,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
 /// A guard encompassing the lifespan of a mutable reference to a lazy
 /// container. The purpose of this is to save the value immediately after it is
 /// mutated. **The save will only occur if the value actually changed.** A copy
 /// of the previous value is saved before the mutable access, and compared after
 /// the access.
+#[must_use = "the guard must be held to mutate; a bare get_mut() call just persists the current value"]
 #[derive(derive_more::Debug)]
 #[debug(bound(K::Value: Debug))]
 pub struct PersistedLazyRefMut<'a, S, K, C>
@@ -253,6 +688,28 @@ where
     lazy: &'a mut PersistedLazy<S, K, C>,
 }
 
+impl<'a, S, K, C> PersistedLazyRefMut<'a, S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    K::Value: PartialEq,
+    C: PersistedContainer<Value = K::Value>,
+{
+    /// Get the key of the container being mutated
+    pub fn key(&self) -> &K {
+        &self.lazy.key
+    }
+
+    /// Persist the container's value immediately if it changed, without
+    /// releasing the guard. This is useful for checkpointing partway through a
+    /// long series of mutations. The committed value becomes the baseline for
+    /// change detection, so dropping the guard won't write again unless the
+    /// value changes further. Return whether the value was persisted.
+    pub fn commit(&mut self) -> bool {
+        self.lazy.persist_if_changed()
+    }
+}
+
 impl<'a, S, K, C> Deref for PersistedLazyRefMut<'a, S, K, C>
 where
     S: PersistedStore<K>,
@@ -288,15 +745,95 @@ where
     C: PersistedContainer<Value = K::Value>,
 {
     fn drop(&mut self) {
+        self.lazy.persist_if_changed();
+    }
+}
+
+/// A guard encompassing the lifespan of a mutable reference to a lazy
+/// container, created by [PersistedLazy::get_mut_by_container_eq]. **The save
+/// will only occur if the container changed.** A clone of the container is
+/// saved before the mutable access, and compared after the access.
+#[must_use = "the guard must be held to mutate; a bare get_mut_by_container_eq() call does nothing"]
+#[derive(derive_more::Debug)]
+#[debug(bound(K::Value: Debug, C: Debug))]
+pub struct PersistedLazyContainerRefMut<'a, S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    C: PersistedContainer<Value = K::Value> + Clone + PartialEq,
+{
+    lazy: &'a mut PersistedLazy<S, K, C>,
+    /// The container before mutation
+    snapshot: C,
+}
+
+impl<'a, S, K, C> PersistedLazyContainerRefMut<'a, S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    C: PersistedContainer<Value = K::Value> + Clone + PartialEq,
+{
+    /// Get the key of the container being mutated
+    pub fn key(&self) -> &K {
+        &self.lazy.key
+    }
+
+    /// Persist the container's value immediately if the container changed,
+    /// without releasing the guard. This is useful for checkpointing partway
+    /// through a long series of mutations. The committed container becomes
+    /// the new snapshot, so dropping the guard won't write again unless the
+    /// container changes further. Return whether the value was persisted.
+    pub fn commit(&mut self) -> bool {
+        if self.lazy.container == self.snapshot {
+            return false;
+        }
         let persisted_value = self.lazy.container.get_to_persist();
-        if !self
-            .lazy
-            .last_persisted
-            .as_ref()
-            .is_some_and(|last_persisted| last_persisted == &persisted_value)
-        {
-            S::store_persisted(&self.lazy.key, &persisted_value);
-            self.lazy.last_persisted = Some(persisted_value);
+        if !self.lazy.persist_value(persisted_value) {
+            return false;
+        }
+        self.lazy.is_dirty = true;
+        self.snapshot.clone_from(&self.lazy.container);
+        true
+    }
+}
+
+impl<'a, S, K, C> Deref for PersistedLazyContainerRefMut<'a, S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    C: PersistedContainer<Value = K::Value> + Clone + PartialEq,
+{
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        &self.lazy.container
+    }
+}
+
+impl<'a, S, K, C> DerefMut for PersistedLazyContainerRefMut<'a, S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    C: PersistedContainer<Value = K::Value> + Clone + PartialEq,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.lazy.container
+    }
+}
+
+/// Save value after modification **only if the container changed**
+impl<'a, S, K, C> Drop for PersistedLazyContainerRefMut<'a, S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    C: PersistedContainer<Value = K::Value> + Clone + PartialEq,
+{
+    fn drop(&mut self) {
+        if self.lazy.container != self.snapshot {
+            let persisted_value = self.lazy.container.get_to_persist();
+            if self.lazy.persist_value(persisted_value) {
+                self.lazy.is_dirty = true;
+            }
         }
     }
 }
@@ -305,6 +842,12 @@ where
 /// conjunction with [PersistedLazy] to define how to lazily get the value that
 /// should be persisted, and how to restore state when a persisted value is
 /// loaded during initialization.
+///
+/// The same [Value](Self::Value) type is used in both directions, because it
+/// must match the key's [PersistedKey::Value], which is what the store loads
+/// and saves. If you need to restore from a richer form than you persist (or
+/// vice versa), make the value a type that can represent both, such as an
+/// enum, and handle each form in [Self::restore_persisted].
 pub trait PersistedContainer {
     /// The value to be persisted
     type Value;
@@ -314,4 +857,307 @@ pub trait PersistedContainer {
 
     /// Set the container's value, based on value loaded from the store
     fn restore_persisted(&mut self, value: Self::Value);
+
+    /// Attempt to set the container's value, based on a value loaded from the
+    /// store. Return `false` to reject the value, e.g. if it refers to an item
+    /// that no longer exists. A rejected value should leave the container
+    /// unmodified, and [PersistedLazy] will treat it as if nothing was loaded.
+    ///
+    /// The default implementation calls [Self::restore_persisted] and accepts
+    /// every value.
+    fn try_restore_persisted(&mut self, value: Self::Value) -> bool {
+        self.restore_persisted(value);
+        true
+    }
+
+    /// Get the current value to persist, but only if it differs from
+    /// `last_persisted`. [PersistedLazy] calls this after every mutable
+    /// access, so it's the hot path for containers that are accessed often
+    /// but rarely changed.
+    ///
+    /// The default implementation calls [Self::get_to_persist] and compares
+    /// the result. Override it if the container can be compared without
+    /// building a new value first. The derive macro does this for structs
+    /// with a single `#[persist]` field, which are only cloned when changed.
+    fn get_to_persist_if_changed(
+        &self,
+        last_persisted: &Self::Value,
+    ) -> Option<Self::Value>
+    where
+        Self::Value: PartialEq,
+    {
+        let value = self.get_to_persist();
+        (value != *last_persisted).then_some(value)
+    }
+}
+
+/// Persist an optional value directly. The entire `Option` is cloned to be
+/// persisted, and restoring overwrites it. If you don't need any derivation
+/// between the in-memory and persisted values, it's simpler to use
+/// [Persisted] with a key whose value is `Option<T>`. This
+/// impl is useful for generic code that needs a [PersistedContainer].
+impl<T: Clone> PersistedContainer for Option<T> {
+    type Value = Self;
+
+    fn get_to_persist(&self) -> Self::Value {
+        self.clone()
+    }
+
+    fn restore_persisted(&mut self, value: Self::Value) {
+        *self = value;
+    }
+
+    fn get_to_persist_if_changed(
+        &self,
+        last_persisted: &Self::Value,
+    ) -> Option<Self::Value>
+    where
+        Self::Value: PartialEq,
+    {
+        (self != last_persisted).then(|| self.clone())
+    }
+}
+
+/// A container that wraps another container, making its persisted value
+/// optional. The inner container is always present, but it can be flagged as
+/// `None` (e.g. nothing in a list is selected). When flagged as `None`, `None`
+/// is persisted. Otherwise, the inner container's value is persisted as
+/// `Some`. Similarly, restoring `Some` restores the inner container and flags
+/// it as `Some`, while restoring `None` just flags it as `None`, leaving the
+/// inner container untouched.
+///
+/// The inner container can be accessed via [Deref],
+/// regardless of whether this is flagged as `Some` or `None`.
+///
+/// ```
+/// use persisted::{OptionContainer, PersistedContainer};
+///
+/// /// Persist the selected index of a list
+/// struct SelectList {
+///     values: Vec<&'static str>,
+///     selected_index: usize,
+/// }
+///
+/// impl PersistedContainer for SelectList {
+///     type Value = usize;
+///
+///     fn get_to_persist(&self) -> Self::Value {
+///         self.selected_index
+///     }
+///
+///     fn restore_persisted(&mut self, value: Self::Value) {
+///         self.selected_index = value;
+///     }
+/// }
+///
+/// let mut list = OptionContainer::none(SelectList {
+///     values: vec!["a", "b"],
+///     selected_index: 0,
+/// });
+/// assert_eq!(list.get_to_persist(), None);
+/// list.restore_persisted(Some(1));
+/// assert!(list.is_some());
+/// assert_eq!(list.selected_index, 1);
+/// ```
+#[derive(Clone, Debug, Default, Deref, DerefMut)]
+pub struct OptionContainer<C> {
+    #[deref]
+    #[deref_mut]
+    container: C,
+    is_some: bool,
+}
+
+impl<C> OptionContainer<C> {
+    /// Wrap a container, flagged as `Some`
+    pub fn some(container: C) -> Self {
+        Self {
+            container,
+            is_some: true,
+        }
+    }
+
+    /// Wrap a container, flagged as `None`
+    pub fn none(container: C) -> Self {
+        Self {
+            container,
+            is_some: false,
+        }
+    }
+
+    /// Is the container flagged as `Some`?
+    pub fn is_some(&self) -> bool {
+        self.is_some
+    }
+
+    /// Flag the container as either `Some` or `None`
+    pub fn set_is_some(&mut self, is_some: bool) {
+        self.is_some = is_some;
+    }
+
+    /// Get the inner container if flagged as `Some`
+    pub fn as_option(&self) -> Option<&C> {
+        self.is_some.then_some(&self.container)
+    }
+
+    /// Get the inner container mutably if flagged as `Some`
+    pub fn as_option_mut(&mut self) -> Option<&mut C> {
+        self.is_some.then_some(&mut self.container)
+    }
+
+    /// Unwrap the inner container, regardless of flag
+    pub fn into_inner(self) -> C {
+        self.container
+    }
+}
+
+impl<C: PersistedContainer> PersistedContainer for OptionContainer<C> {
+    type Value = Option<C::Value>;
+
+    fn get_to_persist(&self) -> Self::Value {
+        self.as_option().map(C::get_to_persist)
+    }
+
+    fn restore_persisted(&mut self, value: Self::Value) {
+        if let Some(value) = value {
+            self.container.restore_persisted(value);
+            self.is_some = true;
+        } else {
+            self.is_some = false;
+        }
+    }
+
+    fn try_restore_persisted(&mut self, value: Self::Value) -> bool {
+        if let Some(value) = value {
+            let accepted = self.container.try_restore_persisted(value);
+            if accepted {
+                self.is_some = true;
+            }
+            accepted
+        } else {
+            self.is_some = false;
+            true
+        }
+    }
+}
+
+/// A container that wraps another container, converting between the inner
+/// container's value and a wider stored value `V`. When persisting, the inner
+/// value is converted with [From]. When restoring, the stored value is
+/// converted with [TryFrom] *before* it reaches the inner container. If the
+/// conversion fails, the value is rejected (see
+/// [PersistedContainer::try_restore_persisted]), and the inner container is
+/// left unchanged.
+///
+/// This is useful when stored values may have drifted out of the range that
+/// the container accepts, e.g. values persisted by an older version of your
+/// program. The conversion handles the drift in one place, so the inner
+/// container doesn't have to validate defensively.
+///
+/// The inner container can be accessed via [Deref].
+///
+/// ```
+/// use persisted::{
+///     PersistedContainer, PersistedKey, PersistedLazy, PersistedStore,
+///     TryFromContainer,
+/// };
+/// use std::num::NonZeroU32;
+/// # struct Store;
+/// # impl PersistedStore<PageSizeKey> for Store {
+/// #     fn load_persisted(_: &PageSizeKey) -> Option<u32> { Some(0) }
+/// #     fn store_persisted(_: &PageSizeKey, _: &u32) {}
+/// # }
+///
+/// /// Older versions allowed a page size of 0
+/// #[derive(PersistedKey)]
+/// #[persisted(u32)]
+/// struct PageSizeKey;
+///
+/// struct Pager {
+///     page_size: NonZeroU32,
+/// }
+///
+/// impl PersistedContainer for Pager {
+///     type Value = NonZeroU32;
+///
+///     fn get_to_persist(&self) -> Self::Value {
+///         self.page_size
+///     }
+///
+///     fn restore_persisted(&mut self, value: Self::Value) {
+///         self.page_size = value;
+///     }
+/// }
+///
+/// // The store has a 0, which can't be converted, so it's skipped
+/// let pager = PersistedLazy::<Store, _, _>::new(
+///     PageSizeKey,
+///     TryFromContainer::new(Pager {
+///         page_size: NonZeroU32::new(20).unwrap(),
+///     }),
+/// );
+/// assert!(!pager.was_restored());
+/// assert_eq!(pager.page_size.get(), 20);
+/// ```
+#[derive(derive_more::Debug, Deref, DerefMut)]
+#[debug(bound(C: Debug))]
+pub struct TryFromContainer<C, V> {
+    #[deref]
+    #[deref_mut]
+    container: C,
+    #[debug(skip)]
+    phantom: PhantomData<V>,
+}
+
+impl<C, V> TryFromContainer<C, V> {
+    /// Wrap a container
+    pub fn new(container: C) -> Self {
+        Self {
+            container,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Unwrap the inner container
+    pub fn into_inner(self) -> C {
+        self.container
+    }
+}
+
+// Needed to omit Clone bound on V
+impl<C: Clone, V> Clone for TryFromContainer<C, V> {
+    fn clone(&self) -> Self {
+        Self::new(self.container.clone())
+    }
+}
+
+// Needed to omit Default bound on V
+impl<C: Default, V> Default for TryFromContainer<C, V> {
+    fn default() -> Self {
+        Self::new(C::default())
+    }
+}
+
+impl<C, V> PersistedContainer for TryFromContainer<C, V>
+where
+    C: PersistedContainer,
+    C::Value: TryFrom<V>,
+    V: From<C::Value>,
+{
+    type Value = V;
+
+    fn get_to_persist(&self) -> Self::Value {
+        self.container.get_to_persist().into()
+    }
+
+    /// Values that fail to convert are ignored
+    fn restore_persisted(&mut self, value: Self::Value) {
+        self.try_restore_persisted(value);
+    }
+
+    fn try_restore_persisted(&mut self, value: Self::Value) -> bool {
+        match C::Value::try_from(value) {
+            Ok(value) => self.container.try_restore_persisted(value),
+            Err(_) => false,
+        }
+    }
 }
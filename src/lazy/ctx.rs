@@ -0,0 +1,255 @@
+use crate::{
+    collision::KeyRegistration, load, persist, PersistedKey, PersistedStore,
+};
+use core::{
+    fmt::Debug,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+/// Similar to [PersistedLazy](crate::PersistedLazy), but the container needs
+/// access to some external context to compute or restore its persisted value.
+/// This is useful when the container is a view over data that lives
+/// elsewhere, such as a shared data model. The context is passed in whenever
+/// the persisted value is needed, rather than being stored in the wrapper.
+///
+/// Since the context is required to detect changes, mutable access via
+/// [Self::get_mut] takes the context as well.
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{
+///     PersistedContainerCtx, PersistedKey, PersistedLazyCtx, PersistedStore,
+/// };
+/// # struct Store;
+/// # impl<K: PersistedKey> PersistedStore<K> for Store {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+///
+/// /// The data model, which lives outside the container
+/// struct People {
+///     names: Vec<&'static str>,
+/// }
+///
+/// /// Which person is selected, by index into the data model
+/// #[derive(Default)]
+/// struct Selection {
+///     index: usize,
+/// }
+///
+/// /// Persist the *name* of the selected person, which requires the model
+/// impl PersistedContainerCtx for Selection {
+///     type Value = String;
+///     type Context = People;
+///
+///     fn get_to_persist(&self, people: &People) -> Self::Value {
+///         people.names[self.index].to_owned()
+///     }
+///
+///     fn restore_persisted(&mut self, people: &People, value: Self::Value) {
+///         if let Some(index) = people.names.iter().position(|n| *n == value) {
+///             self.index = index;
+///         }
+///     }
+/// }
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(String)]
+/// struct SelectedNameKey;
+///
+/// let people = People {
+///     names: vec!["Fred", "Susan"],
+/// };
+/// let mut selection =
+///     PersistedLazyCtx::<Store, _, Selection>::new_default(
+///         SelectedNameKey,
+///         &people,
+///     );
+/// selection.get_mut(&people).index = 1;
+/// assert_eq!(selection.last_persisted().map(String::as_str), Some("Susan"));
+/// ```
+#[derive(derive_more::Debug)]
+#[debug(bound(K: Debug, K::Value: Debug, C: Debug))]
+pub struct PersistedLazyCtx<S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    C: PersistedContainerCtx<Value = K::Value>,
+{
+    #[debug(skip)] // Omit bound on S
+    backend: PhantomData<S>,
+    key: K,
+    /// Cache the most recently persisted value so we can check if it's changed
+    /// after each mutable access
+    last_persisted: Option<K::Value>,
+    container: C,
+    #[debug(skip)]
+    #[allow(dead_code)] // Only held to unregister the key on drop
+    registration: KeyRegistration,
+}
+
+impl<S, K, C> PersistedLazyCtx<S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    C: PersistedContainerCtx<Value = K::Value>,
+{
+    /// Initialize a given container whose value will lazily be loaded and
+    /// persisted. If a persisted value is available in the store, it will be
+    /// loaded, passed through [PersistedKey::validate], and used to initialize
+    /// the container via [PersistedContainerCtx::restore_persisted]. A
+    /// restored value becomes the baseline for change detection.
+    pub fn new(key: K, mut container: C, ctx: &C::Context) -> Self {
+        // The store already has the restored value, so it doesn't need to be
+        // written back until it changes
+        let last_persisted = load::<S, K>(&key).map(|value| {
+            container.restore_persisted(ctx, value);
+            container.get_to_persist(ctx)
+        });
+        Self {
+            backend: PhantomData,
+            registration: KeyRegistration::new(&key),
+            key,
+            last_persisted,
+            container,
+        }
+    }
+
+    /// Initialize a new default container whose value will lazily be loaded
+    /// and persisted. See [Self::new].
+    pub fn new_default(key: K, ctx: &C::Context) -> Self
+    where
+        C: Default,
+    {
+        Self::new(key, C::default(), ctx)
+    }
+
+    /// Get the value that was most recently persisted by this wrapper. See
+    /// [PersistedLazy::last_persisted](crate::PersistedLazy::last_persisted).
+    pub fn last_persisted(&self) -> Option<&K::Value> {
+        self.last_persisted.as_ref()
+    }
+
+    /// Persist the container's current value to the store immediately,
+    /// regardless of whether it has changed
+    pub fn persist(&mut self, ctx: &C::Context) {
+        let persisted_value = self.container.get_to_persist(ctx);
+        if persist::<S, K>(
+            &self.key,
+            self.last_persisted.as_ref(),
+            &persisted_value,
+        ) {
+            self.last_persisted = Some(persisted_value);
+        }
+    }
+
+    /// Persist the container's current value, if it differs from the last
+    /// persisted value
+    fn persist_if_changed(&mut self, ctx: &C::Context)
+    where
+        K::Value: PartialEq,
+    {
+        let persisted_value = self.container.get_to_persist(ctx);
+        if self.last_persisted.as_ref() != Some(&persisted_value) {
+            self.persist(ctx);
+        }
+    }
+
+    /// Get a mutable reference to the container. This is wrapped by a guard,
+    /// so that after mutation when the guard is dropped, the value can be
+    /// persisted **only if it changed**. The context is held by the guard, so
+    /// it can be used to compute the persisted value on drop.
+    pub fn get_mut<'a>(
+        &'a mut self,
+        ctx: &'a C::Context,
+    ) -> PersistedLazyCtxRefMut<'a, S, K, C>
+    where
+        K::Value: PartialEq,
+    {
+        PersistedLazyCtxRefMut { lazy: self, ctx }
+    }
+}
+
+impl<S, K, C> Deref for PersistedLazyCtx<S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    C: PersistedContainerCtx<Value = K::Value>,
+{
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        &self.container
+    }
+}
+
+/// A guard encompassing the lifespan of a mutable reference to a lazy
+/// container with context. See [PersistedLazyCtx::get_mut].
+#[must_use = "the guard must be held to mutate; a bare get_mut() call just persists the current value"]
+pub struct PersistedLazyCtxRefMut<'a, S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    K::Value: PartialEq,
+    C: PersistedContainerCtx<Value = K::Value>,
+{
+    lazy: &'a mut PersistedLazyCtx<S, K, C>,
+    ctx: &'a C::Context,
+}
+
+impl<'a, S, K, C> Deref for PersistedLazyCtxRefMut<'a, S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    K::Value: PartialEq,
+    C: PersistedContainerCtx<Value = K::Value>,
+{
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        &self.lazy.container
+    }
+}
+
+impl<'a, S, K, C> DerefMut for PersistedLazyCtxRefMut<'a, S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    K::Value: PartialEq,
+    C: PersistedContainerCtx<Value = K::Value>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.lazy.container
+    }
+}
+
+/// Save value after modification **only if it changed**
+impl<'a, S, K, C> Drop for PersistedLazyCtxRefMut<'a, S, K, C>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    K::Value: PartialEq,
+    C: PersistedContainerCtx<Value = K::Value>,
+{
+    fn drop(&mut self) {
+        self.lazy.persist_if_changed(self.ctx);
+    }
+}
+
+/// A container that needs external context to provide and restore its
+/// persisted value. This is the equivalent of
+/// [PersistedContainer](crate::PersistedContainer) for [PersistedLazyCtx].
+pub trait PersistedContainerCtx {
+    /// The value to be persisted
+    type Value;
+    /// Context needed to compute and restore the persisted value
+    type Context: ?Sized;
+
+    /// Get the current value to persist in the store
+    fn get_to_persist(&self, ctx: &Self::Context) -> Self::Value;
+
+    /// Set the container's value, based on value loaded from the store
+    fn restore_persisted(&mut self, ctx: &Self::Context, value: Self::Value);
+}
@@ -1,4 +1,5 @@
 #![no_std]
+#![forbid(unsafe_code)]
 
 //! `persisted` is a library for persisting arbitrary values in your program so
 //! they can easily be restored later. The main goals of the library are:
@@ -26,6 +27,8 @@
 //!     - These wrap your data to automatically restore and save values from/to
 //!       the store
 //! - Data store: any implementor of [PersistedStore]
+//!     - Stores can be composed with combinators such as [TeeStore],
+//!       [EitherStore], and [FallbackStore]
 //! - Key: A unique identifier for a value in the store. Each persisted value
 //!   must have its own key. Key types must implement [PersistedKey].
 //!
@@ -136,22 +139,253 @@
 //! `persisted` supports the following Cargo features:
 //! - `derive` (default): Enable derive macros
 //! - `serde`: Enable `Serialize/Deserialize` implementations
+//! - `alloc`: Enable features that require allocation (e.g. `String` and
+//!   `Vec`), for `no_std` targets that have a heap. Implied by `std`
+//! - `std`: Enable features that require the standard library
+//! - `json-file`: Enable `JsonFileStore`, a store that persists to a JSON file.
+//!   Implies `serde` and `std`
+//! - `metrics`: Enable the `metrics` module, which counts store accesses
+//! - `keyring`: Enable `KeyringStore`, a store that persists secrets to the
+//!   platform's secret store. Implies `std`
+//! - `log`: Enable `LoggingStore`, a store combinator that logs all operations
+//! - `inventory`: Register key types at compile time, so their identifiers can
+//!   be checked for collisions with `validate_keys`. Implies `std`
+//! - `serde-json`, `ron`, `bincode`: Enable the corresponding `Codec` for
+//!   `CodecStore`. Each implies `serde` and `std`. `serde-json` also enables
+//!   `Snapshot`, for exporting and importing many values at once
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub extern crate alloc as __alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+mod builder;
+mod cell;
+#[cfg(feature = "alloc")]
+mod codec;
+mod collision;
+#[cfg(feature = "std")]
+mod dyn_store;
 mod eager;
+#[cfg(feature = "serde")]
+mod entry;
+#[cfg(feature = "std")]
+mod global;
+#[cfg(feature = "json-file")]
+mod json_file;
+#[cfg(feature = "alloc")]
+mod key_codec;
+#[cfg(feature = "keyring")]
+mod keyring;
 mod lazy;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "inventory")]
+mod registry;
+mod secret;
+#[cfg(feature = "alloc")]
+mod select;
+#[cfg(all(feature = "serde", feature = "alloc"))]
+mod serializable;
+#[cfg(feature = "std")]
+mod set;
+#[cfg(feature = "serde-json")]
+mod snapshot;
+mod store;
 
+#[cfg(feature = "bincode")]
+pub use crate::codec::BincodeCodec;
+#[cfg(feature = "serde-json")]
+pub use crate::codec::JsonCodec;
+#[cfg(feature = "ron")]
+pub use crate::codec::RonCodec;
+#[cfg(feature = "alloc")]
+pub use crate::codec::{ByteStore, Codec, CodecStore};
+#[cfg(feature = "std")]
+pub use crate::dyn_store::DynStore;
+#[cfg(feature = "serde")]
+pub use crate::entry::{PersistedEntry, PersistedEntryOwned};
+#[cfg(feature = "std")]
+pub use crate::global::{GlobalStore, RwLockStore};
+#[cfg(feature = "json-file")]
+pub use crate::json_file::{JsonFile, JsonFileStore};
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub use crate::key_codec::__join_key_id;
+#[cfg(feature = "alloc")]
+pub use crate::key_codec::KeyCodec;
+#[cfg(feature = "keyring")]
+pub use crate::keyring::{KeyringService, KeyringStore};
+#[cfg(feature = "inventory")]
+#[doc(hidden)]
+pub use crate::registry::__inventory;
+#[cfg(feature = "inventory")]
+pub use crate::registry::{
+    registered_keys, validate_keys, KeyCollision, RegisteredKey,
+};
+#[cfg(feature = "alloc")]
+pub use crate::select::IdSelect;
+#[cfg(all(feature = "serde", feature = "alloc"))]
+pub use crate::serializable::SerializablePersistedStore;
+#[cfg(feature = "std")]
+pub use crate::set::PersistedSet;
+#[cfg(feature = "serde-json")]
+pub use crate::snapshot::Snapshot;
+#[cfg(feature = "log")]
+pub use crate::store::LoggingStore;
+#[cfg(feature = "std")]
+pub use crate::store::{
+    DefaultRetryPolicy, Expiring, RetryPolicy, RetryStore, TryPersistedStore,
+    TtlKey, TtlStore,
+};
 pub use crate::{
-    eager::Persisted,
-    lazy::{PersistedContainer, PersistedLazy, PersistedLazyRefMut},
+    builder::{NoDefault, PersistedBuilder, PersistedLazyBuilder, WithDefault},
+    cell::PersistedCopyCell,
+    eager::{LensRefMut, Persisted},
+    lazy::{
+        OptionContainer, PersistedContainer, PersistedContainerCtx,
+        PersistedLazy, PersistedLazyContainerRefMut, PersistedLazyCtx,
+        PersistedLazyCtxRefMut, PersistedLazyRefMut, TryFromContainer,
+    },
+    secret::Secret,
+    store::{
+        AsyncPersistedStore, BlockOn, BlockingStore, EitherStore,
+        FallbackStore, KeyRenames, NamedStore, RemapStore, StoreSelector,
+        TeeStore,
+    },
 };
+/// Derive macro for [KeyCodec]
+///
+/// On a struct, the identifier is the `/`-separated list of its fields, in
+/// declaration order. On an enum, it's the variant name followed by the
+/// variant's fields. All fields must implement
+/// [Display](core::fmt::Display). Unit structs have an empty identifier.
+#[cfg(all(feature = "derive", feature = "alloc"))]
+pub use persisted_derive::KeyCodec;
+/// Derive macro for [PersistedContainer]
+///
+/// ## Enums
+///
+/// Fieldless enums can be persisted as their integer discriminant with
+/// `#[persisted(enum_repr = <type>)]`. This allows you to rename variants
+/// without breaking previously persisted values, as long as the
+/// discriminants don't change. The derive also generates `From<Enum>` for
+/// the integer type and `TryFrom<integer>` for the enum. If an unknown
+/// discriminant is loaded, it is ignored and the container keeps its
+/// initial value.
+///
+/// ```
+/// use persisted::{PersistedContainer, PersistedKey};
+///
+/// #[derive(Copy, Clone, Debug, PartialEq, PersistedContainer)]
+/// #[persisted(enum_repr = u8)]
+/// enum Theme {
+///     Light = 0,
+///     Dark = 1,
+/// }
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(u8)]
+/// struct ThemeKey;
+///
+/// assert_eq!(Theme::Dark.get_to_persist(), 1);
+/// ```
+///
+/// ## Structs
+///
+/// On a struct, mark one or more fields with `#[persist]`. Marked fields
+/// will be cloned to be persisted, and overwritten when a persisted value
+/// is restored. All other fields are left alone. If one field is marked,
+/// the persisted value is that field's type. If multiple fields are
+/// marked, it's a tuple of their types, in declaration order.
+///
+/// ```
+/// use persisted::PersistedContainer;
+///
+/// #[derive(PersistedContainer)]
+/// struct SelectList {
+///     values: Vec<String>,
+///     #[persist]
+///     selected_index: usize,
+/// }
+///
+/// let mut list = SelectList {
+///     values: vec!["a".into(), "b".into()],
+///     selected_index: 0,
+/// };
+/// list.restore_persisted(1);
+/// assert_eq!(list.get_to_persist(), 1);
+///
+/// #[derive(PersistedContainer)]
+/// struct Viewport {
+///     #[persist]
+///     scroll: u32,
+///     #[persist]
+///     zoom: u8,
+///     hovered: Option<usize>,
+/// }
+///
+/// let viewport = Viewport {
+///     scroll: 40,
+///     zoom: 2,
+///     hovered: None,
+/// };
+/// assert_eq!(viewport.get_to_persist(), (40, 2));
+/// ```
+#[cfg(feature = "derive")]
+pub use persisted_derive::PersistedContainer;
 /// Derive macro for [PersistedKey]
+///
+/// The value type is given with `#[persisted(<type>)]`. Optional arguments
+/// can follow the type, separated by commas:
+///
+/// - `validate = <path>`: See [PersistedKey::validate]
+/// - `skip_if_default`: See [PersistedKey::skip_if_default]
+/// - `store_selector = <Left|Right>`: See [PersistedKey::store_selector]
+/// - `group = "<name>"`: See [PersistedKey::group]
+/// - `id = <u64>`: See [PersistedKey::type_id]
+/// - `ttl_secs = <u64>`: See [PersistedKey::ttl]
+/// - `serde`: Assert that the value type implements `serde::Serialize` and
+///   `serde::de::DeserializeOwned`. If your store serializes values, this
+///   reports a missing impl at the key definition rather than where the
+///   store is used. Requires `serde` as a dependency of your crate.
+/// - `detect_collisions`: See [PersistedKey::key_hash]. Requires the key
+///   to implement [Hash]
+/// - `transparent`: Set [PersistedKey::type_name] to `""`, for stores
+///   whose format already includes the type. See [PersistedKey::type_name]
+///
+/// With the `inventory` feature, the derive also registers non-generic key
+/// types for collision checking with `validate_keys`. See [register_key].
+///
+/// For keys that hold a value of their own value type (e.g. a typed ID),
+/// the value type can be taken from a field with `#[persisted(value =
+/// <field>)]`, where `<field>` is a field name or tuple index:
+///
+/// ```
+/// use persisted::PersistedKey;
+///
+/// #[derive(Copy, Clone, Debug, PartialEq)]
+/// struct PersonId(u64);
+///
+/// /// Which person replaces the given person, after merging duplicates
+/// #[derive(PersistedKey)]
+/// #[persisted(value = 0)]
+/// struct ReplacedByKey(PersonId);
+///
+/// let _: <ReplacedByKey as PersistedKey>::Value = PersonId(3);
+/// ```
 #[cfg(feature = "derive")]
 pub use persisted_derive::PersistedKey;
 
 use core::{
     any,
     fmt::{self, Debug},
+    hash::{Hash, Hasher},
     marker::PhantomData,
+    time::Duration,
 };
 
 /// A trait for any data store capable of persisting data. A store is the layer
@@ -191,6 +425,220 @@ pub trait PersistedStore<K: PersistedKey> {
 
     /// Persist a value in the store, under the given key
     fn store_persisted(key: &K, value: &K::Value);
+
+    /// Persist a value in the store, with access to the previous value. This
+    /// is what wrappers call when persisting, so stores that want to track
+    /// changes (e.g. for an audit log or undo stack) can override it. The
+    /// default implementation ignores the old value and forwards to
+    /// [store_persisted](Self::store_persisted).
+    ///
+    /// `old` is the previous value, if known by the wrapper. [PersistedLazy]
+    /// passes the last value it loaded or persisted (see
+    /// [PersistedLazy::last_persisted]). [Persisted] passes the value from
+    /// before the mutation when mutated via [Persisted::get_mut_diff], which
+    /// clones the value to snapshot it. Other writes from [Persisted] pass
+    /// `None`, to avoid cloning on every mutation.
+    fn store_persisted_diff(key: &K, _old: Option<&K::Value>, new: &K::Value) {
+        Self::store_persisted(key, new);
+    }
+
+    /// Remove the value for the given key from the store, so that subsequent
+    /// loads return `None`. This is called instead of
+    /// [store_persisted](Self::store_persisted) for keys that opt into
+    /// [PersistedKey::skip_if_default] when their value is the default. The
+    /// default implementation does nothing, so stores that are used with such
+    /// keys **must** override this. Otherwise the previous non-default value
+    /// would linger in the store.
+    fn remove_persisted(_key: &K) {}
+
+    /// Store `new` under the given key, but only if the currently stored value
+    /// equals `expected`, where `None` means the store has no value. Return
+    /// whether the value was stored. This enables lost-update-free writes
+    /// against stores shared with other processes, e.g. incrementing a shared
+    /// counter. See [Persisted::try_set_if].
+    ///
+    /// The default implementation loads, compares, then stores. This is
+    /// **not** atomic, so another writer could modify the value in between.
+    /// Stores that can compare and write in a single operation (e.g. SQL with a
+    /// `WHERE` clause, or a Redis transaction) should override this.
+    ///
+    /// For keys that opt into [PersistedKey::skip_if_default], a default `new`
+    /// value must be removed rather than stored, just like
+    /// [remove_persisted](Self::remove_persisted) replaces
+    /// [store_persisted](Self::store_persisted). Overrides should handle this
+    /// too. A default `expected` value is passed as `None`, because the store
+    /// never holds it.
+    fn compare_and_swap(
+        key: &K,
+        expected: Option<&K::Value>,
+        new: &K::Value,
+    ) -> bool
+    where
+        K::Value: PartialEq,
+    {
+        if Self::load_persisted(key).as_ref() == expected {
+            if K::skip_if_default() && K::is_default(new) {
+                Self::remove_persisted(key);
+            } else {
+                Self::store_persisted(key, new);
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Does the store contain a value for the given key? The default
+    /// implementation loads the value and checks if it's present. Stores that
+    /// can check for presence without loading the whole value may want to
+    /// override this.
+    fn contains(key: &K) -> bool {
+        Self::load_persisted(key).is_some()
+    }
+
+    /// Get all keys of type `K` that have a value in the store. This enables
+    /// bulk operations such as exporting or clearing all values of a type.
+    /// Enumerating keys requires the store to be able to reconstruct them from
+    /// their stored form, which not all stores can do. For that reason, this
+    /// is best-effort: the default implementation returns an empty list.
+    ///
+    /// ```
+    /// use persisted::{PersistedKey, PersistedStore};
+    /// use std::{cell::RefCell, collections::HashMap};
+    ///
+    /// #[derive(Clone, PartialEq, Eq, Hash, PersistedKey)]
+    /// #[persisted(bool)]
+    /// struct ToggleKey(u32);
+    ///
+    /// struct Store;
+    ///
+    /// impl Store {
+    ///     thread_local! {
+    ///         static INSTANCE: RefCell<HashMap<ToggleKey, bool>> =
+    ///             Default::default();
+    ///     }
+    /// }
+    ///
+    /// impl PersistedStore<ToggleKey> for Store {
+    ///     fn load_persisted(key: &ToggleKey) -> Option<bool> {
+    ///         Self::INSTANCE.with(|map| map.borrow().get(key).copied())
+    ///     }
+    ///
+    ///     fn store_persisted(key: &ToggleKey, value: &bool) {
+    ///         Self::INSTANCE
+    ///             .with(|map| map.borrow_mut().insert(key.clone(), *value));
+    ///     }
+    ///
+    ///     fn keys() -> Vec<ToggleKey> {
+    ///         Self::INSTANCE.with(|map| map.borrow().keys().cloned().collect())
+    ///     }
+    /// }
+    ///
+    /// Store::store_persisted(&ToggleKey(3), &true);
+    /// assert_eq!(Store::keys().len(), 1);
+    /// ```
+    #[cfg(feature = "alloc")]
+    fn keys() -> alloc::vec::Vec<K> {
+        alloc::vec::Vec::new()
+    }
+
+    /// Durably write any buffered changes. Stores that buffer writes in memory
+    /// (e.g. to avoid an fsync on every mutation) can use this as an explicit
+    /// durability boundary. The wrappers never call this; it's up to you to
+    /// call it at appropriate points, such as program exit or an explicit
+    /// "save" action. See [flush] for a shorthand. The default implementation
+    /// does nothing.
+    fn flush() {}
+
+    /// Preload values into memory, so that subsequent loads are fast. Stores
+    /// that are slow to read from (e.g. a file or remote database) can
+    /// implement this to do one bulk read up front, rather than many small
+    /// reads as each wrapper is created. Like [Self::flush], the wrappers
+    /// never call this; call it yourself at startup. The default
+    /// implementation does nothing.
+    fn warm_up() {}
+
+    /// Called by the store when a value is present for a key, but can't be
+    /// converted to [PersistedKey::Value]. This typically happens in
+    /// dynamically typed stores (e.g. JSON or SQL) after the key's value type
+    /// changes between versions of your program.
+    ///
+    /// The wrappers never call this; it's a convention for store
+    /// implementations. Rather than panicking on a bad value,
+    /// [Self::load_persisted] should call this hook and then return `None`, so
+    /// the wrapper falls back to its default value. Implement this to report
+    /// the error, e.g. via logging. The default implementation does nothing.
+    ///
+    /// ```
+    /// use persisted::{PersistedKey, PersistedStore};
+    /// use std::{cell::RefCell, collections::HashMap, str::FromStr};
+    ///
+    /// /// Store all values as strings
+    /// struct Store;
+    ///
+    /// impl Store {
+    ///     thread_local! {
+    ///         static INSTANCE: RefCell<HashMap<&'static str, String>> =
+    ///             Default::default();
+    ///     }
+    /// }
+    ///
+    /// impl<K> PersistedStore<K> for Store
+    /// where
+    ///     K: PersistedKey,
+    ///     K::Value: ToString + FromStr,
+    /// {
+    ///     fn load_persisted(key: &K) -> Option<K::Value> {
+    ///         let value = Self::INSTANCE
+    ///             .with(|map| map.borrow().get(K::type_name()).cloned())?;
+    ///         match value.parse() {
+    ///             Ok(value) => Some(value),
+    ///             Err(_) => {
+    ///                 Self::on_load_error(key);
+    ///                 None
+    ///             }
+    ///         }
+    ///     }
+    ///
+    ///     fn store_persisted(_key: &K, value: &K::Value) {
+    ///         Self::INSTANCE.with(|map| {
+    ///             map.borrow_mut().insert(K::type_name(), value.to_string())
+    ///         });
+    ///     }
+    ///
+    ///     fn on_load_error(_key: &K) {
+    ///         eprintln!("Invalid persisted value for {}", K::type_name());
+    ///     }
+    /// }
+    /// ```
+    fn on_load_error(_key: &K) {}
+}
+
+/// Flush buffered changes in store `S` for key type `K`. This is shorthand for
+/// [PersistedStore::flush], which otherwise requires a fully qualified path
+/// to disambiguate the key type.
+///
+/// ```
+/// use persisted::{PersistedKey, PersistedStore};
+/// # struct Store;
+/// # impl<K: PersistedKey> PersistedStore<K> for Store {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(u32)]
+/// struct CountKey;
+///
+/// // At shutdown
+/// persisted::flush::<Store, CountKey>();
+/// ```
+pub fn flush<S, K>()
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+{
+    S::flush();
 }
 
 /// A unique key mapped to a persisted state value in your program. A key can
@@ -227,6 +675,56 @@ pub trait PersistedStore<K: PersistedKey> {
 ///     Component2,
 /// }
 /// ```
+///
+/// Enum variants can carry data too. Keep in mind that all variants share the
+/// key type's [type_name](Self::type_name), so a store that keys on the type
+/// name alone will put every variant in the same slot. The store must also
+/// include the key's *content*, e.g. by serializing it or via a
+/// [Display](core::fmt::Display) impl that includes the variant data:
+///
+/// ```
+/// use persisted::PersistedKey;
+/// use std::fmt::{self, Display};
+///
+/// /// Scroll position, per tab or for the global view
+/// #[derive(PersistedKey)]
+/// #[persisted(u32)]
+/// enum ScrollKey {
+///     Tab(u64),
+///     Global,
+/// }
+///
+/// impl Display for ScrollKey {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         match self {
+///             Self::Tab(id) => write!(f, "tab/{id}"),
+///             Self::Global => write!(f, "global"),
+///         }
+///     }
+/// }
+/// ```
+///
+/// Keys don't need to be `'static`. A key can borrow data, such as a name,
+/// as long as the data outlives the wrapper holding the key. The lifetime is
+/// carried by the key type, so the wrappers don't need a lifetime parameter of
+/// their own:
+///
+/// ```
+/// use persisted::{Persisted, PersistedKey, PersistedStore};
+/// # struct Store;
+/// # impl<K: PersistedKey> PersistedStore<K> for Store {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(bool)]
+/// struct PanelVisibleKey<'a>(&'a str);
+///
+/// let name = String::from("sidebar");
+/// let visible = Persisted::<Store, _>::new(PanelVisibleKey(&name), true);
+/// assert!(visible.get());
+/// ```
 pub trait PersistedKey {
     /// The type of the persisted value associated with this key
     type Value;
@@ -245,21 +743,203 @@ pub trait PersistedKey {
     /// actually employ this function, it's provided merely as a utility.
     ///
     /// In most cases this you can rely on the derive implementation, which uses
-    /// [core::any::type_name]. However, for wrapper key types (e.g.
+    /// [Self::TYPE_NAME]. However, for wrapper key types (e.g.
     /// [SingletonKey]), this should return the name of the wrapped type.
     ///
     /// Using this is *not* necessary if you use a persistence format that
     /// includes the type name, e.g. [RON](https://github.com/ron-rs/ron). If
-    /// that's the case your implementations of this can return `""` (or panic),
-    /// but in most cases it's easier just to use the derive macro anyway, and
-    /// just don't call this function.
-    fn type_name() -> &'static str;
+    /// that's the case, derive with `#[persisted(<type>, transparent)]` to
+    /// make this return `""`, signaling to the store that no prefix is needed.
+    /// Because all transparent keys share the same name, they also share the
+    /// default [Self::type_id], so assign explicit IDs if your store uses
+    /// them. Transparent keys aren't registered for `validate_keys`.
+    ///
+    /// ```
+    /// use persisted::PersistedKey;
+    ///
+    /// #[derive(PersistedKey)]
+    /// #[persisted(u32, transparent)]
+    /// struct VolumeKey;
+    ///
+    /// assert_eq!(VolumeKey::type_name(), "");
+    /// ```
+    ///
+    /// The default implementation returns [Self::TYPE_NAME] if set, otherwise
+    /// [core::any::type_name].
+    fn type_name() -> &'static str {
+        match Self::TYPE_NAME {
+            Some(name) => name,
+            None => any::type_name::<Self>(),
+        }
+    }
+
+    /// A unique name for this key type, available in `const` contexts. If
+    /// set, this is returned by [Self::type_name]. This allows stores to use
+    /// the name in `const` positions, e.g. to build static lookup tables. It's
+    /// also cheaper than a [Self::type_name] implementation that builds the
+    /// name at runtime.
+    ///
+    /// This is optional because not all key types can name themselves at
+    /// compile time (e.g. [SingletonKey], which is generic). The derive macro
+    /// sets this to the module path and name of the key type.
+    ///
+    /// ```
+    /// use persisted::PersistedKey;
+    ///
+    /// #[derive(PersistedKey)]
+    /// #[persisted(bool)]
+    /// struct ToggleKey;
+    ///
+    /// const NAME: Option<&str> = ToggleKey::TYPE_NAME;
+    /// assert_eq!(NAME, Some(ToggleKey::type_name()));
+    /// ```
+    const TYPE_NAME: Option<&'static str> = None;
+
+    /// Get a numeric identifier for this key type. This is useful for compact
+    /// stores, e.g. on embedded devices, where storing [Self::type_name] as a
+    /// string would be too expensive.
+    ///
+    /// The default implementation is [Self::type_name_hash]. This is stable as
+    /// long as the type name doesn't
+    /// change, but hash collisions between different key types are possible
+    /// (albeit unlikely), and renaming or moving the key type will change the
+    /// ID. For durable storage, you should assign explicit IDs instead. With
+    /// the derive macro, set the ID with `#[persisted(<type>, id = <u64>)]`:
+    ///
+    /// ```
+    /// use persisted::PersistedKey;
+    ///
+    /// #[derive(PersistedKey)]
+    /// #[persisted(bool, id = 42)]
+    /// struct ToggleKey;
+    ///
+    /// assert_eq!(ToggleKey::type_id(), 42);
+    /// ```
+    fn type_id() -> u64 {
+        Self::type_name_hash()
+    }
+
+    /// Get a 64-bit FNV-1a hash of [Self::type_name]. Type names of generic
+    /// types (e.g. [SingletonKey] wrapping a nested type) can get very long,
+    /// which is a problem for stores that limit key length. Those stores
+    /// should key on this hash instead of the name. It is fixed length and
+    /// stable across builds, as long as the type name doesn't change. Unlike
+    /// [Self::type_id], this can't be overridden with an explicit ID.
+    fn type_name_hash() -> u64 {
+        fnv1a(Self::type_name())
+    }
+
+    /// Validate a value that was loaded from the store, before it's used by
+    /// [Persisted] or [PersistedLazy]. This is useful when the set of valid
+    /// values changes over time, e.g. a range of valid values shrinks or an
+    /// enum variant is removed. A previously persisted value may no longer be
+    /// valid, so this gives you a chance to clamp or replace it.
+    ///
+    /// This is only called on values loaded from the store, *not* on default
+    /// values. The default implementation returns the value unmodified. With
+    /// the derive macro, you can provide a validation function with
+    /// `#[persisted(<type>, validate = <path>)]`:
+    ///
+    /// ```
+    /// use persisted::PersistedKey;
+    ///
+    /// #[derive(PersistedKey)]
+    /// #[persisted(u32, validate = clamp_volume)]
+    /// struct VolumeKey;
+    ///
+    /// fn clamp_volume(volume: u32) -> u32 {
+    ///     volume.min(100)
+    /// }
+    ///
+    /// assert_eq!(VolumeKey::validate(150), 100);
+    /// ```
+    fn validate(value: Self::Value) -> Self::Value {
+        value
+    }
+
+    /// Should values equal to the value type's [Default] be omitted from the
+    /// store? If `true`, whenever a wrapper would persist a value for which
+    /// [Self::is_default] returns `true`, it will call
+    /// [PersistedStore::remove_persisted] instead of
+    /// [PersistedStore::store_persisted]. This keeps the store limited to
+    /// non-default customizations. Defaults to `false`.
+    ///
+    /// Because a removed value will be loaded as missing, wrappers for these
+    /// keys should be initialized with the value type's default (e.g. via
+    /// [Persisted::new_default]). Otherwise a value that was reset to the
+    /// default would be restored as the wrapper's own default.
+    ///
+    /// With the derive macro, enable this with
+    /// `#[persisted(<type>, skip_if_default)]`. This requires the value type to
+    /// implement [Default] and [PartialEq].
+    fn skip_if_default() -> bool {
+        false
+    }
+
+    /// Is the given value equal to the value type's [Default]? This is only
+    /// used when [Self::skip_if_default] returns `true`. The default
+    /// implementation always returns `false`.
+    fn is_default(_value: &Self::Value) -> bool {
+        false
+    }
+
+    /// Select which store values of this key type should be routed to, when
+    /// used with [EitherStore]. Defaults to [StoreSelector::Left].
+    ///
+    /// With the derive macro, set this with
+    /// `#[persisted(<type>, store_selector = Right)]`.
+    fn store_selector() -> StoreSelector {
+        StoreSelector::Left
+    }
+
+    /// Name of the group this key type belongs to, if any. Related key types
+    /// (e.g. all window settings) can share a group, which stores can use to
+    /// prefix or bucket their entries. This makes it possible to operate on a
+    /// whole group at once, e.g. to reset all window settings. The wrappers
+    /// never call this; it's up to the store. Defaults to `None`.
+    ///
+    /// With the derive macro, set this with
+    /// `#[persisted(<type>, group = "<name>")]`.
+    fn group() -> Option<&'static str> {
+        None
+    }
+
+    /// How long values of this key type remain valid after being persisted,
+    /// when used with `TtlStore` (requires the `std`
+    /// feature). Expired values are treated as missing. Defaults to `None`,
+    /// meaning values never expire.
+    ///
+    /// With the derive macro, set this with
+    /// `#[persisted(<type>, ttl_secs = <seconds>)]`.
+    fn ttl() -> Option<Duration> {
+        None
+    }
+
+    /// Get a hash of this key *value*, used to detect live wrappers with equal
+    /// keys. Two such wrappers would silently overwrite each other's values in
+    /// the store. In debug builds with the `std` feature, the wrappers track
+    /// the keys of all live wrappers on the current thread, and panic if a
+    /// wrapper is created with a key equal to that of another live wrapper.
+    /// Detection is compiled out of release builds.
+    ///
+    /// Return `None` to opt out of detection, which is the default. Use
+    /// [hash_key] to implement this for any key that implements [Hash]. With
+    /// the derive macro, enable detection with
+    /// `#[persisted(<type>, detect_collisions)]`.
+    fn key_hash(&self) -> Option<u64> {
+        None
+    }
 }
 
 /// A persisted key for a value type that appears only once in a program. The
 /// **name of the value type** is the only information available as the key,
 /// hence why the value type must only be used once.
 ///
+/// With the `serde` feature, the key serializes as its
+/// [type name](PersistedKey::type_name), so singleton keys for different value
+/// types remain distinct once serialized. Deserialization fails if the name
+/// doesn't match.
+///
 /// ## Example
 ///
 /// ```
@@ -290,9 +970,7 @@ pub trait PersistedKey {
 /// }
 /// ```
 #[derive(Copy, Clone)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SingletonKey<V> {
-    #[cfg_attr(feature = "serde", serde(skip))]
     phantom: PhantomData<V>,
 }
 
@@ -324,10 +1002,575 @@ impl<V> Default for SingletonKey<V> {
     }
 }
 
+// All singleton keys of the same type are equal. Hand-written to omit bounds
+// on V
+impl<V> PartialEq for SingletonKey<V> {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<V> Eq for SingletonKey<V> {}
+
+impl<V> Hash for SingletonKey<V> {
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+#[cfg(feature = "serde")]
+impl<V> serde::Serialize for SingletonKey<V> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(Self::type_name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, V> serde::Deserialize<'de> for SingletonKey<V> {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        use serde::de::DeserializeSeed;
+        crate::entry::TypeNameSeed::<Self>(PhantomData)
+            .deserialize(deserializer)?;
+        Ok(Self::default())
+    }
+}
+
+/// A key that wraps another key, making its value optional. This allows the
+/// store to distinguish three states for a value:
+///
+/// - Absent: the store has no value for the key (`load_persisted` returns
+///   `None`)
+/// - Explicitly unset: the user cleared the value or chose the default, which
+///   is persisted as `Some(None)`
+/// - Explicitly set: persisted as `Some(Some(value))`
+///
+/// With a plain key, a persisted value that happens to equal the default is
+/// indistinguishable from one the user chose deliberately, especially once
+/// [PersistedKey::skip_if_default] is involved. Wrapping the key allows you to
+/// record that the user made a choice, even if that choice is "nothing".
+///
+/// The wrapper forwards [PersistedKey::validate] (for `Some` values) to the
+/// inner key. It also shares the inner key's identity: its
+/// [PersistedKey::type_name], [PersistedKey::type_id], group, store selector,
+/// and key hash are all forwarded, so values are stored under the inner key's
+/// (possibly pinned or transparent) name. With the `serde` feature, it
+/// serializes as the inner key. This means a value persisted under a plain key
+/// can be loaded through the wrapper, if the store's format can read a bare
+/// value as `Some`.
+///
+/// ```
+/// use persisted::{OptionKey, Persisted, PersistedKey, PersistedStore};
+/// # struct Store;
+/// # impl<K: PersistedKey> PersistedStore<K> for Store {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(u32)]
+/// struct VolumeKey;
+///
+/// let mut volume = Persisted::<Store, _>::new_default(OptionKey(VolumeKey));
+/// assert_eq!(volume.get(), None);
+/// // User explicitly reset the volume to the default
+/// *volume.get_mut() = None;
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct OptionKey<K>(pub K);
+
+impl<K: PersistedKey> PersistedKey for OptionKey<K> {
+    type Value = Option<K::Value>;
+
+    const TYPE_NAME: Option<&'static str> = K::TYPE_NAME;
+
+    fn type_name() -> &'static str {
+        K::type_name()
+    }
+
+    fn type_id() -> u64 {
+        K::type_id()
+    }
+
+    fn validate(value: Self::Value) -> Self::Value {
+        value.map(K::validate)
+    }
+
+    fn store_selector() -> StoreSelector {
+        K::store_selector()
+    }
+
+    fn group() -> Option<&'static str> {
+        K::group()
+    }
+
+    fn key_hash(&self) -> Option<u64> {
+        self.0.key_hash()
+    }
+}
+
+/// A name and value type for a [TypedKey]. Implement this on a zero-sized
+/// marker type. The name is used as the key's [PersistedKey::type_name], so it
+/// should be globally unique within your program, and stable across versions.
+pub trait KeyName {
+    /// The key's type name
+    const NAME: &'static str;
+
+    /// The type of the persisted value
+    type Value;
+}
+
+/// A key made of plain data, such as an integer or string, with its name and
+/// value type given by a [KeyName] marker `N`. This avoids defining a
+/// dedicated newtype for every ad hoc key, while keeping the key's name stable
+/// and distinct from other keys that hold the same type of data. Keys with the
+/// same marker are distinguished by their data. With the `serde` feature, the
+/// key serializes as its data.
+///
+/// ```
+/// use persisted::{KeyName, Persisted, PersistedKey, PersistedStore, TypedKey};
+/// # struct Store;
+/// # impl<K: PersistedKey> PersistedStore<K> for Store {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+///
+/// /// Scroll position of each tab, by tab ID
+/// struct TabScroll;
+///
+/// impl KeyName for TabScroll {
+///     const NAME: &'static str = "TabScroll";
+///     type Value = u32;
+/// }
+///
+/// let scroll =
+///     Persisted::<Store, _>::new(TypedKey::<TabScroll, u64>::new(3), 0);
+/// assert_eq!(scroll.key().data(), &3);
+/// assert_eq!(TypedKey::<TabScroll, u64>::type_name(), "TabScroll");
+/// ```
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct TypedKey<N, T> {
+    data: T,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    phantom: PhantomData<N>,
+}
+
+impl<N, T> TypedKey<N, T> {
+    /// Create a key with the given data
+    pub const fn new(data: T) -> Self {
+        Self {
+            data,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Get the key's data
+    pub fn data(&self) -> &T {
+        &self.data
+    }
+
+    /// Unwrap the key's data
+    pub fn into_data(self) -> T {
+        self.data
+    }
+}
+
+impl<N: KeyName, T> PersistedKey for TypedKey<N, T> {
+    type Value = N::Value;
+
+    const TYPE_NAME: Option<&'static str> = Some(N::NAME);
+}
+
+// The traits below are hand-written to omit bounds on N
+
+impl<N, T: Copy> Copy for TypedKey<N, T> {}
+
+impl<N, T: Clone> Clone for TypedKey<N, T> {
+    fn clone(&self) -> Self {
+        Self::new(self.data.clone())
+    }
+}
+
+impl<N, T: Debug> Debug for TypedKey<N, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TypedKey").field(&self.data).finish()
+    }
+}
+
+impl<N, T: Default> Default for TypedKey<N, T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<N, T: PartialEq> PartialEq for TypedKey<N, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+    }
+}
+
+impl<N, T: Eq> Eq for TypedKey<N, T> {}
+
+impl<N, T: Hash> Hash for TypedKey<N, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.data.hash(state);
+    }
+}
+
+/// A reference to a key is a key for the same value, so generic store code can
+/// accept either owned or borrowed keys. All key behavior, including the type
+/// name, is forwarded to the referenced key, so a value stored under `&K` is
+/// the same as one stored under `K`.
+///
+/// Note that stores that implement [PersistedStore] for specific key types
+/// won't automatically implement it for references to those types.
+impl<K: PersistedKey + ?Sized> PersistedKey for &K {
+    type Value = K::Value;
+
+    const TYPE_NAME: Option<&'static str> = K::TYPE_NAME;
+
+    fn type_name() -> &'static str {
+        K::type_name()
+    }
+
+    fn type_id() -> u64 {
+        K::type_id()
+    }
+
+    fn validate(value: Self::Value) -> Self::Value {
+        K::validate(value)
+    }
+
+    fn skip_if_default() -> bool {
+        K::skip_if_default()
+    }
+
+    fn is_default(value: &Self::Value) -> bool {
+        K::is_default(value)
+    }
+
+    fn store_selector() -> StoreSelector {
+        K::store_selector()
+    }
+
+    fn group() -> Option<&'static str> {
+        K::group()
+    }
+
+    fn ttl() -> Option<Duration> {
+        K::ttl()
+    }
+
+    fn key_hash(&self) -> Option<u64> {
+        (**self).key_hash()
+    }
+}
+
+/// Register a key type, so it's included in [validate_keys] (requires the
+/// `inventory` feature). The derive macro does this automatically for
+/// non-generic keys, so this is only needed for hand-written
+/// [PersistedKey] impls, or for specific instantiations of generic keys.
+/// Without the `inventory` feature, this does nothing.
+///
+/// ```
+/// use persisted::{register_key, PersistedKey};
+///
+/// struct ZoomKey;
+///
+/// impl PersistedKey for ZoomKey {
+///     type Value = f32;
+/// }
+///
+/// register_key!(ZoomKey);
+/// ```
+#[cfg(feature = "inventory")]
+#[macro_export]
+macro_rules! register_key {
+    ($key:ty) => {
+        $crate::__inventory::submit! {
+            $crate::RegisteredKey::new::<$key>()
+        }
+    };
+}
+
+/// Register a key type, so it's included in `validate_keys` (requires the
+/// `inventory` feature). Without the `inventory` feature, this does nothing.
+#[cfg(not(feature = "inventory"))]
+#[macro_export]
+macro_rules! register_key {
+    ($key:ty) => {};
+}
+
+/// Is persistence globally enabled? See [set_persistence_enabled].
+static PERSISTENCE_ENABLED: core::sync::atomic::AtomicBool =
+    core::sync::atomic::AtomicBool::new(true);
+
+/// Enable or disable all writes from the persisted wrappers. While disabled,
+/// mutations proceed normally in memory, but nothing is written to or removed
+/// from any store. Loads are unaffected. This
+/// is useful for modes where changes shouldn't be saved, such as onboarding or
+/// an "incognito" mode. Persistence is enabled by default.
+///
+/// The flag is process-global: it applies to every store, key, and thread.
+/// It's an atomic, so it's safe to toggle from any thread, but a write that's
+/// already in progress on another thread won't be interrupted. Writes skipped
+/// while disabled are **not** replayed when persistence is re-enabled, but
+/// [PersistedLazy] still considers the value changed, so it's written by the
+/// next mutable access after re-enabling. Calling a store directly bypasses
+/// the flag.
+///
+/// ```
+/// use persisted::{Persisted, PersistedKey, PersistedStore};
+/// use std::cell::Cell;
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(u32)]
+/// struct CountKey;
+///
+/// struct Store;
+///
+/// thread_local! {
+///     static VALUE: Cell<Option<u32>> = Cell::default();
+/// }
+///
+/// impl PersistedStore<CountKey> for Store {
+///     fn load_persisted(_: &CountKey) -> Option<u32> {
+///         VALUE.get()
+///     }
+///
+///     fn store_persisted(_: &CountKey, value: &u32) {
+///         VALUE.set(Some(*value));
+///     }
+/// }
+///
+/// let mut count = Persisted::<Store, _>::new(CountKey, 0);
+/// persisted::set_persistence_enabled(false);
+/// *count.get_mut() += 1;
+/// assert_eq!(count.get(), 1);
+/// assert_eq!(VALUE.get(), None);
+///
+/// persisted::set_persistence_enabled(true);
+/// *count.get_mut() += 1;
+/// assert_eq!(VALUE.get(), Some(2));
+/// ```
+pub fn set_persistence_enabled(enabled: bool) {
+    PERSISTENCE_ENABLED.store(enabled, core::sync::atomic::Ordering::Relaxed);
+}
+
+/// Is persistence globally enabled? See [set_persistence_enabled].
+pub fn is_persistence_enabled() -> bool {
+    PERSISTENCE_ENABLED.load(core::sync::atomic::Ordering::Relaxed)
+}
+
+/// Hash a key with 64-bit FNV-1a. This is stable across program runs and
+/// platforms, unlike the hasher in `std`. See [PersistedKey::key_hash].
+pub fn hash_key<K: Hash + ?Sized>(key: &K) -> u64 {
+    let mut hasher = FnvHasher(FNV_OFFSET_BASIS);
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Incremental 64-bit FNV-1a hasher
+struct FnvHasher(u64);
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+/// 64-bit FNV-1a hash of a string
+const fn fnv1a(value: &str) -> u64 {
+    let bytes = value.as_bytes();
+    let mut hash = FNV_OFFSET_BASIS;
+    let mut i = 0;
+    // Iterators aren't available in const fns
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+        i += 1;
+    }
+    hash
+}
+
+/// Load a value from the store and validate it. All wrappers should load
+/// through here, rather than calling the store directly, so that key-level
+/// behavior is applied consistently.
+fn load<S, K>(key: &K) -> Option<K::Value>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+{
+    #[cfg(feature = "metrics")]
+    metrics::record_load::<K>();
+    S::load_persisted(key).map(K::validate)
+}
+
+/// Persist a value to the store. All wrappers should persist through here,
+/// rather than calling the store directly, so that key-level behavior is
+/// applied consistently.
+///
+/// `old` is the previously persisted value, if known by the wrapper. Return
+/// whether the store was written, i.e. `false` while persistence is disabled.
+/// Wrappers should only update their change tracking if this returns `true`.
+fn persist<S, K>(key: &K, old: Option<&K::Value>, value: &K::Value) -> bool
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+{
+    if !is_persistence_enabled() {
+        return false;
+    }
+    #[cfg(feature = "metrics")]
+    metrics::record_store::<K>();
+    if K::skip_if_default() && K::is_default(value) {
+        S::remove_persisted(key);
+    } else {
+        S::store_persisted_diff(key, old, value);
+    }
+    true
+}
+
+/// Write a value to the store if the stored value matches `expected`. All
+/// wrappers should compare-and-swap through here, so that writes are skipped
+/// while persistence is disabled. Return whether the value was written.
+fn compare_and_swap<S, K>(
+    key: &K,
+    expected: Option<&K::Value>,
+    new: &K::Value,
+) -> bool
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+    K::Value: PartialEq,
+{
+    if !is_persistence_enabled() {
+        return false;
+    }
+    #[cfg(feature = "metrics")]
+    metrics::record_store::<K>();
+    // Default values are never stored for these keys, so expecting the
+    // default is the same as expecting nothing
+    let expected = expected
+        .filter(|value| !(K::skip_if_default() && K::is_default(value)));
+    S::compare_and_swap(key, expected, new)
+}
+
+/// Remove a value from the store. All wrappers should remove through here,
+/// rather than calling the store directly, so that removals are skipped while
+/// persistence is disabled.
+fn remove<S, K>(key: &K)
+where
+    S: PersistedStore<K>,
+    K: PersistedKey,
+{
+    if !is_persistence_enabled() {
+        return;
+    }
+    S::remove_persisted(key);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a("a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a("foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn test_option_key() {
+        struct VolumeKey;
+
+        impl PersistedKey for VolumeKey {
+            type Value = u32;
+
+            fn validate(value: u32) -> u32 {
+                value.min(100)
+            }
+        }
+
+        assert_eq!(OptionKey::<VolumeKey>::validate(Some(150)), Some(100));
+        assert_eq!(OptionKey::<VolumeKey>::validate(None), None);
+        assert_eq!(OptionKey::<VolumeKey>::type_name(), VolumeKey::type_name());
+        assert_eq!(OptionKey::<VolumeKey>::type_id(), VolumeKey::type_id());
+    }
+
+    #[test]
+    fn test_typed_key() {
+        struct First;
+
+        impl KeyName for First {
+            const NAME: &'static str = "First";
+            type Value = u32;
+        }
+
+        struct Second;
+
+        impl KeyName for Second {
+            const NAME: &'static str = "Second";
+            type Value = bool;
+        }
+
+        assert_eq!(TypedKey::<First, u64>::type_name(), "First");
+        assert_eq!(TypedKey::<Second, u64>::type_name(), "Second");
+        assert_ne!(
+            TypedKey::<First, u64>::type_id(),
+            TypedKey::<Second, u64>::type_id()
+        );
+        // Data distinguishes keys with the same name
+        let key = TypedKey::<First, _>::new(3u64);
+        assert_eq!(key, TypedKey::new(3));
+        assert_ne!(key, TypedKey::new(4));
+        assert_eq!(key.into_data(), 3);
+    }
+
+    #[test]
+    fn test_ref_key() {
+        struct VolumeKey;
+
+        impl PersistedKey for VolumeKey {
+            type Value = u32;
+
+            fn validate(value: u32) -> u32 {
+                value.min(100)
+            }
+        }
+
+        /// Generic code can be handed either owned or borrowed keys
+        fn name_of<K: PersistedKey>(_: K) -> &'static str {
+            K::type_name()
+        }
+
+        assert_eq!(name_of(&VolumeKey), name_of(VolumeKey));
+        assert_eq!(<&VolumeKey>::type_id(), VolumeKey::type_id());
+        assert_eq!(<&VolumeKey>::validate(150), 100);
+    }
+
     #[test]
     fn test_singleton_key() {
         struct Foo;
@@ -340,6 +1583,35 @@ mod tests {
         assert_eq!(
             SingletonKey::<Option<Foo>>::type_name(),
             "core::option::Option<persisted::tests::test_singleton_key::Foo>"
-        )
+        );
+
+        // Hashes are stable and distinct
+        assert_eq!(
+            SingletonKey::<Foo>::type_name_hash(),
+            fnv1a("persisted::tests::test_singleton_key::Foo")
+        );
+        assert_ne!(
+            SingletonKey::<Foo>::type_name_hash(),
+            SingletonKey::<Option<Foo>>::type_name_hash()
+        );
+
+        // All instances are equal, even though Foo isn't comparable
+        assert_eq!(SingletonKey::<Foo>::default(), SingletonKey::default());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_singleton_key_serde() {
+        struct A;
+        struct B;
+
+        let a = serde_json::to_string(&SingletonKey::<A>::default()).unwrap();
+        let b = serde_json::to_string(&SingletonKey::<B>::default()).unwrap();
+        assert_eq!(a, r#""persisted::tests::test_singleton_key_serde::A""#);
+        assert_ne!(a, b);
+
+        assert!(serde_json::from_str::<SingletonKey<A>>(&a).is_ok());
+        // Keys for a different type are rejected
+        assert!(serde_json::from_str::<SingletonKey<A>>(&b).is_err());
     }
 }
@@ -0,0 +1,191 @@
+//! Counters for store access, to help detect redundant loads and writes. Only
+//! accesses made by the wrappers ([Persisted](crate::Persisted) and
+//! [PersistedLazy](crate::PersistedLazy)) are counted; calling a store
+//! directly is not. Counters are global and shared across all stores and
+//! threads. Counters saturate rather than overflow.
+//!
+//! With the `std` feature, counts are also broken down by key type (see
+//! [load_count_for] and [store_count_for]). Because no other key type can
+//! affect them, per-type counts are useful for asserting write behavior in
+//! tests, without having to track writes in the store. When the `metrics`
+//! feature is disabled, nothing is counted.
+//!
+//! ```
+//! use persisted::{metrics, Persisted, PersistedKey, PersistedStore};
+//! # struct Store;
+//! # impl<K: PersistedKey> PersistedStore<K> for Store {
+//! #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+//! #     fn store_persisted(_: &K, _: &K::Value) {}
+//! # }
+//!
+//! #[derive(PersistedKey)]
+//! #[persisted(u32)]
+//! struct CountKey;
+//!
+//! metrics::reset();
+//! let mut count = Persisted::<Store, _>::new(CountKey, 0);
+//! *count.get_mut() += 1;
+//! assert_eq!(metrics::load_count(), 1);
+//! assert_eq!(metrics::store_count(), 1);
+//! # #[cfg(feature = "std")]
+//! assert_eq!(metrics::store_count_for::<CountKey>(), 1);
+//! ```
+
+use crate::PersistedKey;
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use {
+    crate::global::lock,
+    std::{collections::BTreeMap, sync::Mutex},
+};
+
+static LOAD_COUNT: AtomicUsize = AtomicUsize::new(0);
+static STORE_COUNT: AtomicUsize = AtomicUsize::new(0);
+/// Counts for each key type, keyed by [PersistedKey::type_name]
+#[cfg(feature = "std")]
+static KEY_COUNTS: Mutex<BTreeMap<&'static str, KeyCounts>> =
+    Mutex::new(BTreeMap::new());
+
+/// Store access counts for a single key type. See [counts_by_type].
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyCounts {
+    /// Number of times a value has been loaded from a store
+    pub loads: usize,
+    /// Number of times a value has been written to a store
+    pub stores: usize,
+}
+
+/// Number of times a value has been loaded from a store
+pub fn load_count() -> usize {
+    LOAD_COUNT.load(Ordering::Relaxed)
+}
+
+/// Number of times a value has been written to a store. This includes
+/// removals of default values for keys with
+/// [skip_if_default](crate::PersistedKey::skip_if_default).
+pub fn store_count() -> usize {
+    STORE_COUNT.load(Ordering::Relaxed)
+}
+
+/// Number of times a value of key type `K` has been loaded from a store
+#[cfg(feature = "std")]
+pub fn load_count_for<K: PersistedKey>() -> usize {
+    key_counts::<K>().loads
+}
+
+/// Number of times a value of key type `K` has been written to a store. See
+/// [store_count].
+#[cfg(feature = "std")]
+pub fn store_count_for<K: PersistedKey>() -> usize {
+    key_counts::<K>().stores
+}
+
+/// Get counts for every key type that has been accessed, keyed by
+/// [PersistedKey::type_name]
+#[cfg(feature = "std")]
+pub fn counts_by_type() -> BTreeMap<&'static str, KeyCounts> {
+    lock(&KEY_COUNTS).clone()
+}
+
+/// Reset all counters to zero
+pub fn reset() {
+    LOAD_COUNT.store(0, Ordering::Relaxed);
+    STORE_COUNT.store(0, Ordering::Relaxed);
+    #[cfg(feature = "std")]
+    lock(&KEY_COUNTS).clear();
+}
+
+// K is only used with std
+#[cfg_attr(not(feature = "std"), allow(clippy::extra_unused_type_parameters))]
+pub(crate) fn record_load<K: PersistedKey>() {
+    increment(&LOAD_COUNT);
+    #[cfg(feature = "std")]
+    update_key_counts::<K>(|counts| &mut counts.loads);
+}
+
+// K is only used with std
+#[cfg_attr(not(feature = "std"), allow(clippy::extra_unused_type_parameters))]
+pub(crate) fn record_store<K: PersistedKey>() {
+    increment(&STORE_COUNT);
+    #[cfg(feature = "std")]
+    update_key_counts::<K>(|counts| &mut counts.stores);
+}
+
+/// Add one to a counter, saturating at the maximum
+fn increment(counter: &AtomicUsize) {
+    let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+        n.checked_add(1)
+    });
+}
+
+#[cfg(feature = "std")]
+fn key_counts<K: PersistedKey>() -> KeyCounts {
+    lock(&KEY_COUNTS)
+        .get(K::type_name())
+        .copied()
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "std")]
+fn update_key_counts<K: PersistedKey>(
+    field: impl FnOnce(&mut KeyCounts) -> &mut usize,
+) {
+    let mut key_counts = lock(&KEY_COUNTS);
+    let count = field(key_counts.entry(K::type_name()).or_default());
+    *count = count.saturating_add(1);
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{Persisted, PersistedStore};
+
+    struct CountKey;
+
+    impl PersistedKey for CountKey {
+        type Value = u32;
+    }
+
+    struct OtherKey;
+
+    impl PersistedKey for OtherKey {
+        type Value = u32;
+    }
+
+    struct Store;
+
+    impl<K: PersistedKey> PersistedStore<K> for Store {
+        fn load_persisted(_: &K) -> Option<K::Value> {
+            None
+        }
+
+        fn store_persisted(_: &K, _: &K::Value) {}
+    }
+
+    #[test]
+    fn test_counts_by_type() {
+        let mut count = Persisted::<Store, _>::new(CountKey, 0);
+        *count.get_mut() += 1;
+        *count.get_mut() += 1;
+        let mut other = Persisted::<Store, _>::new(OtherKey, 0);
+        *other.get_mut() += 1;
+
+        assert_eq!(load_count_for::<CountKey>(), 1);
+        assert_eq!(store_count_for::<CountKey>(), 2);
+        assert_eq!(
+            counts_by_type().get(OtherKey::type_name()),
+            Some(&KeyCounts {
+                loads: 1,
+                stores: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_increment_saturates() {
+        let counter = AtomicUsize::new(usize::MAX);
+        increment(&counter);
+        assert_eq!(counter.load(Ordering::Relaxed), usize::MAX);
+    }
+}
@@ -0,0 +1,131 @@
+//! Registration of key types, for validating that their identifiers are
+//! unique. See [validate_keys].
+
+use crate::PersistedKey;
+use std::{collections::BTreeMap, vec::Vec};
+
+#[doc(hidden)]
+pub use inventory as __inventory;
+
+/// A key type that has been registered via [register_key](crate::register_key)
+#[derive(Debug)]
+pub struct RegisteredKey {
+    type_name: fn() -> &'static str,
+    type_id: fn() -> u64,
+}
+
+impl RegisteredKey {
+    #[doc(hidden)]
+    pub const fn new<K: PersistedKey>() -> Self {
+        Self {
+            type_name: K::type_name,
+            type_id: K::type_id,
+        }
+    }
+
+    /// Get the key type's [PersistedKey::type_name]
+    pub fn type_name(&self) -> &'static str {
+        (self.type_name)()
+    }
+
+    /// Get the key type's [PersistedKey::type_id]
+    pub fn type_id(&self) -> u64 {
+        (self.type_id)()
+    }
+}
+
+inventory::collect!(RegisteredKey);
+
+/// Identifiers shared by multiple registered key types. See [validate_keys].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyCollision {
+    /// Multiple key types have this [PersistedKey::type_name]
+    TypeName(&'static str),
+    /// Multiple key types have this [PersistedKey::type_id]. Includes the
+    /// type names of all of them.
+    TypeId(u64, Vec<&'static str>),
+}
+
+/// Get all registered key types, in no particular order
+pub fn registered_keys() -> impl Iterator<Item = &'static RegisteredKey> {
+    inventory::iter::<RegisteredKey>.into_iter()
+}
+
+/// Check that no two registered key types share a [PersistedKey::type_name]
+/// or [PersistedKey::type_id]. Return all collisions found, or an empty list
+/// if every key type is unique. Key types are registered by the derive macro,
+/// or manually with [register_key](crate::register_key).
+///
+/// Call this at startup, e.g. with `assert!`, to catch collisions before
+/// they can corrupt stored data.
+///
+/// ```
+/// use persisted::PersistedKey;
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(bool)]
+/// struct DarkModeKey;
+///
+/// assert_eq!(persisted::validate_keys(), []);
+/// ```
+pub fn validate_keys() -> Vec<KeyCollision> {
+    let mut names: BTreeMap<&'static str, usize> = BTreeMap::new();
+    let mut ids: BTreeMap<u64, Vec<&'static str>> = BTreeMap::new();
+    for key in registered_keys() {
+        *names.entry(key.type_name()).or_default() += 1;
+        ids.entry(key.type_id()).or_default().push(key.type_name());
+    }
+
+    let name_collisions = names
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(name, _)| KeyCollision::TypeName(name));
+    let id_collisions = ids
+        .into_iter()
+        .filter(|(_, names)| names.len() > 1)
+        .map(|(id, mut names)| {
+            names.sort_unstable();
+            KeyCollision::TypeId(id, names)
+        });
+    name_collisions.chain(id_collisions).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::register_key;
+
+    struct FirstKey;
+
+    impl PersistedKey for FirstKey {
+        type Value = bool;
+
+        fn type_id() -> u64 {
+            7
+        }
+    }
+
+    struct SecondKey;
+
+    impl PersistedKey for SecondKey {
+        type Value = bool;
+
+        fn type_id() -> u64 {
+            7
+        }
+    }
+
+    register_key!(FirstKey);
+    register_key!(SecondKey);
+
+    #[test]
+    fn test_validate_keys() {
+        assert_eq!(
+            validate_keys(),
+            [KeyCollision::TypeId(
+                7,
+                std::vec![FirstKey::type_name(), SecondKey::type_name()]
+            )]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use core::fmt::{self, Debug, Display};
+
+/// A wrapper for sensitive persisted values, such as tokens or passwords. The
+/// inner value is hidden from [Debug] and [Display] output, so it can't leak
+/// into logs by accident. Access the inner value explicitly with
+/// [Secret::expose].
+///
+/// With the `serde` feature, this serializes transparently as the inner value.
+/// `persisted` doesn't encrypt anything itself. Instead, use `Secret` as the
+/// value type of your key, so your store can tell which values need special
+/// handling and encrypt/decrypt them on the way in and out:
+///
+/// ```
+/// use persisted::{Persisted, PersistedKey, PersistedStore, Secret};
+/// use std::cell::RefCell;
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(Secret<String>)]
+/// struct TokenKey;
+///
+/// /// A store for secret values only
+/// struct SecureStore;
+///
+/// impl SecureStore {
+///     thread_local! {
+///         static ENCRYPTED: RefCell<Option<String>> = RefCell::default();
+///     }
+///
+///     // Pretend this is real encryption
+///     fn encrypt(value: &str) -> String {
+///         value.chars().rev().collect()
+///     }
+///
+///     fn decrypt(value: &str) -> String {
+///         value.chars().rev().collect()
+///     }
+/// }
+///
+/// impl<K: PersistedKey<Value = Secret<String>>> PersistedStore<K>
+///     for SecureStore
+/// {
+///     fn load_persisted(_key: &K) -> Option<Secret<String>> {
+///         Self::ENCRYPTED.with(|cell| {
+///             cell.borrow().as_deref().map(|value| Self::decrypt(value).into())
+///         })
+///     }
+///
+///     fn store_persisted(_key: &K, value: &Secret<String>) {
+///         let encrypted = Self::encrypt(value.expose());
+///         Self::ENCRYPTED.with(|cell| *cell.borrow_mut() = Some(encrypted));
+///     }
+/// }
+///
+/// let mut token = Persisted::<SecureStore, _>::new_default(TokenKey);
+/// *token.get_mut() = Secret::new("hunter2".to_owned());
+/// assert_eq!(format!("{:?}", token.value()), "Secret(***)");
+/// SecureStore::ENCRYPTED.with(|cell| {
+///     assert_eq!(cell.borrow().as_deref(), Some("2retnuh"))
+/// });
+/// ```
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Secret<V>(V);
+
+impl<V> Secret<V> {
+    /// Wrap a secret value
+    pub fn new(value: V) -> Self {
+        Self(value)
+    }
+
+    /// Get a reference to the secret value
+    pub fn expose(&self) -> &V {
+        &self.0
+    }
+
+    /// Get a mutable reference to the secret value
+    pub fn expose_mut(&mut self) -> &mut V {
+        &mut self.0
+    }
+
+    /// Unwrap the secret value
+    pub fn into_inner(self) -> V {
+        self.0
+    }
+}
+
+impl<V> From<V> for Secret<V> {
+    fn from(value: V) -> Self {
+        Self(value)
+    }
+}
+
+impl<V> Debug for Secret<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret(***)")
+    }
+}
+
+impl<V> Display for Secret<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "***")
+    }
+}
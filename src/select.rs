@@ -0,0 +1,186 @@
+use crate::PersistedContainer;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+/// A list of items with one item selected, which persists the *ID* of the
+/// selected item rather than its index. Restoring searches the list for an
+/// item with the persisted ID, so the selection survives changes to the order
+/// or contents of the list. If no item matches, the first item is selected.
+///
+/// IDs are obtained from items with the `id_of` projection given at
+/// construction. The persisted value is `None` only if the list is empty.
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{IdSelect, PersistedKey, PersistedLazy, PersistedStore};
+/// # struct Store;
+/// # impl<K: PersistedKey> PersistedStore<K> for Store {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+///
+/// struct Person {
+///     id: u64,
+///     name: &'static str,
+/// }
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(Option<u64>)]
+/// struct SelectedPersonKey;
+///
+/// let people = vec![
+///     Person { id: 23089, name: "Fred" },
+///     Person { id: 28833, name: "Susan" },
+/// ];
+/// let mut people = PersistedLazy::<Store, _, _>::new(
+///     SelectedPersonKey,
+///     IdSelect::new(people, |person| person.id),
+/// );
+/// people.get_mut().select_id(&28833);
+/// assert_eq!(people.selected().unwrap().name, "Susan");
+/// ```
+#[derive(Clone, derive_more::Debug)]
+#[debug(bound(T: Debug))]
+pub struct IdSelect<T, I> {
+    values: Vec<T>,
+    selected_index: usize,
+    #[debug(skip)]
+    id_of: fn(&T) -> I,
+}
+
+impl<T, I> IdSelect<T, I> {
+    /// Create a new list, with the first item selected
+    pub fn new(values: Vec<T>, id_of: fn(&T) -> I) -> Self {
+        Self {
+            values,
+            selected_index: 0,
+            id_of,
+        }
+    }
+
+    /// Get all items in the list
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Get the index of the selected item. This is always `0` for an empty
+    /// list.
+    pub fn selected_index(&self) -> usize {
+        self.selected_index
+    }
+
+    /// Get the selected item, or `None` if the list is empty
+    pub fn selected(&self) -> Option<&T> {
+        self.values.get(self.selected_index)
+    }
+
+    /// Get the ID of the selected item, or `None` if the list is empty
+    pub fn selected_id(&self) -> Option<I> {
+        self.selected().map(self.id_of)
+    }
+
+    /// Select the item at the given index. Return `false` and leave the
+    /// selection unchanged if the index is out of bounds.
+    pub fn select(&mut self, index: usize) -> bool {
+        if index < self.values.len() {
+            self.selected_index = index;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Select the first item with the given ID. Return `false` and leave the
+    /// selection unchanged if no item has the ID.
+    pub fn select_id(&mut self, id: &I) -> bool
+    where
+        I: PartialEq,
+    {
+        match self.index_of(id) {
+            Some(index) => {
+                self.selected_index = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replace the items in the list, retaining the selection by ID. If the
+    /// selected item is no longer present, the first item is selected.
+    pub fn set_values(&mut self, values: Vec<T>)
+    where
+        I: PartialEq,
+    {
+        let selected_id = self.selected_id();
+        self.values = values;
+        self.selected_index = selected_id
+            .and_then(|id| self.index_of(&id))
+            .unwrap_or_default();
+    }
+
+    /// Unwrap the list of items
+    pub fn into_values(self) -> Vec<T> {
+        self.values
+    }
+
+    fn index_of(&self, id: &I) -> Option<usize>
+    where
+        I: PartialEq,
+    {
+        self.values
+            .iter()
+            .position(|value| (self.id_of)(value) == *id)
+    }
+}
+
+impl<T, I: PartialEq> PersistedContainer for IdSelect<T, I> {
+    type Value = Option<I>;
+
+    fn get_to_persist(&self) -> Self::Value {
+        self.selected_id()
+    }
+
+    fn restore_persisted(&mut self, value: Self::Value) {
+        self.selected_index =
+            value.and_then(|id| self.index_of(&id)).unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn list() -> IdSelect<(u32, &'static str), u32> {
+        IdSelect::new(vec![(1, "one"), (2, "two"), (3, "three")], |item| item.0)
+    }
+
+    #[test]
+    fn test_id_select() {
+        let mut list = list();
+        assert_eq!(list.get_to_persist(), Some(1));
+        assert!(list.select(2));
+        assert!(!list.select(3));
+        assert_eq!(list.get_to_persist(), Some(3));
+
+        // Selection is retained by ID when the list changes
+        list.set_values(vec![(3, "three"), (4, "four")]);
+        assert_eq!(list.selected_index(), 0);
+        assert_eq!(list.get_to_persist(), Some(3));
+        list.set_values(vec![(4, "four")]);
+        assert_eq!(list.get_to_persist(), Some(4));
+
+        let mut list = self::list();
+        list.restore_persisted(Some(2));
+        assert_eq!(list.selected(), Some(&(2, "two")));
+        // Unknown IDs fall back to the first item
+        list.restore_persisted(Some(7));
+        assert_eq!(list.selected_index(), 0);
+
+        let mut empty = IdSelect::new(vec![], |item: &(u32, ())| item.0);
+        assert_eq!(empty.get_to_persist(), None);
+        empty.restore_persisted(Some(1));
+        assert_eq!(empty.selected(), None);
+    }
+}
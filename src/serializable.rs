@@ -0,0 +1,109 @@
+use crate::{PersistedKey, PersistedStore};
+use alloc::string::String;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// A store that persists keys and values as strings. This separates the
+/// storage mechanism (e.g. a file or key-value database) from the
+/// serialization format. Implementing this trait implements [PersistedStore]
+/// for **all** key types whose key and value are serializable, so you don't
+/// need to write out the bounds yourself.
+///
+/// Values are identified by [PersistedKey::type_name] plus the serialized
+/// key. If a stored value fails to deserialize (e.g. because the value type
+/// changed), it will be treated as missing.
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{Persisted, PersistedKey, SerializablePersistedStore};
+/// use serde::{de::DeserializeOwned, Serialize};
+/// use std::{cell::RefCell, collections::HashMap};
+///
+/// struct Store;
+///
+/// impl Store {
+///     thread_local! {
+///         static INSTANCE: RefCell<HashMap<(String, String), String>> =
+///             Default::default();
+///     }
+/// }
+///
+/// impl SerializablePersistedStore for Store {
+///     fn serialize_value<T: Serialize>(value: &T) -> String {
+///         serde_json::to_string(value).expect("Error serializing value")
+///     }
+///
+///     fn deserialize_value<T: DeserializeOwned>(value: &str) -> Option<T> {
+///         serde_json::from_str(value).ok()
+///     }
+///
+///     fn load_serialized(type_name: &str, key: &str) -> Option<String> {
+///         Self::INSTANCE.with(|map| {
+///             map.borrow()
+///                 .get(&(type_name.to_owned(), key.to_owned()))
+///                 .cloned()
+///         })
+///     }
+///
+///     fn store_serialized(type_name: &str, key: &str, value: String) {
+///         Self::INSTANCE.with(|map| {
+///             map.borrow_mut()
+///                 .insert((type_name.to_owned(), key.to_owned()), value)
+///         });
+///     }
+/// }
+///
+/// #[derive(PersistedKey, Serialize)]
+/// #[persisted(bool)]
+/// struct ToggleKey(u32);
+///
+/// let mut toggle = Persisted::<Store, _>::new(ToggleKey(3), false);
+/// *toggle.get_mut() = true;
+/// let toggle = Persisted::<Store, _>::new(ToggleKey(3), false);
+/// assert!(toggle.get());
+/// ```
+pub trait SerializablePersistedStore {
+    /// Serialize a key or value to a string
+    fn serialize_value<T: Serialize>(value: &T) -> String;
+
+    /// Deserialize a value from a string. Return `None` if deserialization
+    /// fails, in which case the value is treated as missing.
+    fn deserialize_value<T: DeserializeOwned>(value: &str) -> Option<T>;
+
+    /// Load a serialized value, identified by the key's type name and the
+    /// serialized key. Return `None` if the value isn't present.
+    fn load_serialized(type_name: &str, key: &str) -> Option<String>;
+
+    /// Persist a serialized value, identified by the key's type name and the
+    /// serialized key
+    fn store_serialized(type_name: &str, key: &str, value: String);
+
+    /// Remove a serialized value. See [PersistedStore::remove_persisted]. The
+    /// default implementation does nothing.
+    fn remove_serialized(_type_name: &str, _key: &str) {}
+}
+
+impl<S, K> PersistedStore<K> for S
+where
+    S: SerializablePersistedStore,
+    K: PersistedKey + Serialize,
+    K::Value: Serialize + DeserializeOwned,
+{
+    fn load_persisted(key: &K) -> Option<K::Value> {
+        let serialized =
+            S::load_serialized(K::type_name(), &S::serialize_value(key))?;
+        S::deserialize_value(&serialized)
+    }
+
+    fn store_persisted(key: &K, value: &K::Value) {
+        S::store_serialized(
+            K::type_name(),
+            &S::serialize_value(key),
+            S::serialize_value(value),
+        );
+    }
+
+    fn remove_persisted(key: &K) {
+        S::remove_serialized(K::type_name(), &S::serialize_value(key));
+    }
+}
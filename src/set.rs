@@ -0,0 +1,280 @@
+use crate::{Persisted, PersistedKey, PersistedStore};
+use core::{
+    fmt::{self, Debug},
+    hash::Hash,
+    ops::Deref,
+};
+use std::collections::{hash_set, HashSet};
+
+/// A persisted set of values. The entire set is persisted as a single value,
+/// under a single key. Unlike [Persisted::get_mut], mutations are made through
+/// [Self::insert] and [Self::remove], which only persist if the set's
+/// membership actually changed. Immutable access to the set is available via
+/// [Deref].
+///
+/// The set can optionally be bounded with [Self::new_bounded]. Once the set
+/// reaches its maximum length, further insertions are rejected.
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{PersistedKey, PersistedSet, PersistedStore};
+/// use std::collections::HashSet;
+/// # struct Store;
+/// # impl<K: PersistedKey> PersistedStore<K> for Store {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+///
+/// /// IDs of favorited items
+/// #[derive(PersistedKey)]
+/// #[persisted(HashSet<u32>)]
+/// struct FavoritesKey;
+///
+/// let mut favorites = PersistedSet::<Store, _, _>::new(FavoritesKey);
+/// assert!(favorites.insert(3));
+/// // Already present, so nothing is persisted
+/// assert!(!favorites.insert(3));
+/// assert!(favorites.contains(&3));
+/// assert_eq!(favorites.iter().count(), 1);
+/// ```
+pub struct PersistedSet<S, K, T>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey<Value = HashSet<T>>,
+{
+    persisted: Persisted<S, K>,
+    max_len: Option<usize>,
+}
+
+impl<S, K, T> PersistedSet<S, K, T>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey<Value = HashSet<T>>,
+    T: Eq + Hash,
+{
+    /// Initialize a new persisted set. The latest persisted set will be
+    /// loaded from the store. If missing, the set will be empty.
+    pub fn new(key: K) -> Self {
+        Self {
+            persisted: Persisted::new_default(key),
+            max_len: None,
+        }
+    }
+
+    /// Initialize a new persisted set that can hold at most `max_len` values.
+    /// If the persisted set is already larger than this, it will be loaded
+    /// as-is, but no more values can be inserted until it shrinks below the
+    /// limit.
+    pub fn new_bounded(key: K, max_len: usize) -> Self {
+        Self {
+            persisted: Persisted::new_default(key),
+            max_len: Some(max_len),
+        }
+    }
+
+    /// Add a value to the set. If the value is new and the set isn't full,
+    /// persist the set and return `true`. Otherwise, return `false` without
+    /// persisting.
+    pub fn insert(&mut self, value: T) -> bool {
+        let set = self.persisted.value();
+        let is_full = self.max_len.is_some_and(|max_len| set.len() >= max_len);
+        if set.contains(&value) || is_full {
+            false
+        } else {
+            self.persisted.get_mut().insert(value)
+        }
+    }
+
+    /// Remove a value from the set. If the value was present, persist the set
+    /// and return `true`. Otherwise, return `false` without persisting.
+    pub fn remove(&mut self, value: &T) -> bool {
+        if self.persisted.value().contains(value) {
+            self.persisted.get_mut().remove(value)
+        } else {
+            false
+        }
+    }
+
+    /// Remove all values from the set. The set is only persisted if it wasn't
+    /// already empty.
+    pub fn clear(&mut self) {
+        if !self.persisted.value().is_empty() {
+            self.persisted.get_mut().clear();
+        }
+    }
+
+    /// Get the maximum number of values the set can hold, if bounded
+    pub fn max_len(&self) -> Option<usize> {
+        self.max_len
+    }
+}
+
+/// Insert all values from an iterator, persisting **once** at the end, and
+/// only if any new values were inserted. If the set is bounded, values beyond
+/// the maximum length are ignored.
+impl<S, K, T> Extend<T> for PersistedSet<S, K, T>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey<Value = HashSet<T>>,
+    T: Eq + Hash,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let set = self.persisted.value();
+        let room = self
+            .max_len
+            .map_or(usize::MAX, |max_len| max_len.saturating_sub(set.len()));
+        // Collect new values first, so we only persist if there are any
+        let mut added = HashSet::new();
+        for value in iter {
+            if added.len() >= room {
+                break;
+            }
+            if !set.contains(&value) {
+                added.insert(value);
+            }
+        }
+        if !added.is_empty() {
+            self.persisted.get_mut().extend(added);
+        }
+    }
+}
+
+/// Create an unbounded set from an iterator, using the key type's [Default].
+/// The set **replaces** whatever was previously persisted for the key, and is
+/// persisted once (if it differs from the stored set).
+impl<S, K, T> FromIterator<T> for PersistedSet<S, K, T>
+where
+    S: PersistedStore<K>,
+    K: Default + PersistedKey<Value = HashSet<T>>,
+    T: Eq + Hash,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let values: HashSet<T> = iter.into_iter().collect();
+        let mut set = Self::new(K::default());
+        if *set.persisted.value() != values {
+            *set.persisted.get_mut() = values;
+        }
+        set
+    }
+}
+
+impl<S, K, T> Debug for PersistedSet<S, K, T>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey<Value = HashSet<T>>,
+    T: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.persisted.value()).finish()
+    }
+}
+
+impl<S, K, T> Deref for PersistedSet<S, K, T>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey<Value = HashSet<T>>,
+{
+    type Target = HashSet<T>;
+
+    fn deref(&self) -> &Self::Target {
+        self.persisted.value()
+    }
+}
+
+impl<'a, S, K, T> IntoIterator for &'a PersistedSet<S, K, T>
+where
+    S: PersistedStore<K>,
+    K: PersistedKey<Value = HashSet<T>>,
+{
+    type Item = &'a T;
+    type IntoIter = hash_set::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.persisted.value().iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, thread_local, vec::Vec};
+
+    #[derive(Default)]
+    struct FavoritesKey;
+
+    impl PersistedKey for FavoritesKey {
+        type Value = HashSet<u32>;
+    }
+
+    /// Count writes
+    struct Store;
+
+    thread_local! {
+        static VALUE: RefCell<(Option<HashSet<u32>>, usize)> =
+            RefCell::default();
+    }
+
+    impl Store {
+        fn writes() -> usize {
+            VALUE.with(|value| value.borrow().1)
+        }
+    }
+
+    impl PersistedStore<FavoritesKey> for Store {
+        fn load_persisted(_: &FavoritesKey) -> Option<HashSet<u32>> {
+            VALUE.with(|value| value.borrow().0.clone())
+        }
+
+        fn store_persisted(_: &FavoritesKey, set: &HashSet<u32>) {
+            VALUE.with(|value| {
+                let mut value = value.borrow_mut();
+                value.0 = Some(set.clone());
+                value.1 += 1;
+            })
+        }
+    }
+
+    #[test]
+    fn test_persisted_set() {
+        let mut favorites =
+            PersistedSet::<Store, _, _>::new_bounded(FavoritesKey, 2);
+        assert!(favorites.insert(1));
+        assert!(!favorites.insert(1));
+        assert!(favorites.insert(2));
+        // Full
+        assert!(!favorites.insert(3));
+        assert_eq!(Store::writes(), 2);
+        assert!(!favorites.remove(&3));
+        assert!(favorites.remove(&1));
+        assert_eq!(Store::writes(), 3);
+
+        // Reloaded
+        let favorites = PersistedSet::<Store, _, _>::new(FavoritesKey);
+        assert_eq!(favorites.iter().collect::<Vec<_>>(), [&2]);
+        assert_eq!((&favorites).into_iter().count(), 1);
+    }
+
+    #[test]
+    fn test_persisted_set_bulk() {
+        // Replaces the stored set, with a single write
+        let mut favorites: PersistedSet<Store, FavoritesKey, u32> =
+            [1, 2, 2].into_iter().collect();
+        let writes = Store::writes();
+        assert_eq!(favorites.len(), 2);
+
+        favorites.extend([2, 3, 4, 3]);
+        assert_eq!(Store::writes(), writes + 1);
+        assert_eq!(favorites.len(), 4);
+        // Nothing new, so nothing is written
+        favorites.extend([1, 4]);
+        assert_eq!(Store::writes(), writes + 1);
+
+        // Bounded sets only take values until full
+        let mut favorites =
+            PersistedSet::<Store, _, _>::new_bounded(FavoritesKey, 5);
+        favorites.extend([5, 6, 7]);
+        assert_eq!(Store::writes(), writes + 2);
+        assert_eq!(favorites.len(), 5);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{Persisted, PersistedKey, PersistedStore};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, string::String};
+
+/// A serializable snapshot of the in-memory values of many persisted
+/// wrappers, of any key types. This is useful for exporting settings to a file
+/// and importing them later. Values are captured with [Self::add], and written
+/// back with [Self::apply].
+///
+/// Values are stored as JSON values, grouped by [PersistedKey::type_name] and
+/// then by the JSON-serialized key, the same layout used by
+/// [JsonFileStore](crate::JsonFileStore):
+///
+/// ```json
+/// {"my_crate::VolumeKey": {"null": 80}}
+/// ```
+///
+/// The snapshot itself can be serialized with any serde format.
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{Persisted, PersistedKey, PersistedStore, Snapshot};
+/// use serde::Serialize;
+/// # struct Store;
+/// # impl<K: PersistedKey> PersistedStore<K> for Store {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+///
+/// #[derive(PersistedKey, Serialize)]
+/// #[persisted(u32)]
+/// struct VolumeKey;
+///
+/// #[derive(PersistedKey, Serialize)]
+/// #[persisted(bool)]
+/// struct DarkModeKey;
+///
+/// let mut volume = Persisted::<Store, _>::new(VolumeKey, 80);
+/// let dark_mode = Persisted::<Store, _>::new(DarkModeKey, true);
+///
+/// let mut snapshot = Snapshot::new();
+/// snapshot.add(&volume).unwrap();
+/// snapshot.add(&dark_mode).unwrap();
+/// let exported = serde_json::to_string(&snapshot).unwrap();
+///
+/// *volume.get_mut() = 20;
+/// let imported: Snapshot = serde_json::from_str(&exported).unwrap();
+/// assert!(imported.apply(&mut volume).unwrap());
+/// assert_eq!(volume.get(), 80);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Snapshot {
+    values: BTreeMap<String, BTreeMap<String, Value>>,
+}
+
+impl Snapshot {
+    /// Create an empty snapshot
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Capture the current in-memory value of a wrapper. If the snapshot
+    /// already has a value for the same key, it's replaced. Return an error
+    /// if the key or value fails to serialize.
+    pub fn add<S, K>(
+        &mut self,
+        persisted: &Persisted<S, K>,
+    ) -> serde_json::Result<()>
+    where
+        S: PersistedStore<K>,
+        K: PersistedKey + Serialize,
+        K::Value: Serialize,
+    {
+        let key = serde_json::to_string(persisted.key())?;
+        let value = serde_json::to_value(persisted.value())?;
+        self.values
+            .entry(K::type_name().into())
+            .or_default()
+            .insert(key, value);
+        Ok(())
+    }
+
+    /// Write the captured value for a wrapper's key back into the wrapper. The
+    /// value is passed through [PersistedKey::validate], then persisted just
+    /// like a mutation via [Persisted::get_mut]. Return `Ok(false)` if the
+    /// snapshot has no value for the key, or an error if the captured value
+    /// fails to deserialize.
+    pub fn apply<S, K>(
+        &self,
+        persisted: &mut Persisted<S, K>,
+    ) -> serde_json::Result<bool>
+    where
+        S: PersistedStore<K>,
+        K: PersistedKey + Serialize,
+        K::Value: DeserializeOwned,
+    {
+        let key = serde_json::to_string(persisted.key())?;
+        let Some(value) = self
+            .values
+            .get(K::type_name())
+            .and_then(|values| values.get(&key))
+        else {
+            return Ok(false);
+        };
+        let value = K::validate(K::Value::deserialize(value)?);
+        *persisted.get_mut() = value;
+        Ok(true)
+    }
+
+    /// Get the number of captured values
+    pub fn len(&self) -> usize {
+        self.values.values().map(BTreeMap::len).sum()
+    }
+
+    /// Does the snapshot have no captured values?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        cell::RefCell, collections::HashMap, string::ToString, thread_local,
+    };
+
+    #[derive(Serialize)]
+    struct VolumeKey(u8);
+
+    impl PersistedKey for VolumeKey {
+        type Value = u32;
+
+        fn type_name() -> &'static str {
+            "VolumeKey"
+        }
+    }
+
+    #[derive(Serialize)]
+    struct NameKey;
+
+    impl PersistedKey for NameKey {
+        type Value = String;
+
+        fn type_name() -> &'static str {
+            "NameKey"
+        }
+    }
+
+    thread_local! {
+        static WRITES: RefCell<HashMap<&'static str, usize>> =
+            RefCell::default();
+    }
+
+    /// Count writes for each key type
+    struct Store;
+
+    impl<K: PersistedKey> PersistedStore<K> for Store {
+        fn load_persisted(_: &K) -> Option<K::Value> {
+            None
+        }
+
+        fn store_persisted(_: &K, _: &K::Value) {
+            WRITES.with(|writes| {
+                *writes.borrow_mut().entry(K::type_name()).or_default() += 1;
+            });
+        }
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut volume1 = Persisted::<Store, _>::new(VolumeKey(1), 10);
+        let mut volume2 = Persisted::<Store, _>::new(VolumeKey(2), 20);
+        let mut name = Persisted::<Store, _>::new(NameKey, "Susan".into());
+
+        let mut snapshot = Snapshot::new();
+        snapshot.add(&volume1).unwrap();
+        snapshot.add(&volume2).unwrap();
+        snapshot.add(&name).unwrap();
+        assert_eq!(snapshot.len(), 3);
+        assert_eq!(
+            serde_json::to_string(&snapshot).unwrap(),
+            r#"{"NameKey":{"null":"Susan"},"VolumeKey":{"1":10,"2":20}}"#
+        );
+
+        *volume1.get_mut() = 0;
+        *volume2.get_mut() = 0;
+        *name.get_mut() = "Fred".to_string();
+        WRITES.with(|writes| writes.borrow_mut().clear());
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+        assert!(snapshot.apply(&mut volume1).unwrap());
+        assert!(snapshot.apply(&mut volume2).unwrap());
+        assert!(snapshot.apply(&mut name).unwrap());
+        assert_eq!(volume1.get(), 10);
+        assert_eq!(volume2.get(), 20);
+        assert_eq!(name.value(), "Susan");
+        // Applied values are persisted
+        WRITES.with(|writes| {
+            assert_eq!(writes.borrow().get("VolumeKey"), Some(&2));
+            assert_eq!(writes.borrow().get("NameKey"), Some(&1));
+        });
+
+        // Missing values are skipped
+        let mut volume3 = Persisted::<Store, _>::new(VolumeKey(3), 30);
+        assert!(!snapshot.apply(&mut volume3).unwrap());
+        assert_eq!(volume3.get(), 30);
+    }
+}
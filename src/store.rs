@@ -0,0 +1,724 @@
+//! Store combinators, which compose one or more existing stores into a new
+//! store
+
+mod blocking;
+mod remap;
+#[cfg(feature = "std")]
+mod retry;
+#[cfg(feature = "std")]
+mod ttl;
+
+pub use blocking::{AsyncPersistedStore, BlockOn, BlockingStore};
+pub use remap::{KeyRenames, NamedStore, RemapStore};
+#[cfg(feature = "std")]
+pub use retry::{
+    DefaultRetryPolicy, RetryPolicy, RetryStore, TryPersistedStore,
+};
+#[cfg(feature = "std")]
+pub use ttl::{Expiring, TtlKey, TtlStore};
+
+use crate::{PersistedKey, PersistedStore};
+use core::{fmt::Debug, marker::PhantomData};
+
+/// A store that writes to two stores simultaneously. Values are persisted to
+/// both `A` and `B`. When loading, `A` is checked first. If it doesn't have a
+/// value, `B` is used as a fallback.
+///
+/// A typical use case is pairing a fast in-memory cache (`A`) with a slower
+/// but durable store (`B`), such as a file or database.
+///
+/// For [PersistedStore::compare_and_swap], `A` is authoritative: the
+/// comparison and write happen in `A` (atomically, if `A` supports it), and
+/// if they succeed the new value is then written to `B` unconditionally. If
+/// `A` has no value, the comparison is against `B` instead, since that's what
+/// would be loaded.
+///
+/// Like any other store, the two inner stores must be statically accessible.
+/// `TeeStore` never holds instances of either store; it just forwards to their
+/// static functions. This means `TeeStore` itself is never instantiated either;
+/// it's only ever used as a type parameter.
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{Persisted, PersistedKey, PersistedStore, TeeStore};
+/// use std::cell::Cell;
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(u32)]
+/// struct CountKey;
+///
+/// /// Fast in-memory store
+/// struct Cache;
+///
+/// impl Cache {
+///     thread_local! {
+///         static VALUE: Cell<Option<u32>> = Cell::default();
+///     }
+/// }
+///
+/// impl PersistedStore<CountKey> for Cache {
+///     fn load_persisted(_key: &CountKey) -> Option<u32> {
+///         Self::VALUE.with(Cell::get)
+///     }
+///
+///     fn store_persisted(_key: &CountKey, value: &u32) {
+///         Self::VALUE.with(|cell| cell.set(Some(*value)))
+///     }
+/// }
+///
+/// /// Pretend this is saving to disk
+/// struct Disk;
+///
+/// impl Disk {
+///     thread_local! {
+///         static VALUE: Cell<Option<u32>> = Cell::default();
+///     }
+/// }
+///
+/// impl PersistedStore<CountKey> for Disk {
+///     fn load_persisted(_key: &CountKey) -> Option<u32> {
+///         Self::VALUE.with(Cell::get)
+///     }
+///
+///     fn store_persisted(_key: &CountKey, value: &u32) {
+///         Self::VALUE.with(|cell| cell.set(Some(*value)))
+///     }
+/// }
+///
+/// type Store = TeeStore<Cache, Disk>;
+///
+/// let mut count = Persisted::<Store, _>::new(CountKey, 0);
+/// *count.get_mut() = 3;
+///
+/// // The value was written to both stores
+/// assert_eq!(Cache::load_persisted(&CountKey), Some(3));
+/// assert_eq!(Disk::load_persisted(&CountKey), Some(3));
+/// ```
+#[derive(Debug)]
+pub struct TeeStore<A, B> {
+    phantom: PhantomData<(A, B)>,
+}
+
+impl<K, A, B> PersistedStore<K> for TeeStore<A, B>
+where
+    K: PersistedKey,
+    A: PersistedStore<K>,
+    B: PersistedStore<K>,
+{
+    fn load_persisted(key: &K) -> Option<K::Value> {
+        A::load_persisted(key).or_else(|| B::load_persisted(key))
+    }
+
+    fn store_persisted(key: &K, value: &K::Value) {
+        A::store_persisted(key, value);
+        B::store_persisted(key, value);
+    }
+
+    fn store_persisted_diff(key: &K, old: Option<&K::Value>, new: &K::Value) {
+        A::store_persisted_diff(key, old, new);
+        B::store_persisted_diff(key, old, new);
+    }
+
+    fn remove_persisted(key: &K) {
+        A::remove_persisted(key);
+        B::remove_persisted(key);
+    }
+
+    fn compare_and_swap(
+        key: &K,
+        expected: Option<&K::Value>,
+        new: &K::Value,
+    ) -> bool
+    where
+        K::Value: PartialEq,
+    {
+        let swapped = if A::contains(key) {
+            A::compare_and_swap(key, expected, new)
+        } else if B::load_persisted(key).as_ref() == expected {
+            // Fails if another writer beat us to A
+            A::compare_and_swap(key, None, new)
+        } else {
+            false
+        };
+        if !swapped {
+            return false;
+        }
+        if K::skip_if_default() && K::is_default(new) {
+            B::remove_persisted(key);
+        } else {
+            B::store_persisted(key, new);
+        }
+        true
+    }
+
+    fn contains(key: &K) -> bool {
+        A::contains(key) || B::contains(key)
+    }
+
+    /// Keys from both stores. Keys in both are only listed once, from `A`.
+    #[cfg(feature = "alloc")]
+    fn keys() -> alloc::vec::Vec<K> {
+        let mut keys = A::keys();
+        keys.extend(B::keys().into_iter().filter(|key| !A::contains(key)));
+        keys
+    }
+
+    fn flush() {
+        A::flush();
+        B::flush();
+    }
+
+    fn warm_up() {
+        A::warm_up();
+        B::warm_up();
+    }
+
+    /// Report to whichever store the value would be loaded from
+    fn on_load_error(key: &K) {
+        if A::contains(key) {
+            A::on_load_error(key);
+        } else {
+            B::on_load_error(key);
+        }
+    }
+}
+
+/// A store that layers a writable store over a read-only fallback. When
+/// loading, `P` (the primary store) is checked first. If it doesn't have a
+/// value, `D` (the defaults) is used. Writes and removals only go to `P`;
+/// `D` is never modified.
+///
+/// This models the common pattern of shipping default settings with your
+/// program (e.g. in an embedded config file) while saving user overrides
+/// separately. Unlike [TeeStore], values are never written to the fallback.
+/// Note that removing a value from `P` will cause the default from `D` to be
+/// loaded next time, rather than nothing.
+///
+/// [PersistedStore::compare_and_swap] compares against the value that would
+/// be loaded, i.e. the default from `D` if `P` has no value, and writes to
+/// `P` atomically if `P` supports it.
+///
+/// Like [TeeStore], this is never instantiated. Both inner stores must be
+/// statically accessible.
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{FallbackStore, Persisted, PersistedKey, PersistedStore};
+/// # use std::cell::Cell;
+/// # struct User;
+/// # impl User {
+/// #     thread_local! {
+/// #         static VALUE: Cell<Option<u32>> = Cell::default();
+/// #     }
+/// # }
+/// # impl PersistedStore<VolumeKey> for User {
+/// #     fn load_persisted(_: &VolumeKey) -> Option<u32> {
+/// #         Self::VALUE.with(Cell::get)
+/// #     }
+/// #     fn store_persisted(_: &VolumeKey, value: &u32) {
+/// #         Self::VALUE.with(|cell| cell.set(Some(*value)))
+/// #     }
+/// # }
+///
+/// /// Defaults bundled with the program
+/// struct Bundled;
+///
+/// impl PersistedStore<VolumeKey> for Bundled {
+///     fn load_persisted(_key: &VolumeKey) -> Option<u32> {
+///         Some(50)
+///     }
+///
+///     fn store_persisted(_key: &VolumeKey, _value: &u32) {
+///         unreachable!("Defaults are read-only")
+///     }
+/// }
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(u32)]
+/// struct VolumeKey;
+///
+/// type Store = FallbackStore<User, Bundled>;
+///
+/// let mut volume = Persisted::<Store, _>::new(VolumeKey, 0);
+/// assert_eq!(volume.get(), 50);
+/// *volume.get_mut() = 80;
+/// assert_eq!(User::load_persisted(&VolumeKey), Some(80));
+/// ```
+#[derive(Debug)]
+pub struct FallbackStore<P, D> {
+    phantom: PhantomData<(P, D)>,
+}
+
+impl<K, P, D> PersistedStore<K> for FallbackStore<P, D>
+where
+    K: PersistedKey,
+    P: PersistedStore<K>,
+    D: PersistedStore<K>,
+{
+    fn load_persisted(key: &K) -> Option<K::Value> {
+        P::load_persisted(key).or_else(|| D::load_persisted(key))
+    }
+
+    fn store_persisted(key: &K, value: &K::Value) {
+        P::store_persisted(key, value);
+    }
+
+    fn store_persisted_diff(key: &K, old: Option<&K::Value>, new: &K::Value) {
+        P::store_persisted_diff(key, old, new);
+    }
+
+    fn remove_persisted(key: &K) {
+        P::remove_persisted(key);
+    }
+
+    fn compare_and_swap(
+        key: &K,
+        expected: Option<&K::Value>,
+        new: &K::Value,
+    ) -> bool
+    where
+        K::Value: PartialEq,
+    {
+        if P::contains(key) {
+            P::compare_and_swap(key, expected, new)
+        } else if D::load_persisted(key).as_ref() == expected {
+            // Fails if another writer beat us to the primary store
+            P::compare_and_swap(key, None, new)
+        } else {
+            false
+        }
+    }
+
+    fn contains(key: &K) -> bool {
+        P::contains(key) || D::contains(key)
+    }
+
+    /// Keys from both stores. Keys in both are only listed once, from `P`.
+    #[cfg(feature = "alloc")]
+    fn keys() -> alloc::vec::Vec<K> {
+        let mut keys = P::keys();
+        keys.extend(D::keys().into_iter().filter(|key| !P::contains(key)));
+        keys
+    }
+
+    fn flush() {
+        P::flush();
+    }
+
+    fn warm_up() {
+        P::warm_up();
+        D::warm_up();
+    }
+
+    /// Report to whichever store the value would be loaded from
+    fn on_load_error(key: &K) {
+        if P::contains(key) {
+            P::on_load_error(key);
+        } else {
+            D::on_load_error(key);
+        }
+    }
+}
+
+/// A store that routes each key type to one of two stores. The store is
+/// selected by [PersistedKey::store_selector]: [StoreSelector::Left] uses `A`,
+/// and [StoreSelector::Right] uses `B`. This allows a single store type to
+/// route different values to different backends, e.g. sensitive values to a
+/// secure store and everything else to disk.
+///
+/// Like [TeeStore], this is never instantiated. Both inner stores must be
+/// statically accessible.
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{EitherStore, PersistedKey, PersistedStore, StoreSelector};
+/// # struct Disk;
+/// # impl<K: PersistedKey> PersistedStore<K> for Disk {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+/// # struct Keychain;
+/// # impl<K: PersistedKey> PersistedStore<K> for Keychain {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+///
+/// type Store = EitherStore<Disk, Keychain>;
+///
+/// /// Stored on disk
+/// #[derive(PersistedKey)]
+/// #[persisted(bool)]
+/// struct DarkModeKey;
+///
+/// /// Stored in the keychain
+/// #[derive(PersistedKey)]
+/// #[persisted(String, store_selector = Right)]
+/// struct TokenKey;
+///
+/// assert_eq!(DarkModeKey::store_selector(), StoreSelector::Left);
+/// assert_eq!(TokenKey::store_selector(), StoreSelector::Right);
+/// ```
+#[derive(Debug)]
+pub struct EitherStore<A, B> {
+    phantom: PhantomData<(A, B)>,
+}
+
+impl<K, A, B> PersistedStore<K> for EitherStore<A, B>
+where
+    K: PersistedKey,
+    A: PersistedStore<K>,
+    B: PersistedStore<K>,
+{
+    fn load_persisted(key: &K) -> Option<K::Value> {
+        match K::store_selector() {
+            StoreSelector::Left => A::load_persisted(key),
+            StoreSelector::Right => B::load_persisted(key),
+        }
+    }
+
+    fn store_persisted(key: &K, value: &K::Value) {
+        match K::store_selector() {
+            StoreSelector::Left => A::store_persisted(key, value),
+            StoreSelector::Right => B::store_persisted(key, value),
+        }
+    }
+
+    fn store_persisted_diff(key: &K, old: Option<&K::Value>, new: &K::Value) {
+        match K::store_selector() {
+            StoreSelector::Left => A::store_persisted_diff(key, old, new),
+            StoreSelector::Right => B::store_persisted_diff(key, old, new),
+        }
+    }
+
+    fn remove_persisted(key: &K) {
+        match K::store_selector() {
+            StoreSelector::Left => A::remove_persisted(key),
+            StoreSelector::Right => B::remove_persisted(key),
+        }
+    }
+
+    fn compare_and_swap(
+        key: &K,
+        expected: Option<&K::Value>,
+        new: &K::Value,
+    ) -> bool
+    where
+        K::Value: PartialEq,
+    {
+        match K::store_selector() {
+            StoreSelector::Left => A::compare_and_swap(key, expected, new),
+            StoreSelector::Right => B::compare_and_swap(key, expected, new),
+        }
+    }
+
+    fn contains(key: &K) -> bool {
+        match K::store_selector() {
+            StoreSelector::Left => A::contains(key),
+            StoreSelector::Right => B::contains(key),
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    fn keys() -> alloc::vec::Vec<K> {
+        match K::store_selector() {
+            StoreSelector::Left => A::keys(),
+            StoreSelector::Right => B::keys(),
+        }
+    }
+
+    fn flush() {
+        match K::store_selector() {
+            StoreSelector::Left => A::flush(),
+            StoreSelector::Right => B::flush(),
+        }
+    }
+
+    fn warm_up() {
+        match K::store_selector() {
+            StoreSelector::Left => A::warm_up(),
+            StoreSelector::Right => B::warm_up(),
+        }
+    }
+
+    fn on_load_error(key: &K) {
+        match K::store_selector() {
+            StoreSelector::Left => A::on_load_error(key),
+            StoreSelector::Right => B::on_load_error(key),
+        }
+    }
+}
+
+/// Which inner store of an [EitherStore] a key should be routed to
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum StoreSelector {
+    /// The first store
+    #[default]
+    Left,
+    /// The second store
+    Right,
+}
+
+/// A store that logs every operation via the [log] crate, then forwards it to
+/// the inner store `S`. Each message includes [PersistedKey::type_name] and
+/// the key's [Debug] representation. Loads also log whether a value was found.
+/// All messages are logged at the `debug` level, under the `persisted` target.
+///
+/// ```
+/// use persisted::{LoggingStore, Persisted, PersistedKey, PersistedStore};
+/// # struct Store;
+/// # impl<K: PersistedKey> PersistedStore<K> for Store {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+///
+/// #[derive(Debug, PersistedKey)]
+/// #[persisted(u32)]
+/// struct CountKey;
+///
+/// // Logs "Loading persisted value for ..." on creation
+/// let mut count = Persisted::<LoggingStore<Store>, _>::new(CountKey, 0);
+/// // Logs "Storing persisted value for ..."
+/// *count.get_mut() += 1;
+/// ```
+#[cfg(feature = "log")]
+#[derive(Debug)]
+pub struct LoggingStore<S> {
+    phantom: PhantomData<S>,
+}
+
+#[cfg(feature = "log")]
+impl<K, S> PersistedStore<K> for LoggingStore<S>
+where
+    K: Debug + PersistedKey,
+    S: PersistedStore<K>,
+{
+    fn load_persisted(key: &K) -> Option<K::Value> {
+        let value = S::load_persisted(key);
+        log::debug!(
+            target: "persisted",
+            "Loading persisted value for {} {key:?}: {}",
+            K::type_name(),
+            if value.is_some() { "found" } else { "missing" },
+        );
+        value
+    }
+
+    fn store_persisted(key: &K, value: &K::Value) {
+        log::debug!(
+            target: "persisted",
+            "Storing persisted value for {} {key:?}",
+            K::type_name(),
+        );
+        S::store_persisted(key, value);
+    }
+
+    fn store_persisted_diff(key: &K, old: Option<&K::Value>, new: &K::Value) {
+        log::debug!(
+            target: "persisted",
+            "Storing persisted value for {} {key:?}",
+            K::type_name(),
+        );
+        S::store_persisted_diff(key, old, new);
+    }
+
+    fn remove_persisted(key: &K) {
+        log::debug!(
+            target: "persisted",
+            "Removing persisted value for {} {key:?}",
+            K::type_name(),
+        );
+        S::remove_persisted(key);
+    }
+
+    fn compare_and_swap(
+        key: &K,
+        expected: Option<&K::Value>,
+        new: &K::Value,
+    ) -> bool
+    where
+        K::Value: PartialEq,
+    {
+        let swapped = S::compare_and_swap(key, expected, new);
+        log::debug!(
+            target: "persisted",
+            "Compare-and-swapping persisted value for {} {key:?}: {}",
+            K::type_name(),
+            if swapped { "swapped" } else { "mismatch" },
+        );
+        swapped
+    }
+
+    fn contains(key: &K) -> bool {
+        S::contains(key)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn keys() -> alloc::vec::Vec<K> {
+        let keys = S::keys();
+        log::debug!(
+            target: "persisted",
+            "Listing persisted keys for {}: {} found",
+            K::type_name(),
+            keys.len(),
+        );
+        keys
+    }
+
+    fn flush() {
+        log::debug!(
+            target: "persisted",
+            "Flushing persisted values for {}",
+            K::type_name(),
+        );
+        S::flush();
+    }
+
+    fn warm_up() {
+        log::debug!(
+            target: "persisted",
+            "Warming up persisted values for {}",
+            K::type_name(),
+        );
+        S::warm_up();
+    }
+
+    fn on_load_error(key: &K) {
+        log::debug!(
+            target: "persisted",
+            "Invalid persisted value for {} {key:?}",
+            K::type_name(),
+        );
+        S::on_load_error(key);
+    }
+}
+
+/// Define a store type that forwards every [PersistedStore] method to another
+/// store. Because stores are accessed statically, they can't be composed
+/// through smart pointers like `Box` or `Arc`. Instead, define a named store
+/// that delegates to a stack of combinators. This gives the stack a short
+/// name, and lets you change its composition in one place without touching
+/// any wrapper types.
+///
+/// ```
+/// use persisted::{
+///     delegate_store, FallbackStore, Persisted, PersistedKey, PersistedStore,
+///     TeeStore,
+/// };
+/// # struct MemoryStore;
+/// # impl<K: PersistedKey> PersistedStore<K> for MemoryStore {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+/// # struct FileStore;
+/// # impl<K: PersistedKey> PersistedStore<K> for FileStore {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+///
+/// delegate_store! {
+///     /// Cache values in memory, backed by a file
+///     pub struct AppStore => TeeStore<MemoryStore, FileStore>;
+/// }
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(u32)]
+/// struct VolumeKey;
+///
+/// let volume = Persisted::<AppStore, _>::new(VolumeKey, 50);
+/// ```
+#[macro_export]
+macro_rules! delegate_store {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident => $inner:ty;
+    ) => {
+        $(#[$attr])*
+        #[derive(Debug)]
+        $vis struct $name;
+
+        impl<K> $crate::PersistedStore<K> for $name
+        where
+            K: $crate::PersistedKey,
+            $inner: $crate::PersistedStore<K>,
+        {
+            fn load_persisted(key: &K) -> ::core::option::Option<K::Value> {
+                <$inner as $crate::PersistedStore<K>>::load_persisted(key)
+            }
+
+            fn store_persisted(key: &K, value: &K::Value) {
+                <$inner as $crate::PersistedStore<K>>::store_persisted(
+                    key, value,
+                )
+            }
+
+            fn store_persisted_diff(
+                key: &K,
+                old: ::core::option::Option<&K::Value>,
+                new: &K::Value,
+            ) {
+                <$inner as $crate::PersistedStore<K>>::store_persisted_diff(
+                    key, old, new,
+                )
+            }
+
+            fn remove_persisted(key: &K) {
+                <$inner as $crate::PersistedStore<K>>::remove_persisted(key)
+            }
+
+            fn compare_and_swap(
+                key: &K,
+                expected: ::core::option::Option<&K::Value>,
+                new: &K::Value,
+            ) -> bool
+            where
+                K::Value: ::core::cmp::PartialEq,
+            {
+                <$inner as $crate::PersistedStore<K>>::compare_and_swap(
+                    key, expected, new,
+                )
+            }
+
+            fn contains(key: &K) -> bool {
+                <$inner as $crate::PersistedStore<K>>::contains(key)
+            }
+
+            $crate::__delegate_store_keys!($inner);
+
+            fn flush() {
+                <$inner as $crate::PersistedStore<K>>::flush()
+            }
+
+            fn warm_up() {
+                <$inner as $crate::PersistedStore<K>>::warm_up()
+            }
+
+            fn on_load_error(key: &K) {
+                <$inner as $crate::PersistedStore<K>>::on_load_error(key)
+            }
+        }
+    };
+}
+
+/// Forward [PersistedStore::keys], which only exists with the `alloc` feature.
+/// This has to be decided by *our* features, not the caller's, so it can't be
+/// a `cfg` inside [delegate_store!].
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __delegate_store_keys {
+    ($inner:ty) => {
+        fn keys() -> $crate::__alloc::vec::Vec<K> {
+            <$inner as $crate::PersistedStore<K>>::keys()
+        }
+    };
+}
+
+#[cfg(not(feature = "alloc"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __delegate_store_keys {
+    ($inner:ty) => {};
+}
@@ -0,0 +1,182 @@
+use crate::{PersistedKey, PersistedStore};
+use core::{future::Future, marker::PhantomData};
+
+/// An asynchronous version of [PersistedStore]. Implement this for stores
+/// whose backend only has an async API, then wrap them in [BlockingStore] to
+/// get a [PersistedStore] that blocks on each operation.
+pub trait AsyncPersistedStore<K: PersistedKey> {
+    /// Load a persisted value from the store. Resolve to `None` if the value
+    /// isn't present.
+    fn load_persisted_async(key: &K) -> impl Future<Output = Option<K::Value>>;
+
+    /// Persist a value to the store
+    fn store_persisted_async(
+        key: &K,
+        value: &K::Value,
+    ) -> impl Future<Output = ()>;
+
+    /// Remove a value from the store. See [PersistedStore::remove_persisted].
+    /// The default implementation does nothing.
+    fn remove_persisted_async(_key: &K) -> impl Future<Output = ()> {
+        async {}
+    }
+}
+
+/// An async runtime that can run a future to completion on the current
+/// thread, used by [BlockingStore]. Implement this on a marker type. Because
+/// stores are accessed statically, the runtime must be too, typically via a
+/// `static` that's initialized on first use. See [BlockingStore] for an
+/// example with tokio.
+pub trait BlockOn {
+    /// Run a future to completion, blocking the current thread until it's
+    /// done
+    fn block_on<F: Future>(future: F) -> F::Output;
+}
+
+/// A store that bridges an [AsyncPersistedStore] into the synchronous
+/// [PersistedStore] interface, by blocking on each operation with the runtime
+/// `E`. Persisting happens synchronously, often while dropping a guard, so
+/// there's no way to `.await` a store operation; this is the pragmatic
+/// alternative for backends that are only available as async.
+///
+/// ## Deadlocks
+///
+/// Blocking on a future from *within* an async runtime is a recipe for
+/// deadlock (or a panic, in tokio's case). Persisted wrappers must **not** be
+/// accessed from a task running on the same runtime used by `E`. The safest
+/// setup is a dedicated runtime that's only used by the store, with wrappers
+/// accessed from regular (non-async) code, e.g. a UI thread.
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{
+///     AsyncPersistedStore, BlockOn, BlockingStore, Persisted, PersistedKey,
+/// };
+/// use std::{
+///     future::Future,
+///     sync::{Mutex, OnceLock},
+/// };
+/// use tokio::runtime::{Builder, Runtime};
+///
+/// /// A store whose backend is only accessible asynchronously
+/// struct RemoteStore;
+///
+/// static VALUE: Mutex<Option<u32>> = Mutex::new(None);
+///
+/// impl AsyncPersistedStore<CountKey> for RemoteStore {
+///     async fn load_persisted_async(_: &CountKey) -> Option<u32> {
+///         // Pretend this is a network request
+///         tokio::task::yield_now().await;
+///         *VALUE.lock().unwrap()
+///     }
+///
+///     async fn store_persisted_async(_: &CountKey, value: &u32) {
+///         tokio::task::yield_now().await;
+///         *VALUE.lock().unwrap() = Some(*value);
+///     }
+/// }
+///
+/// /// A dedicated runtime for the store
+/// struct Tokio;
+///
+/// impl BlockOn for Tokio {
+///     fn block_on<F: Future>(future: F) -> F::Output {
+///         static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+///         RUNTIME
+///             .get_or_init(|| Builder::new_current_thread().build().unwrap())
+///             .block_on(future)
+///     }
+/// }
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(u32)]
+/// struct CountKey;
+///
+/// type Store = BlockingStore<RemoteStore, Tokio>;
+///
+/// let mut count = Persisted::<Store, _>::new(CountKey, 0);
+/// *count.get_mut() += 1;
+/// assert_eq!(*VALUE.lock().unwrap(), Some(1));
+/// ```
+#[derive(Debug)]
+pub struct BlockingStore<S, E> {
+    phantom: PhantomData<(S, E)>,
+}
+
+impl<K, S, E> PersistedStore<K> for BlockingStore<S, E>
+where
+    K: PersistedKey,
+    S: AsyncPersistedStore<K>,
+    E: BlockOn,
+{
+    fn load_persisted(key: &K) -> Option<K::Value> {
+        E::block_on(S::load_persisted_async(key))
+    }
+
+    fn store_persisted(key: &K, value: &K::Value) {
+        E::block_on(S::store_persisted_async(key, value));
+    }
+
+    fn remove_persisted(key: &K) {
+        E::block_on(S::remove_persisted_async(key));
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, thread_local};
+    use tokio::runtime::Builder;
+
+    struct CountKey;
+
+    impl PersistedKey for CountKey {
+        type Value = u32;
+    }
+
+    thread_local! {
+        static VALUE: Cell<Option<u32>> = const { Cell::new(None) };
+    }
+
+    struct AsyncStore;
+
+    impl AsyncPersistedStore<CountKey> for AsyncStore {
+        async fn load_persisted_async(_: &CountKey) -> Option<u32> {
+            tokio::task::yield_now().await;
+            VALUE.get()
+        }
+
+        async fn store_persisted_async(_: &CountKey, value: &u32) {
+            tokio::task::yield_now().await;
+            VALUE.set(Some(*value));
+        }
+
+        async fn remove_persisted_async(_: &CountKey) {
+            tokio::task::yield_now().await;
+            VALUE.set(None);
+        }
+    }
+
+    struct Tokio;
+
+    impl BlockOn for Tokio {
+        fn block_on<F: Future>(future: F) -> F::Output {
+            Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(future)
+        }
+    }
+
+    #[test]
+    fn test_blocking_store() {
+        type Store = BlockingStore<AsyncStore, Tokio>;
+
+        assert_eq!(Store::load_persisted(&CountKey), None);
+        Store::store_persisted(&CountKey, &3);
+        assert_eq!(Store::load_persisted(&CountKey), Some(3));
+        Store::remove_persisted(&CountKey);
+        assert_eq!(Store::load_persisted(&CountKey), None);
+    }
+}
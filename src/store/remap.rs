@@ -0,0 +1,178 @@
+use crate::{PersistedKey, PersistedStore};
+use core::marker::PhantomData;
+
+/// A store whose values are addressed by [PersistedKey::type_name] plus the
+/// key itself. This allows values to be accessed under a name other than the
+/// key type's *current* name, which is needed to migrate renamed keys. See
+/// [RemapStore].
+///
+/// Implementations must be consistent with the [PersistedStore] impl, i.e.
+/// `load_named(K::type_name(), key)` must be equivalent to
+/// `load_persisted(key)`.
+pub trait NamedStore<K: PersistedKey>: PersistedStore<K> {
+    /// Load a persisted value stored under the given type name
+    fn load_named(type_name: &'static str, key: &K) -> Option<K::Value>;
+
+    /// Remove a value stored under the given type name
+    fn remove_named(type_name: &'static str, key: &K);
+}
+
+/// A list of key type renames, used to configure [RemapStore]. Implement this
+/// on a marker type.
+pub trait KeyRenames {
+    /// Pairs of `(old_name, new_name)`, where each name is a
+    /// [PersistedKey::type_name]. Renames are not chained: if a key was
+    /// renamed twice, list both old names with the current name.
+    const RENAMES: &'static [(&'static str, &'static str)];
+
+    /// When a value is found under an old name, should it be rewritten under
+    /// the new name and removed from the old one? If `false`, the value is
+    /// left in place and will be loaded from the old name again next time,
+    /// until it's overwritten. Defaults to `true`.
+    const REWRITE: bool = true;
+}
+
+/// A store that migrates values stored under old key type names. When the
+/// inner store `S` has no value for a key under its current
+/// [PersistedKey::type_name], each old name mapped to the current name by
+/// `R` is checked. If found, the value is moved to the current name (see
+/// [KeyRenames::REWRITE]). All other operations are forwarded to `S`
+/// unchanged, except [PersistedStore::compare_and_swap], which compares
+/// against the value under an old name if there's none under the current
+/// name.
+///
+/// This allows key types to be renamed or moved between modules without
+/// losing user data. Once all data has been migrated, the mapping can be
+/// dropped.
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{
+///     KeyRenames, NamedStore, Persisted, PersistedKey, PersistedStore,
+///     RemapStore,
+/// };
+/// use std::{cell::RefCell, collections::HashMap};
+///
+/// struct Store;
+///
+/// thread_local! {
+///     static VALUES: RefCell<HashMap<&'static str, u32>> =
+///         Default::default();
+/// }
+///
+/// impl<K: PersistedKey<Value = u32>> PersistedStore<K> for Store {
+///     fn load_persisted(key: &K) -> Option<u32> {
+///         Self::load_named(K::type_name(), key)
+///     }
+///
+///     fn store_persisted(_: &K, value: &u32) {
+///         VALUES.with(|map| map.borrow_mut().insert(K::type_name(), *value));
+///     }
+/// }
+///
+/// impl<K: PersistedKey<Value = u32>> NamedStore<K> for Store {
+///     fn load_named(type_name: &'static str, _: &K) -> Option<u32> {
+///         VALUES.with(|map| map.borrow().get(type_name).copied())
+///     }
+///
+///     fn remove_named(type_name: &'static str, _: &K) {
+///         VALUES.with(|map| map.borrow_mut().remove(type_name));
+///     }
+/// }
+///
+/// struct VolumeKey;
+///
+/// impl PersistedKey for VolumeKey {
+///     type Value = u32;
+///     const TYPE_NAME: Option<&'static str> = Some("app::VolumeKey");
+/// }
+///
+/// /// VolumeKey used to be in the settings module
+/// struct Renames;
+///
+/// impl KeyRenames for Renames {
+///     const RENAMES: &'static [(&'static str, &'static str)] =
+///         &[("app::settings::VolumeKey", "app::VolumeKey")];
+/// }
+///
+/// VALUES.with(|map| map.borrow_mut().insert("app::settings::VolumeKey", 80));
+///
+/// let volume = Persisted::<RemapStore<Store, Renames>, _>::new(VolumeKey, 50);
+/// assert_eq!(volume.get(), 80);
+/// // The value was moved to the new name
+/// VALUES.with(|map| {
+///     assert_eq!(map.borrow().get("app::VolumeKey"), Some(&80));
+///     assert_eq!(map.borrow().get("app::settings::VolumeKey"), None);
+/// });
+/// ```
+#[derive(Debug)]
+pub struct RemapStore<S, R> {
+    phantom: PhantomData<(S, R)>,
+}
+
+impl<K, S, R> PersistedStore<K> for RemapStore<S, R>
+where
+    K: PersistedKey,
+    S: NamedStore<K>,
+    R: KeyRenames,
+{
+    fn load_persisted(key: &K) -> Option<K::Value> {
+        if let Some(value) = S::load_persisted(key) {
+            return Some(value);
+        }
+        let type_name = K::type_name();
+        R::RENAMES
+            .iter()
+            .filter(|(_, new_name)| *new_name == type_name)
+            .find_map(|(old_name, _)| {
+                let value = S::load_named(old_name, key)?;
+                if R::REWRITE {
+                    // Write before removing, so the value can't be lost
+                    S::store_persisted(key, &value);
+                    S::remove_named(old_name, key);
+                }
+                Some(value)
+            })
+    }
+
+    fn store_persisted(key: &K, value: &K::Value) {
+        S::store_persisted(key, value);
+    }
+
+    fn store_persisted_diff(key: &K, old: Option<&K::Value>, new: &K::Value) {
+        S::store_persisted_diff(key, old, new);
+    }
+
+    fn remove_persisted(key: &K) {
+        S::remove_persisted(key);
+    }
+
+    fn compare_and_swap(
+        key: &K,
+        expected: Option<&K::Value>,
+        new: &K::Value,
+    ) -> bool
+    where
+        K::Value: PartialEq,
+    {
+        if !S::contains(key) {
+            // Values under an old name are only visible through a load, which
+            // may also move them to the current name
+            let current = Self::load_persisted(key);
+            if !S::contains(key) {
+                return current.as_ref() == expected
+                    && S::compare_and_swap(key, None, new);
+            }
+        }
+        S::compare_and_swap(key, expected, new)
+    }
+
+    fn flush() {
+        S::flush();
+    }
+
+    fn warm_up() {
+        S::warm_up();
+    }
+}
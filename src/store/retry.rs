@@ -0,0 +1,268 @@
+use crate::{PersistedKey, PersistedStore};
+use core::{marker::PhantomData, time::Duration};
+use std::thread;
+
+/// A fallible version of [PersistedStore]. Implement this for stores that can
+/// fail transiently (e.g. over a network), then wrap them in [RetryStore] to
+/// get a [PersistedStore] that retries failed operations.
+pub trait TryPersistedStore<K: PersistedKey> {
+    /// Error returned by failed operations
+    type Error;
+
+    /// Load a persisted value from the store. Return `Ok(None)` if the value
+    /// isn't present.
+    fn try_load_persisted(key: &K) -> Result<Option<K::Value>, Self::Error>;
+
+    /// Persist a value to the store
+    fn try_store_persisted(
+        key: &K,
+        value: &K::Value,
+    ) -> Result<(), Self::Error>;
+
+    /// Remove a value from the store. See [PersistedStore::remove_persisted].
+    /// The default implementation does nothing.
+    fn try_remove_persisted(_key: &K) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Handle an error that persisted through every retry. Per the
+    /// [PersistedStore] contract, errors aren't propagated to the wrappers.
+    /// The default implementation does nothing.
+    fn on_error(_error: Self::Error) {}
+}
+
+/// Configuration for [RetryStore]. Implement this on a marker type to
+/// override any of the defaults. Because stores are accessed statically, the
+/// configuration is too.
+///
+/// Persisting happens synchronously, often while dropping a guard, so retries
+/// block the calling thread. [Self::BUDGET] caps the total time spent waiting
+/// on any one operation.
+pub trait RetryPolicy {
+    /// Maximum number of attempts per operation, including the first
+    const MAX_ATTEMPTS: u32 = 3;
+    /// Delay before the first retry. The delay doubles after each retry.
+    const BACKOFF: Duration = Duration::from_millis(10);
+    /// Maximum total delay per operation. If the next delay would exceed the
+    /// remaining budget, the operation fails without using its remaining
+    /// attempts.
+    const BUDGET: Duration = Duration::from_millis(100);
+}
+
+/// The default [RetryPolicy]
+#[derive(Debug)]
+pub struct DefaultRetryPolicy;
+
+impl RetryPolicy for DefaultRetryPolicy {}
+
+/// A store that retries failed operations on an inner [TryPersistedStore],
+/// with exponential backoff. If an operation still fails once the policy `P`
+/// is exhausted, the final error is passed to [TryPersistedStore::on_error],
+/// and the operation is treated as a no-op (or a missing value, for loads).
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{
+///     Persisted, PersistedKey, RetryPolicy, RetryStore, TryPersistedStore,
+/// };
+/// use std::time::Duration;
+///
+/// /// A store that's sometimes unreachable
+/// struct RemoteStore;
+///
+/// impl<K: PersistedKey> TryPersistedStore<K> for RemoteStore {
+///     type Error = String;
+///
+///     fn try_load_persisted(_: &K) -> Result<Option<K::Value>, String> {
+///         Ok(None)
+///     }
+///
+///     fn try_store_persisted(_: &K, _: &K::Value) -> Result<(), String> {
+///         Err("Connection reset".into())
+///     }
+///
+///     fn on_error(error: String) {
+///         eprintln!("Error persisting value: {error}");
+///     }
+/// }
+///
+/// /// Keep the UI responsive: give up after 50ms
+/// struct Impatient;
+///
+/// impl RetryPolicy for Impatient {
+///     const BUDGET: Duration = Duration::from_millis(50);
+/// }
+///
+/// #[derive(PersistedKey)]
+/// #[persisted(u32)]
+/// struct CountKey;
+///
+/// let mut count =
+///     Persisted::<RetryStore<RemoteStore, Impatient>, _>::new(CountKey, 0);
+/// *count.get_mut() += 1; // Retried, then dropped
+/// ```
+#[derive(Debug)]
+pub struct RetryStore<S, P = DefaultRetryPolicy> {
+    phantom: PhantomData<(S, P)>,
+}
+
+impl<S, P: RetryPolicy> RetryStore<S, P> {
+    /// Run an operation until it succeeds or the policy is exhausted
+    fn retry<T, E>(
+        mut operation: impl FnMut() -> Result<T, E>,
+    ) -> Result<T, E> {
+        let mut attempts = 1;
+        let mut delay = P::BACKOFF;
+        let mut budget = P::BUDGET;
+        loop {
+            match operation() {
+                Ok(value) => return Ok(value),
+                Err(error) if attempts >= P::MAX_ATTEMPTS || delay > budget => {
+                    return Err(error)
+                }
+                Err(_) => {
+                    thread::sleep(delay);
+                    attempts += 1;
+                    budget -= delay;
+                    delay = delay.saturating_mul(2);
+                }
+            }
+        }
+    }
+}
+
+impl<K, S, P> PersistedStore<K> for RetryStore<S, P>
+where
+    K: PersistedKey,
+    S: TryPersistedStore<K>,
+    P: RetryPolicy,
+{
+    fn load_persisted(key: &K) -> Option<K::Value> {
+        Self::retry(|| S::try_load_persisted(key)).unwrap_or_else(|error| {
+            S::on_error(error);
+            None
+        })
+    }
+
+    fn store_persisted(key: &K, value: &K::Value) {
+        if let Err(error) = Self::retry(|| S::try_store_persisted(key, value)) {
+            S::on_error(error);
+        }
+    }
+
+    fn remove_persisted(key: &K) {
+        if let Err(error) = Self::retry(|| S::try_remove_persisted(key)) {
+            S::on_error(error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, thread_local};
+
+    struct CountKey;
+
+    impl PersistedKey for CountKey {
+        type Value = u32;
+    }
+
+    /// Fails a fixed number of attempts before succeeding
+    struct FlakyStore;
+
+    thread_local! {
+        static FAILURES: Cell<u32> = const { Cell::new(0) };
+        static ATTEMPTS: Cell<u32> = const { Cell::new(0) };
+        static ERRORS: Cell<u32> = const { Cell::new(0) };
+        static VALUE: Cell<Option<u32>> = const { Cell::new(None) };
+    }
+
+    impl FlakyStore {
+        /// Fail the next `failures` attempts, and reset counters
+        fn reset(failures: u32) {
+            FAILURES.set(failures);
+            ATTEMPTS.set(0);
+            ERRORS.set(0);
+        }
+
+        fn attempt() -> Result<(), ()> {
+            ATTEMPTS.set(ATTEMPTS.get() + 1);
+            if FAILURES.get() > 0 {
+                FAILURES.set(FAILURES.get() - 1);
+                Err(())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl TryPersistedStore<CountKey> for FlakyStore {
+        type Error = ();
+
+        fn try_load_persisted(_: &CountKey) -> Result<Option<u32>, ()> {
+            Self::attempt()?;
+            Ok(VALUE.get())
+        }
+
+        fn try_store_persisted(_: &CountKey, value: &u32) -> Result<(), ()> {
+            Self::attempt()?;
+            VALUE.set(Some(*value));
+            Ok(())
+        }
+
+        fn on_error(_: ()) {
+            ERRORS.set(ERRORS.get() + 1);
+        }
+    }
+
+    struct Fast;
+
+    impl RetryPolicy for Fast {
+        const MAX_ATTEMPTS: u32 = 4;
+        const BACKOFF: Duration = Duration::ZERO;
+    }
+
+    #[test]
+    fn test_retry_store() {
+        type Store = RetryStore<FlakyStore, Fast>;
+
+        // Succeeds on the last attempt
+        FlakyStore::reset(3);
+        Store::store_persisted(&CountKey, &3);
+        assert_eq!(ATTEMPTS.get(), 4);
+        assert_eq!(ERRORS.get(), 0);
+        assert_eq!(VALUE.get(), Some(3));
+
+        // Out of attempts
+        FlakyStore::reset(4);
+        Store::store_persisted(&CountKey, &4);
+        assert_eq!(ATTEMPTS.get(), 4);
+        assert_eq!(ERRORS.get(), 1);
+        assert_eq!(VALUE.get(), Some(3));
+
+        // Failed loads are treated as missing
+        FlakyStore::reset(4);
+        assert_eq!(Store::load_persisted(&CountKey), None);
+        FlakyStore::reset(1);
+        assert_eq!(Store::load_persisted(&CountKey), Some(3));
+    }
+
+    #[test]
+    fn test_retry_budget() {
+        struct Limited;
+
+        impl RetryPolicy for Limited {
+            const MAX_ATTEMPTS: u32 = 10;
+            const BACKOFF: Duration = Duration::from_millis(1);
+            const BUDGET: Duration = Duration::from_millis(4);
+        }
+
+        // Delays of 1ms and 2ms fit in the budget, but 4ms doesn't
+        FlakyStore::reset(10);
+        RetryStore::<FlakyStore, Limited>::store_persisted(&CountKey, &5);
+        assert_eq!(ATTEMPTS.get(), 3);
+        assert_eq!(ERRORS.get(), 1);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{PersistedKey, PersistedStore};
+use core::{marker::PhantomData, time::Duration};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A store that expires values after a per-key time-to-live. Each value is
+/// stored in the inner store `S` along with the time it was saved, as an
+/// [Expiring]. When loading, if the value is older than [PersistedKey::ttl],
+/// it's treated as missing. Keys whose TTL is `None` never expire.
+///
+/// Because the inner store persists a different value type than the key, it
+/// must implement [PersistedStore] for [TtlKey]. Storing requires cloning the
+/// key and value, so both must implement [Clone].
+///
+/// ## Example
+///
+/// ```
+/// use persisted::{Persisted, PersistedKey, PersistedStore, TtlKey, TtlStore};
+/// # struct Store;
+/// # impl<K: PersistedKey> PersistedStore<K> for Store {
+/// #     fn load_persisted(_: &K) -> Option<K::Value> { None }
+/// #     fn store_persisted(_: &K, _: &K::Value) {}
+/// # }
+///
+/// /// Don't show the notification again until tomorrow
+/// #[derive(Clone, PersistedKey)]
+/// #[persisted(bool, ttl_secs = 86400)]
+/// struct DismissedKey;
+///
+/// let mut dismissed =
+///     Persisted::<TtlStore<Store>, _>::new(DismissedKey, false);
+/// *dismissed.get_mut() = true;
+/// ```
+#[derive(Debug)]
+pub struct TtlStore<S> {
+    phantom: PhantomData<S>,
+}
+
+impl<K, S> PersistedStore<K> for TtlStore<S>
+where
+    K: Clone + PersistedKey,
+    K::Value: Clone,
+    S: PersistedStore<TtlKey<K>>,
+{
+    fn load_persisted(key: &K) -> Option<K::Value> {
+        let expiring = S::load_persisted(&TtlKey(key.clone()))?;
+        if let Some(ttl) = K::ttl() {
+            let saved_at = UNIX_EPOCH + Duration::from_secs(expiring.saved_at);
+            // If the clock went backwards, consider the value fresh
+            let age = SystemTime::now()
+                .duration_since(saved_at)
+                .unwrap_or_default();
+            if age > ttl {
+                return None;
+            }
+        }
+        Some(expiring.value)
+    }
+
+    fn store_persisted(key: &K, value: &K::Value) {
+        let saved_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        S::store_persisted(
+            &TtlKey(key.clone()),
+            &Expiring {
+                value: value.clone(),
+                saved_at,
+            },
+        );
+    }
+
+    fn remove_persisted(key: &K) {
+        S::remove_persisted(&TtlKey(key.clone()));
+    }
+
+    fn flush() {
+        S::flush();
+    }
+
+    fn warm_up() {
+        S::warm_up();
+    }
+}
+
+/// A key wrapper used by [TtlStore] to persist values along with the time
+/// they were saved. It shares the inner key's identity: its
+/// [PersistedKey::type_name], [PersistedKey::type_id], group, store selector,
+/// and key hash are all forwarded, so renaming the wrapper or moving it within
+/// this crate never changes where values are stored. With the `serde`
+/// feature, this serializes as the inner key.
+///
+/// Because the persisted values have a different shape than the inner key's,
+/// don't persist the same key both directly and through a [TtlStore] in the
+/// same store.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct TtlKey<K>(pub K);
+
+impl<K: PersistedKey> PersistedKey for TtlKey<K> {
+    type Value = Expiring<K::Value>;
+
+    const TYPE_NAME: Option<&'static str> = K::TYPE_NAME;
+
+    fn type_name() -> &'static str {
+        K::type_name()
+    }
+
+    fn type_id() -> u64 {
+        K::type_id()
+    }
+
+    fn store_selector() -> crate::StoreSelector {
+        K::store_selector()
+    }
+
+    fn group() -> Option<&'static str> {
+        K::group()
+    }
+
+    fn key_hash(&self) -> Option<u64> {
+        self.0.key_hash()
+    }
+}
+
+/// A persisted value, with the time it was saved. See [TtlStore].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Expiring<V> {
+    /// The persisted value
+    pub value: V,
+    /// When the value was saved, in seconds since the Unix epoch
+    pub saved_at: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, thread_local};
+
+    #[derive(Clone)]
+    struct DismissedKey;
+
+    impl PersistedKey for DismissedKey {
+        type Value = bool;
+
+        fn ttl() -> Option<Duration> {
+            Some(Duration::from_secs(60))
+        }
+    }
+
+    struct Store;
+
+    thread_local! {
+        static VALUE: RefCell<Option<Expiring<bool>>> = RefCell::default();
+    }
+
+    impl PersistedStore<TtlKey<DismissedKey>> for Store {
+        fn load_persisted(_: &TtlKey<DismissedKey>) -> Option<Expiring<bool>> {
+            VALUE.with(|cell| cell.borrow().clone())
+        }
+
+        fn store_persisted(_: &TtlKey<DismissedKey>, value: &Expiring<bool>) {
+            VALUE.with(|cell| *cell.borrow_mut() = Some(value.clone()));
+        }
+    }
+
+    #[test]
+    fn test_ttl_store() {
+        type Ttl = TtlStore<Store>;
+
+        Ttl::store_persisted(&DismissedKey, &true);
+        assert_eq!(Ttl::load_persisted(&DismissedKey), Some(true));
+
+        // Pretend the value was saved two minutes ago
+        let mut expiring =
+            Store::load_persisted(&TtlKey(DismissedKey)).unwrap();
+        expiring.saved_at -= 120;
+        Store::store_persisted(&TtlKey(DismissedKey), &expiring);
+        assert_eq!(Ttl::load_persisted(&DismissedKey), None);
+    }
+
+    /// The wrapper is stored under the inner key's identity
+    #[test]
+    fn test_ttl_key_identity() {
+        assert_eq!(
+            TtlKey::<DismissedKey>::type_name(),
+            DismissedKey::type_name()
+        );
+        assert_eq!(TtlKey::<DismissedKey>::type_id(), DismissedKey::type_id());
+    }
+}
@@ -0,0 +1,10 @@
+//! Compile-time tests, to lock in guarantees enforced by the type system
+
+#[test]
+fn compile() {
+    let t = trybuild::TestCases::new();
+    // Wrappers must not be cloneable, to prevent duplicate keys. Derives
+    // must reject invalid input.
+    t.compile_fail("tests/ui/fail/*.rs");
+    t.pass("tests/ui/pass/*.rs");
+}
@@ -0,0 +1,275 @@
+//! Tests for derive macros
+
+use persisted::{
+    Persisted, PersistedContainer, PersistedKey, PersistedLazy, PersistedStore,
+};
+use std::cell::{Cell, RefCell};
+
+/// Persist a single integer
+#[derive(Default)]
+struct Store(Cell<Option<u8>>);
+
+impl Store {
+    thread_local! {
+        static INSTANCE: Store = Default::default();
+    }
+
+    fn set(value: u8) {
+        Self::INSTANCE.with(|store| store.0.set(Some(value)))
+    }
+}
+
+impl PersistedStore<ThemeKey> for Store {
+    fn load_persisted(_key: &ThemeKey) -> Option<u8> {
+        Self::INSTANCE.with(|store| store.0.get())
+    }
+
+    fn store_persisted(_key: &ThemeKey, value: &u8) {
+        Self::set(*value)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, PersistedContainer)]
+#[persisted(enum_repr = u8)]
+enum Theme {
+    #[default]
+    Light = 3,
+    Dark = 7,
+    HighContrast,
+}
+
+#[derive(Debug, PersistedKey)]
+#[persisted(u8)]
+struct ThemeKey;
+
+#[test]
+fn enum_repr() {
+    assert_eq!(u8::from(Theme::Light), 3);
+    assert_eq!(u8::from(Theme::HighContrast), 8);
+    assert_eq!(Theme::try_from(7), Ok(Theme::Dark));
+    assert_eq!(Theme::try_from(4), Err(4));
+
+    let mut theme = PersistedLazy::<Store, _, Theme>::new_default(ThemeKey);
+    *theme.get_mut() = Theme::HighContrast;
+    assert_eq!(Store::load_persisted(&ThemeKey), Some(8));
+
+    // Round trip
+    let theme = PersistedLazy::<Store, _, Theme>::new_default(ThemeKey);
+    assert_eq!(*theme, Theme::HighContrast);
+    assert!(theme.was_restored());
+
+    // Unknown discriminant falls back to the initial value
+    Store::set(100);
+    let theme = PersistedLazy::<Store, _, _>::new(ThemeKey, Theme::Dark);
+    assert_eq!(*theme, Theme::Dark);
+    assert!(!theme.was_restored());
+}
+
+/// Tuple struct, with a generic field that isn't persisted
+#[derive(Default, PersistedContainer)]
+struct Labeled<T>(Vec<T>, #[persist] u8);
+
+#[test]
+fn struct_field() {
+    Store::set(4);
+    let mut labeled = PersistedLazy::<Store, _, Labeled<&str>>::new(
+        ThemeKey,
+        Labeled(vec!["a"], 0),
+    );
+    assert!(labeled.was_restored());
+    assert_eq!(labeled.0, ["a"]);
+    assert_eq!(labeled.1, 4);
+
+    labeled.get_mut().1 = 5;
+    assert_eq!(Store::load_persisted(&ThemeKey), Some(5));
+}
+
+/// A single marked field is compared in place, and only cloned if changed
+#[test]
+fn struct_field_if_changed() {
+    #[derive(PartialEq)]
+    struct Counted(u8);
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            CLONE_COUNT.set(CLONE_COUNT.get() + 1);
+            Self(self.0)
+        }
+    }
+
+    thread_local! {
+        static CLONE_COUNT: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[derive(PersistedContainer)]
+    struct Wrapper(#[persist] Counted);
+
+    let wrapper = Wrapper(Counted(1));
+    assert!(wrapper.get_to_persist_if_changed(&Counted(1)).is_none());
+    assert_eq!(CLONE_COUNT.get(), 0);
+    assert_eq!(
+        wrapper.get_to_persist_if_changed(&Counted(2)).map(|c| c.0),
+        Some(1)
+    );
+    assert_eq!(CLONE_COUNT.get(), 1);
+}
+
+/// Multiple marked fields are persisted as a tuple
+#[test]
+fn struct_fields() {
+    #[derive(Debug, Default, PartialEq, PersistedContainer)]
+    struct Pair {
+        #[persist]
+        scroll: u32,
+        label: &'static str,
+        #[persist]
+        zoom: u8,
+    }
+
+    #[derive(Debug, Default, PartialEq, PersistedContainer)]
+    struct Triple(#[persist] bool, #[persist] u32, (), #[persist] char);
+
+    let mut pair = Pair {
+        scroll: 3,
+        label: "a",
+        zoom: 1,
+    };
+    assert_eq!(pair.get_to_persist(), (3, 1));
+    pair.restore_persisted((10, 2));
+    assert_eq!(
+        pair,
+        Pair {
+            scroll: 10,
+            label: "a",
+            zoom: 2
+        }
+    );
+
+    let mut triple = Triple::default();
+    assert_eq!(triple.get_to_persist(), (false, 0, '\0'));
+    triple.restore_persisted((true, 5, 'x'));
+    assert_eq!(triple, Triple(true, 5, (), 'x'));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn key_codec() {
+    use persisted::KeyCodec;
+
+    #[derive(KeyCodec, PersistedKey)]
+    #[persisted(u8)]
+    struct UnitKey;
+
+    #[derive(KeyCodec, PersistedKey)]
+    #[persisted(u8)]
+    struct NamedKey<'a> {
+        name: &'a str,
+        index: usize,
+    }
+
+    #[derive(KeyCodec, PersistedKey)]
+    #[persisted(u8)]
+    enum EnumKey {
+        Unit,
+        Tuple(u32, char),
+        Named { id: u32 },
+    }
+
+    assert_eq!(UnitKey.key_id(), "");
+    assert_eq!(
+        NamedKey {
+            name: "a",
+            index: 2
+        }
+        .key_id(),
+        "a/2"
+    );
+    assert_eq!(EnumKey::Unit.key_id(), "Unit");
+    assert_eq!(EnumKey::Tuple(3, 'x').key_id(), "Tuple/3/x");
+    assert_eq!(EnumKey::Named { id: 4 }.key_id(), "Named/4");
+    assert_eq!(UnitKey.storage_key(), (UnitKey::type_name(), String::new()));
+}
+
+#[test]
+fn transparent() {
+    #[derive(PersistedKey)]
+    #[persisted(u8, transparent)]
+    struct VolumeKey;
+
+    #[derive(PersistedKey)]
+    #[persisted(T, transparent)]
+    struct GenericKey<T>(T);
+
+    assert_eq!(VolumeKey::type_name(), "");
+    assert_eq!(VolumeKey::TYPE_NAME, Some(""));
+    assert_eq!(GenericKey::<u32>::type_name(), "");
+}
+
+#[test]
+fn group() {
+    use std::collections::HashMap;
+
+    #[derive(PersistedKey)]
+    #[persisted(u32, group = "window")]
+    struct WidthKey;
+
+    #[derive(PersistedKey)]
+    #[persisted(u32, group = "window")]
+    struct HeightKey;
+
+    #[derive(PersistedKey)]
+    #[persisted(u32)]
+    struct VolumeKey;
+
+    type Buckets = HashMap<Option<&'static str>, HashMap<&'static str, u32>>;
+
+    /// Bucket values by group, so a whole group can be cleared
+    struct GroupStore;
+
+    impl GroupStore {
+        thread_local! {
+            static BUCKETS: RefCell<Buckets> = RefCell::default();
+        }
+
+        fn clear_group(group: &'static str) {
+            Self::BUCKETS
+                .with(|buckets| buckets.borrow_mut().remove(&Some(group)));
+        }
+    }
+
+    impl<K: PersistedKey<Value = u32>> PersistedStore<K> for GroupStore {
+        fn load_persisted(_: &K) -> Option<u32> {
+            Self::BUCKETS.with(|buckets| {
+                buckets
+                    .borrow()
+                    .get(&K::group())?
+                    .get(K::type_name())
+                    .copied()
+            })
+        }
+
+        fn store_persisted(_: &K, value: &u32) {
+            Self::BUCKETS.with(|buckets| {
+                buckets
+                    .borrow_mut()
+                    .entry(K::group())
+                    .or_default()
+                    .insert(K::type_name(), *value);
+            });
+        }
+    }
+
+    assert_eq!(WidthKey::group(), Some("window"));
+    assert_eq!(VolumeKey::group(), None);
+
+    *Persisted::<GroupStore, _>::new(WidthKey, 0).get_mut() = 800;
+    *Persisted::<GroupStore, _>::new(HeightKey, 0).get_mut() = 600;
+    *Persisted::<GroupStore, _>::new(VolumeKey, 0).get_mut() = 80;
+    assert_eq!(GroupStore::load_persisted(&HeightKey), Some(600));
+
+    // Reset all window settings
+    GroupStore::clear_group("window");
+    assert_eq!(GroupStore::load_persisted(&WidthKey), None);
+    assert_eq!(GroupStore::load_persisted(&HeightKey), None);
+    assert_eq!(GroupStore::load_persisted(&VolumeKey), Some(80));
+}
@@ -3,10 +3,14 @@
 //! pattern is effective if you want to persist values between multiple life
 //! cycles of some subsection of your program, within the span of one process.
 
-use persisted::{Persisted, PersistedKey, PersistedStore};
+use persisted::{
+    FallbackStore, KeyRenames, NamedStore, Persisted, PersistedBuilder,
+    PersistedCopyCell, PersistedKey, PersistedStore, RemapStore, TeeStore,
+    WithDefault,
+};
 use std::{
-    cell::RefCell,
-    collections::HashMap,
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
     fmt::{self, Debug, Display},
     str::FromStr,
 };
@@ -34,17 +38,39 @@ where
     <K::Value as FromStr>::Err: Debug,
 {
     fn load_persisted(key: &K) -> Option<K::Value> {
+        Self::load_named(K::type_name(), key)
+    }
+
+    fn store_persisted(key: &K, value: &K::Value) {
+        Self::INSTANCE.with(|store| {
+            let mut map = store.0.borrow_mut();
+            map.insert((K::type_name(), key.to_string()), value.to_string());
+        })
+    }
+
+    fn remove_persisted(key: &K) {
+        Self::remove_named(K::type_name(), key);
+    }
+}
+
+impl<K> NamedStore<K> for Store
+where
+    K: Display + PersistedKey,
+    K::Value: Display + FromStr,
+    <K::Value as FromStr>::Err: Debug,
+{
+    fn load_named(type_name: &'static str, key: &K) -> Option<K::Value> {
         Self::INSTANCE.with(|store| {
             let map = store.0.borrow();
-            let value_str = map.get(&(K::type_name(), key.to_string()));
+            let value_str = map.get(&(type_name, key.to_string()));
             value_str.map(|value| value.parse().expect("Error parsing value"))
         })
     }
 
-    fn store_persisted(key: &K, value: &K::Value) {
+    fn remove_named(type_name: &'static str, key: &K) {
         Self::INSTANCE.with(|store| {
             let mut map = store.0.borrow_mut();
-            map.insert((K::type_name(), key.to_string()), value.to_string());
+            map.remove(&(type_name, key.to_string()));
         })
     }
 }
@@ -91,7 +117,7 @@ impl<T> SelectList<T> {
 /// Persist the selected value in the list by storing its index. This is simple
 /// but relies on the list keeping the same items, in the same order, between
 /// sessions.
-#[derive(PersistedKey)]
+#[derive(Default, PersistedKey)]
 #[persisted(usize)]
 struct SelectedIndexKey;
 
@@ -112,6 +138,45 @@ impl Display for ToggleKey {
     }
 }
 
+/// Default values aren't stored for this key
+#[derive(Debug, PersistedKey)]
+#[persisted(u32, skip_if_default)]
+struct CountKey;
+
+impl Display for CountKey {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// A key that borrows its name from elsewhere
+#[derive(Debug, PartialEq, Eq, Hash, PersistedKey)]
+#[persisted(u32)]
+struct NamedKey<'a>(&'a str);
+
+impl<'a> Display for NamedKey<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A key whose variants carry data
+#[derive(Debug, PersistedKey)]
+#[persisted(u32)]
+enum ScrollKey {
+    Tab(u32),
+    Global,
+}
+
+impl Display for ScrollKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tab(id) => write!(f, "tab/{id}"),
+            Self::Global => write!(f, "global"),
+        }
+    }
+}
+
 #[test]
 fn hashmap() {
     let make_list = || {
@@ -123,11 +188,738 @@ fn hashmap() {
     };
 
     let mut people = SelectList::new(make_list());
+    assert!(!people.selected_index.was_loaded());
+    assert!(!Store::contains(&SelectedIndexKey));
     *people.selected_index.get_mut() = 1;
-    *people.values[1].enabled.get_mut() = false;
+    let mut enabled = people.values[1].enabled.get_mut();
+    assert_eq!(enabled.key().0, PersonId(28833));
+    *enabled = false;
+    drop(enabled);
 
     let people = SelectList::new(make_list());
     // The previous values were restored
+    assert!(people.selected_index.was_loaded());
+    assert!(Store::contains(&SelectedIndexKey));
     assert_eq!(*people.selected_index, 1);
     assert!(!*people.values[1].enabled);
 }
+
+/// Lazy defaults are only computed when the store has no value
+#[test]
+fn new_with() {
+    let index = Persisted::<Store, _>::new_with(SelectedIndexKey, || 1);
+    assert_eq!(index.get(), 1);
+    index.persist();
+
+    let index = Persisted::<Store, _>::new_with(SelectedIndexKey, || {
+        panic!("Default should not be computed")
+    });
+    assert_eq!(index.get(), 1);
+}
+
+/// Deferred values aren't loaded until first access
+#[test]
+fn deferred() {
+    *Persisted::<Store, _>::new(SelectedIndexKey, 0).get_mut() = 2;
+
+    let mut index = Persisted::<Store, _>::new_deferred(SelectedIndexKey, 0);
+    assert_eq!(*index, 0);
+    assert!(!index.was_loaded());
+    index.ensure_loaded();
+    assert_eq!(*index, 2);
+    assert!(index.was_loaded());
+
+    // get_mut also triggers the load
+    let mut index = Persisted::<Store, _>::new_deferred(SelectedIndexKey, 0);
+    assert_eq!(*index.get_mut(), 2);
+}
+
+/// Each variant of an enum key, including its data, gets its own slot
+#[test]
+fn enum_key() {
+    *Persisted::<Store, _>::new(ScrollKey::Tab(1), 0).get_mut() = 10;
+    *Persisted::<Store, _>::new(ScrollKey::Tab(2), 0).get_mut() = 20;
+
+    assert_eq!(Persisted::<Store, _>::new(ScrollKey::Tab(1), 0).get(), 10);
+    assert_eq!(Persisted::<Store, _>::new(ScrollKey::Tab(2), 0).get(), 20);
+    assert_eq!(Persisted::<Store, _>::new(ScrollKey::Global, 0).get(), 0);
+}
+
+/// Guards can persist before they're dropped
+#[test]
+fn commit() {
+    let mut volume = Persisted::<Store, _>::new(NamedKey("commit"), 0);
+    let mut guard = volume.get_mut();
+    *guard = 3;
+    guard.commit();
+    assert_eq!(Store::load_persisted(&NamedKey("commit")), Some(3));
+    *guard = 4;
+    assert_eq!(Store::load_persisted(&NamedKey("commit")), Some(3));
+    drop(guard);
+    assert_eq!(Store::load_persisted(&NamedKey("commit")), Some(4));
+}
+
+/// new_loaded requires a value in the store
+#[test]
+fn new_loaded() {
+    assert!(Persisted::<Store, _>::new_loaded(NamedKey("new_loaded")).is_none());
+
+    *Persisted::<Store, _>::new(NamedKey("new_loaded"), 0).get_mut() = 4;
+    let value = Persisted::<Store, _>::new_loaded(NamedKey("new_loaded"))
+        .expect("Value should be loaded");
+    assert_eq!(value.get(), 4);
+    assert!(value.was_loaded());
+}
+
+/// Default values should be removed from the store instead of persisted
+#[test]
+fn skip_if_default() {
+    let mut count = Persisted::<Store, _>::new_default(CountKey);
+    // Writing the default doesn't store anything
+    *count.get_mut() = 0;
+    assert!(!Store::contains(&CountKey));
+
+    *count.get_mut() = 3;
+    assert_eq!(Store::load_persisted(&CountKey), Some(3));
+
+    // Resetting to the default removes the stored value
+    *count.get_mut() = 0;
+    assert!(!Store::contains(&CountKey));
+
+    // Conditional writes follow the same rules, and treat an absent value as
+    // the default
+    assert!(count.try_set_if(Some(&0), 5));
+    assert_eq!(Store::load_persisted(&CountKey), Some(5));
+    assert!(count.try_set_if(Some(&5), 0));
+    assert!(!Store::contains(&CountKey));
+}
+
+/// Explicit persistence doesn't require mutation
+#[test]
+fn persist() {
+    let index = Persisted::<Store, _>::new(SelectedIndexKey, 4);
+    assert_eq!(index.get(), 4);
+    assert_eq!(index.get_cloned(), 4);
+    assert_eq!(index.value(), &4);
+    assert_eq!(index.as_ref(), &4);
+    assert!(!Store::contains(&SelectedIndexKey));
+    index.persist();
+    assert_eq!(Store::load_persisted(&SelectedIndexKey), Some(4));
+
+    // The stored value takes precedence over the given one
+    let index = Persisted::<Store, SelectedIndexKey>::from_value(0);
+    assert_eq!(index.get(), 4);
+}
+
+/// Updates are applied to the latest stored value, not the in-memory value
+#[test]
+fn update_from_store() {
+    let mut first = Persisted::<Store, _>::new(SelectedIndexKey, 0);
+    let mut second = Persisted::<Store, _>::new(SelectedIndexKey, 0);
+    first.update_from_store(|index| *index += 1);
+    second.update_from_store(|index| *index += 1);
+    assert_eq!(second.get(), 2);
+    assert_eq!(Store::load_persisted(&SelectedIndexKey), Some(2));
+}
+
+/// Peeking reads the store without changing the in-memory value
+#[test]
+fn peek_stored() {
+    let mut first = Persisted::<Store, _>::new(ToggleKey(PersonId(3)), true);
+    let mut second = Persisted::<Store, _>::new(ToggleKey(PersonId(3)), true);
+    assert_eq!(second.peek_stored(), None);
+    assert!(!second.revert());
+    assert!(second.get());
+
+    *first.get_mut() = false;
+    assert_eq!(second.peek_stored(), Some(false));
+    assert!(second.get());
+    assert!(second.revert());
+    assert!(!second.get());
+}
+
+/// Manual values are only persisted when explicitly requested
+#[test]
+fn manual() {
+    let key = ToggleKey(PersonId(4));
+    let mut toggle = Persisted::<Store, _>::new_manual(key, false);
+    for _ in 0..3 {
+        let mut value = toggle.get_mut();
+        *value = !*value;
+    }
+    assert!(!Store::contains(&ToggleKey(PersonId(4))));
+    toggle.persist();
+    assert_eq!(Store::load_persisted(&ToggleKey(PersonId(4))), Some(true));
+}
+
+/// Swapped values are persisted under both keys
+#[test]
+fn swap() {
+    let mut first = Persisted::<Store, _>::new(ToggleKey(PersonId(1)), true);
+    let mut second = Persisted::<Store, _>::new(ToggleKey(PersonId(2)), false);
+    first.swap(&mut second);
+    assert!(!first.get());
+    assert!(second.get());
+
+    let first = Persisted::<Store, _>::new(ToggleKey(PersonId(1)), true);
+    let second = Persisted::<Store, _>::new(ToggleKey(PersonId(2)), false);
+    assert!(!first.get());
+    assert!(second.get());
+}
+
+/// Builder combines deferred loading with a persist callback
+#[test]
+fn builder() {
+    thread_local! {
+        static PERSISTED: std::cell::Cell<Option<usize>> = Default::default();
+    }
+
+    *Persisted::<Store, _>::new(SelectedIndexKey, 0).get_mut() = 2;
+
+    let mut index = Persisted::<Store, _>::builder(SelectedIndexKey)
+        .default(1)
+        .deferred()
+        .on_persist(|value| PERSISTED.with(|cell| cell.set(Some(*value))))
+        .build();
+    assert_eq!(index.get(), 1);
+    assert!(!index.was_loaded());
+    *index.get_mut() += 1;
+    assert_eq!(index.get(), 3);
+    assert_eq!(PERSISTED.with(|cell| cell.get()), Some(3));
+
+    // Without an explicit default, Default is used
+    let index = Persisted::<Store, _>::builder(SelectedIndexKey).build();
+    assert_eq!(index.get(), 3);
+
+    // Configured builders can be named and built later
+    let builder: PersistedBuilder<Store, SelectedIndexKey, WithDefault<usize>> =
+        Persisted::builder(SelectedIndexKey).default(0);
+    assert_eq!(builder.build().get(), 3);
+}
+
+/// Keys can borrow data that outlives the wrapper
+#[test]
+fn borrowed_key() {
+    let name = String::from("volume");
+    let mut volume = Persisted::<Store, _>::new(NamedKey(&name), 5);
+    *volume.get_mut() = 7;
+
+    let volume = Persisted::<Store, _>::new(NamedKey(&name), 5);
+    assert_eq!(volume.get(), 7);
+    // Lifetimes aren't included in the type name
+    assert!(NamedKey::type_name().ends_with("::NamedKey"));
+}
+
+/// Values stored under an old key name are migrated to the new name
+#[test]
+fn remap() {
+    struct OldVolumeKey;
+
+    impl PersistedKey for OldVolumeKey {
+        type Value = u32;
+        const TYPE_NAME: Option<&'static str> = Some("settings::Volume");
+    }
+
+    impl Display for OldVolumeKey {
+        fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+            Ok(())
+        }
+    }
+
+    struct VolumeKey;
+
+    impl PersistedKey for VolumeKey {
+        type Value = u32;
+        const TYPE_NAME: Option<&'static str> = Some("audio::Volume");
+    }
+
+    impl Display for VolumeKey {
+        fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+            Ok(())
+        }
+    }
+
+    struct Renames;
+
+    impl KeyRenames for Renames {
+        const RENAMES: &'static [(&'static str, &'static str)] =
+            &[("settings::Volume", "audio::Volume")];
+    }
+
+    type Remapped = RemapStore<Store, Renames>;
+
+    *Persisted::<Store, _>::new(OldVolumeKey, 50).get_mut() = 80;
+    let volume = Persisted::<Remapped, _>::new(VolumeKey, 50);
+    assert_eq!(volume.get(), 80);
+    // The value was moved to the new name
+    assert_eq!(Store::load_persisted(&VolumeKey), Some(80));
+    assert_eq!(Store::load_persisted(&OldVolumeKey), None);
+
+    // The new name takes precedence
+    *Persisted::<Store, _>::new(OldVolumeKey, 50).get_mut() = 20;
+    assert_eq!(Persisted::<Remapped, _>::new(VolumeKey, 50).get(), 80);
+}
+
+/// Debug output includes the key and value, but not the store
+#[test]
+fn debug() {
+    let mut volume = Persisted::<Store, _>::new(NamedKey("debug"), 5);
+    let debug = format!("{volume:?}");
+    assert!(debug.starts_with("Persisted { key: NamedKey(\"debug\"), value: 5"));
+    assert!(!debug.contains("PhantomData"));
+    let guard = volume.get_mut();
+    let debug = format!("{guard:?}");
+    assert!(debug.starts_with("PersistedRefMut { key: NamedKey(\"debug\")"));
+    assert!(!debug.contains("PhantomData"));
+}
+
+/// Wrappers are hashed and compared by key, ignoring their values
+#[test]
+fn key_identity() {
+    let volume = Persisted::<Store, _>::new(NamedKey("volume"), 5);
+    let mut diverged = Persisted::<Store, _>::new(NamedKey("volume"), 5);
+    *diverged.get_mut() = 8;
+    assert_eq!(volume, diverged);
+
+    let balance = Persisted::<Store, _>::new(NamedKey("balance"), 5);
+    assert_ne!(volume, balance);
+    let set: HashSet<_> = [volume, diverged, balance].into_iter().collect();
+    assert_eq!(set.len(), 2);
+}
+
+/// Live wrappers with equal keys are detected in debug builds
+#[test]
+#[cfg(all(debug_assertions, feature = "std"))]
+#[should_panic(expected = "Multiple live persisted wrappers")]
+fn key_collision() {
+    #[derive(Hash, PersistedKey)]
+    #[persisted(u32, detect_collisions)]
+    struct VolumeKey(u8);
+
+    impl Display for VolumeKey {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    let first = Persisted::<Store, _>::new(VolumeKey(1), 0);
+    // The key is released when the wrapper is dropped
+    drop(first);
+    let _first = Persisted::<Store, _>::new(VolumeKey(1), 0);
+    let _other = Persisted::<Store, _>::new(VolumeKey(2), 0);
+    let _second = Persisted::<Store, _>::new(VolumeKey(1), 0);
+}
+
+/// Wrappers can be dropped on a different thread than they were created on
+#[test]
+#[cfg(all(debug_assertions, feature = "std"))]
+fn key_collision_send() {
+    #[derive(Hash, PersistedKey)]
+    #[persisted(u32, detect_collisions)]
+    struct VolumeKey(u8);
+
+    impl Display for VolumeKey {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    let first = Persisted::<Store, _>::new(VolumeKey(1), 0);
+    std::thread::spawn(move || drop(first)).join().unwrap();
+    let _first = Persisted::<Store, _>::new(VolumeKey(1), 0);
+}
+
+/// Cells persist on every set, through a shared reference
+#[test]
+fn copy_cell() {
+    let cell = PersistedCopyCell::<Store, _>::new(NamedKey("copy_cell"), 1);
+    assert_eq!(cell.get(), 1);
+    let shared = &cell;
+    shared.set(2);
+    assert_eq!(cell.get(), 2);
+    assert_eq!(Store::load_persisted(&NamedKey("copy_cell")), Some(2));
+
+    let cell = PersistedCopyCell::<Store, _>::new(NamedKey("copy_cell"), 1);
+    assert_eq!(cell.into_inner(), 2);
+}
+
+/// Lenses expose part of the value, but persist the whole thing once
+#[test]
+fn lens() {
+    #[derive(Clone, Debug, Default, PartialEq)]
+    struct Settings {
+        audio: Audio,
+        name: String,
+    }
+
+    #[derive(Clone, Debug, Default, PartialEq)]
+    struct Audio {
+        volume: u32,
+    }
+
+    #[derive(PersistedKey)]
+    #[persisted(Settings)]
+    struct SettingsKey;
+
+    thread_local! {
+        static WRITES: RefCell<Vec<Settings>> = RefCell::default();
+    }
+
+    struct WriteStore;
+
+    impl PersistedStore<SettingsKey> for WriteStore {
+        fn load_persisted(_: &SettingsKey) -> Option<Settings> {
+            None
+        }
+
+        fn store_persisted(_: &SettingsKey, value: &Settings) {
+            WRITES.with(|writes| writes.borrow_mut().push(value.clone()));
+        }
+    }
+
+    let mut settings = Persisted::<WriteStore, _>::new_default(SettingsKey);
+    settings.get_mut().name = "Fred".into();
+    WRITES.with(|writes| writes.borrow_mut().clear());
+    {
+        let mut volume = settings.lens_mut(
+            |settings| &settings.audio.volume,
+            |settings| &mut settings.audio.volume,
+        );
+        *volume += 5;
+        *volume *= 2;
+        assert_eq!(*volume, 10);
+    }
+    assert_eq!(settings.audio.volume, 10);
+    WRITES.with(|writes| {
+        assert_eq!(
+            *writes.borrow(),
+            [Settings {
+                audio: Audio { volume: 10 },
+                name: "Fred".into(),
+            }]
+        )
+    });
+}
+
+/// Lens projections that select different parts of the value are caught
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "lens_mut projections")]
+fn lens_mut_mismatch() {
+    #[derive(Default)]
+    struct Range {
+        start: u32,
+        end: u32,
+    }
+
+    #[derive(PersistedKey)]
+    #[persisted(Range)]
+    struct RangeKey;
+
+    struct NullStore;
+
+    impl PersistedStore<RangeKey> for NullStore {
+        fn load_persisted(_: &RangeKey) -> Option<Range> {
+            None
+        }
+
+        fn store_persisted(_: &RangeKey, _: &Range) {}
+    }
+
+    let mut range = Persisted::<NullStore, _>::new_default(RangeKey);
+    let _guard = range.lens_mut(|range| &range.start, |range| &mut range.end);
+}
+
+/// Conditional writes only succeed if the store has the expected value
+#[test]
+fn try_set_if() {
+    let mut count = Persisted::<Store, _>::new(NamedKey("try_set_if"), 0);
+    assert!(count.try_set_if(None, 1));
+    assert_eq!(count.get(), 1);
+
+    // Another writer changes the stored value
+    Store::store_persisted(&NamedKey("try_set_if"), &5);
+    assert!(!count.try_set_if(Some(&1), 2));
+    assert_eq!(count.get(), 1);
+    assert_eq!(Store::load_persisted(&NamedKey("try_set_if")), Some(5));
+
+    // Pick up the other write, then try again
+    assert!(count.revert());
+    assert!(count.try_set_if(Some(&5), 6));
+    assert_eq!(Store::load_persisted(&NamedKey("try_set_if")), Some(6));
+}
+
+/// Diffing guards give the store the value from before the mutation
+#[test]
+fn get_mut_diff() {
+    #[derive(PersistedKey)]
+    #[persisted(u32)]
+    struct VolumeKey;
+
+    thread_local! {
+        static WRITES: RefCell<Vec<(Option<u32>, u32)>> = RefCell::default();
+    }
+
+    struct DiffStore;
+
+    impl PersistedStore<VolumeKey> for DiffStore {
+        fn load_persisted(_: &VolumeKey) -> Option<u32> {
+            None
+        }
+
+        fn store_persisted(_: &VolumeKey, _: &u32) {
+            unreachable!("Wrappers persist via store_persisted_diff")
+        }
+
+        fn store_persisted_diff(_: &VolumeKey, old: Option<&u32>, new: &u32) {
+            WRITES
+                .with(|writes| writes.borrow_mut().push((old.copied(), *new)));
+        }
+    }
+
+    let mut volume = Persisted::<DiffStore, _>::new(VolumeKey, 10);
+    *volume.get_mut_diff() = 20;
+    {
+        let mut guard = volume.get_mut_diff();
+        *guard = 30;
+        guard.commit();
+        *guard = 40;
+    }
+    // Plain guards don't snapshot
+    *volume.get_mut() = 50;
+    WRITES.with(|writes| {
+        assert_eq!(
+            *writes.borrow(),
+            [(Some(10), 20), (Some(20), 30), (Some(30), 40), (None, 50)]
+        )
+    });
+
+    // Cells always know the previous value, since it's Copy
+    WRITES.with(|writes| writes.borrow_mut().clear());
+    let cell = PersistedCopyCell::<DiffStore, _>::new(VolumeKey, 1);
+    cell.set(2);
+    WRITES.with(|writes| assert_eq!(*writes.borrow(), [(Some(1), 2)]));
+}
+
+/// Unpersisted mutations change the value without writing to the store
+#[test]
+fn as_mut_unpersisted() {
+    let mut count = Persisted::<Store, _>::new(NamedKey("unpersisted"), 0);
+    *count.get_mut() = 1;
+    *count.as_mut_unpersisted() = 2;
+    assert_eq!(count.get(), 2);
+    assert_eq!(Store::load_persisted(&NamedKey("unpersisted")), Some(1));
+}
+
+/// The key's type name is reachable from the wrapper
+#[test]
+fn type_name() {
+    let value = Persisted::<Store, _>::new(NamedKey("type_name"), 0);
+    assert_eq!(value.type_name(), NamedKey::type_name());
+}
+
+thread_local! {
+    static SWAPS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Forwards to [Store], but counts compare-and-swaps, to check that
+/// combinators forward them rather than falling back to load-then-store
+struct AtomicStore;
+
+impl<K> PersistedStore<K> for AtomicStore
+where
+    K: Display + PersistedKey,
+    K::Value: Display + FromStr,
+    <K::Value as FromStr>::Err: Debug,
+{
+    fn load_persisted(key: &K) -> Option<K::Value> {
+        Store::load_persisted(key)
+    }
+
+    fn store_persisted(key: &K, value: &K::Value) {
+        Store::store_persisted(key, value);
+    }
+
+    fn remove_persisted(key: &K) {
+        Store::remove_persisted(key);
+    }
+
+    fn compare_and_swap(
+        key: &K,
+        expected: Option<&K::Value>,
+        new: &K::Value,
+    ) -> bool
+    where
+        K::Value: PartialEq,
+    {
+        SWAPS.set(SWAPS.get() + 1);
+        Store::compare_and_swap(key, expected, new)
+    }
+}
+
+impl<K> NamedStore<K> for AtomicStore
+where
+    K: Display + PersistedKey,
+    K::Value: Display + FromStr,
+    <K::Value as FromStr>::Err: Debug,
+{
+    fn load_named(type_name: &'static str, key: &K) -> Option<K::Value> {
+        Store::load_named(type_name, key)
+    }
+
+    fn remove_named(type_name: &'static str, key: &K) {
+        Store::remove_named(type_name, key);
+    }
+}
+
+/// Combinators forward compare-and-swap to the inner store, so they don't
+/// lose its atomicity
+#[test]
+fn compare_and_swap_forwarding() {
+    struct NoRenames;
+
+    impl KeyRenames for NoRenames {
+        const RENAMES: &'static [(&'static str, &'static str)] = &[];
+    }
+
+    /// Read-only defaults
+    struct Defaults;
+
+    impl PersistedStore<NamedKey<'_>> for Defaults {
+        fn load_persisted(_: &NamedKey) -> Option<u32> {
+            Some(50)
+        }
+
+        fn store_persisted(_: &NamedKey, _: &u32) {
+            unreachable!("Defaults are read-only")
+        }
+    }
+
+    let key = NamedKey("cas_forwarding");
+    assert!(TeeStore::<AtomicStore, Store>::compare_and_swap(
+        &key, None, &1
+    ));
+    assert!(RemapStore::<AtomicStore, NoRenames>::compare_and_swap(
+        &key,
+        Some(&1),
+        &2
+    ));
+    assert!(FallbackStore::<AtomicStore, Defaults>::compare_and_swap(
+        &key,
+        Some(&2),
+        &3
+    ));
+    assert_eq!(SWAPS.get(), 3);
+    assert_eq!(Store::load_persisted(&key), Some(3));
+
+    // The fallback compares against the default if the primary store is empty
+    let key = NamedKey("cas_fallback");
+    type Fallback = FallbackStore<AtomicStore, Defaults>;
+    assert!(!Fallback::compare_and_swap(&key, None, &1));
+    assert!(Fallback::compare_and_swap(&key, Some(&50), &1));
+    assert_eq!(Store::load_persisted(&key), Some(1));
+}
+
+/// A tee with a cold first store compares against the second store, since
+/// that's what would be loaded
+#[test]
+fn compare_and_swap_tee_cold() {
+    /// An empty cache in front of [Store]
+    struct Cache;
+
+    thread_local! {
+        static CACHE: RefCell<HashMap<String, u32>> = RefCell::default();
+    }
+
+    impl PersistedStore<NamedKey<'_>> for Cache {
+        fn load_persisted(key: &NamedKey) -> Option<u32> {
+            CACHE.with(|cache| cache.borrow().get(key.0).copied())
+        }
+
+        fn store_persisted(key: &NamedKey, value: &u32) {
+            CACHE.with(|cache| cache.borrow_mut().insert(key.0.into(), *value));
+        }
+    }
+
+    type Tee = TeeStore<Cache, Store>;
+    let key = NamedKey("cas_tee_cold");
+    Store::store_persisted(&key, &5);
+    assert!(!Tee::compare_and_swap(&key, None, &6));
+    assert!(Tee::compare_and_swap(&key, Some(&5), &6));
+    assert_eq!(Cache::load_persisted(&key), Some(6));
+    assert_eq!(Store::load_persisted(&key), Some(6));
+    // Now the cache is authoritative
+    assert!(Tee::compare_and_swap(&key, Some(&6), &7));
+    assert_eq!(Store::load_persisted(&key), Some(7));
+}
+
+/// Combinators forward every store method, including the optional ones
+#[cfg(feature = "alloc")]
+#[test]
+fn combinator_forwarding() {
+    use persisted::{EitherStore, FallbackStore};
+
+    #[derive(Debug, PartialEq, PersistedKey)]
+    #[persisted(u32)]
+    struct IdKey(u32);
+
+    thread_local! {
+        static ERRORS: RefCell<Vec<u32>> = RefCell::default();
+    }
+
+    /// Contains the keys `1..=N`, and reports load errors as `N`
+    struct Listed<const N: u32>;
+
+    impl<const N: u32> PersistedStore<IdKey> for Listed<N> {
+        fn load_persisted(key: &IdKey) -> Option<u32> {
+            (key.0 <= N).then_some(key.0)
+        }
+
+        fn store_persisted(_: &IdKey, _: &u32) {}
+
+        fn keys() -> Vec<IdKey> {
+            (1..=N).map(IdKey).collect()
+        }
+
+        fn on_load_error(_: &IdKey) {
+            ERRORS.with(|errors| errors.borrow_mut().push(N));
+        }
+    }
+
+    type Tee = TeeStore<Listed<1>, Listed<2>>;
+    type Fallback = FallbackStore<Listed<1>, Listed<2>>;
+    type Either = EitherStore<Listed<1>, Listed<2>>;
+    assert_eq!(Tee::keys(), [IdKey(1), IdKey(2)]);
+    assert_eq!(Fallback::keys(), [IdKey(1), IdKey(2)]);
+    assert_eq!(Either::keys(), [IdKey(1)]);
+
+    // Errors go to the store that the value would be loaded from
+    Tee::on_load_error(&IdKey(1));
+    Tee::on_load_error(&IdKey(2));
+    Fallback::on_load_error(&IdKey(2));
+    Either::on_load_error(&IdKey(2));
+    ERRORS.with(|errors| assert_eq!(*errors.borrow(), [1, 2, 2, 1]));
+
+    #[cfg(feature = "log")]
+    {
+        type Logging = persisted::LoggingStore<Listed<2>>;
+        assert_eq!(Logging::keys(), [IdKey(1), IdKey(2)]);
+        Logging::on_load_error(&IdKey(1));
+        ERRORS.with(|errors| assert_eq!(*errors.borrow(), [1, 2, 2, 1, 2]));
+    }
+}
+
+persisted::delegate_store! {
+    /// Forward everything to [Store]
+    struct DelegateStore => Store;
+}
+
+/// Delegating stores share the inner store's values
+#[test]
+fn delegate_store() {
+    *Persisted::<DelegateStore, _>::new(NamedKey("delegate"), 1).get_mut() = 2;
+    assert_eq!(Store::load_persisted(&NamedKey("delegate")), Some(2));
+    assert!(DelegateStore::contains(&NamedKey("delegate")));
+    DelegateStore::remove_persisted(&NamedKey("delegate"));
+    assert_eq!(Store::load_persisted(&NamedKey("delegate")), None);
+}
@@ -1,27 +1,27 @@
 //! Lazy persistence allows storing and loading values when some amount of work
 //! needs to be done to retrieve and/or restore the value on the data side. In
-//! this example, the persisted value is the selected index in a list. Rather
-//! than persisting the index directly, we can define `SelectList` as a
-//! [PersistedContainer], then persist the entire container. The ID of the
-//! selected item the only thing stored in the database; this is merely a
-//! convenience to change where in our data tree we declare the persistence.
-//!
-//! This is useful when you have a generic container (in this case
-//! `SelectList`) that may be used multiple times with different persistence
-//! keys (or not persisted at all in some cases).
+//! these tests, the persisted value is the selected item in a list. Rather
+//! than persisting the index directly, the list is an [IdSelect], which
+//! persists the ID of the selected item and finds it again on restore.
 
 use persisted::{
-    PersistedContainer, PersistedKey, PersistedLazy, PersistedStore,
+    IdSelect, Persisted, PersistedContainer, PersistedContainerCtx,
+    PersistedKey, PersistedLazy, PersistedLazyCtx, PersistedStore,
 };
 use std::{
-    cell::Cell,
+    cell::{Cell, RefCell},
+    collections::HashMap,
     sync::atomic::{AtomicUsize, Ordering},
 };
 
-/// Persist just the stored ID
+/// Persist just the stored ID. Lists are never empty in these tests, so the
+/// selected ID is never `None`.
 #[derive(Default)]
 struct Store {
     id: Cell<Option<PersonId>>,
+    /// Previous value passed with the most recent write
+    old_id: Cell<Option<PersonId>>,
+    load_count: AtomicUsize,
     save_count: AtomicUsize,
 }
 
@@ -30,23 +30,73 @@ impl Store {
         static INSTANCE: Store = Default::default();
     }
 
+    fn load_count() -> usize {
+        Self::INSTANCE.with(|store| store.load_count.load(Ordering::Relaxed))
+    }
+
     fn save_count() -> usize {
-        Self::INSTANCE
-            .with(|store| store.save_count.load(Ordering::Relaxed).into())
+        Self::INSTANCE.with(|store| store.save_count.load(Ordering::Relaxed))
+    }
+
+    fn old_id() -> Option<PersonId> {
+        Self::INSTANCE.with(|store| store.old_id.get())
     }
 }
 
 impl PersistedStore<SelectedIdKey> for Store {
-    fn load_persisted(_key: &SelectedIdKey) -> Option<PersonId> {
-        Self::INSTANCE.with(|store| store.id.get())
+    fn load_persisted(_key: &SelectedIdKey) -> Option<Option<PersonId>> {
+        Self::INSTANCE.with(|store| {
+            store.load_count.fetch_add(1, Ordering::Relaxed);
+            store.id.get().map(Some)
+        })
     }
 
-    fn store_persisted(_key: &SelectedIdKey, value: &PersonId) {
+    fn store_persisted(_key: &SelectedIdKey, value: &Option<PersonId>) {
         Self::INSTANCE.with(|store| {
-            store.id.set(Some(*value));
+            store.id.set(*value);
             store.save_count.fetch_add(1, Ordering::Relaxed);
         })
     }
+
+    fn store_persisted_diff(
+        key: &SelectedIdKey,
+        old: Option<&Option<PersonId>>,
+        new: &Option<PersonId>,
+    ) {
+        Self::INSTANCE.with(|store| store.old_id.set(old.copied().flatten()));
+        Self::store_persisted(key, new);
+    }
+}
+
+/// Persist selected IDs for multiple lists
+#[derive(Default)]
+struct MapStore(RefCell<HashMap<u32, PersonId>>);
+
+impl MapStore {
+    thread_local! {
+        static INSTANCE: MapStore = Default::default();
+    }
+
+    fn get(list: u32) -> Option<PersonId> {
+        Self::INSTANCE.with(|store| store.0.borrow().get(&list).copied())
+    }
+}
+
+impl PersistedStore<ListKey> for MapStore {
+    fn load_persisted(key: &ListKey) -> Option<Option<PersonId>> {
+        Self::get(key.0).map(Some)
+    }
+
+    fn store_persisted(key: &ListKey, value: &Option<PersonId>) {
+        if let Some(id) = value {
+            Self::INSTANCE
+                .with(|store| store.0.borrow_mut().insert(key.0, *id));
+        }
+    }
+
+    fn remove_persisted(key: &ListKey) {
+        Self::INSTANCE.with(|store| store.0.borrow_mut().remove(&key.0));
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -61,38 +111,19 @@ struct Person {
 }
 
 #[derive(Debug, PersistedKey)]
-#[persisted(PersonId)]
+#[persisted(Option<PersonId>)]
 struct SelectedIdKey;
 
-/// A list of items, with one item selected
-struct SelectList {
-    values: Vec<Person>,
-    selected_index: usize,
-}
+/// Selected ID for one of many lists
+#[derive(Debug, PartialEq, PersistedKey)]
+#[persisted(Option<PersonId>)]
+struct ListKey(u32);
 
-impl SelectList {
-    fn selected(&self) -> &Person {
-        &self.values[self.selected_index]
-    }
-}
-
-impl PersistedContainer for SelectList {
-    type Value = PersonId;
-
-    fn get_to_persist(&self) -> Self::Value {
-        self.selected().id
-    }
+/// A list of people, with one selected by ID
+type SelectList = IdSelect<Person, PersonId>;
 
-    fn restore_persisted(&mut self, value: Self::Value) {
-        // Find selected person by ID
-        self.selected_index = self
-            .values
-            .iter()
-            .enumerate()
-            .find(|(_, person)| person.id == value)
-            .map(|(i, _)| i)
-            .unwrap_or_default();
-    }
+fn select_list(people: Vec<Person>) -> SelectList {
+    IdSelect::new(people, |person| person.id)
 }
 
 #[test]
@@ -117,29 +148,520 @@ fn lazy() {
 
     let mut people = PersistedLazy::<Store, _, _>::new(
         SelectedIdKey,
-        SelectList {
-            values: person_list.clone(),
-            selected_index: 0,
-        },
+        select_list(person_list.clone()),
     );
     assert_eq!(Store::save_count(), 0);
-    people.get_mut().selected_index = 1;
+    assert!(!people.was_restored());
+    assert_eq!(people.last_persisted(), None);
+    people.get_mut().select(1);
     assert_eq!(Store::save_count(), 1);
+    assert_eq!(people.last_persisted(), Some(&Some(PersonId(28833))));
 
     // Store should only be called if the persisted value actually changed
-    people.get_mut().selected_index = 1;
+    people.get_mut().select(1);
     assert_eq!(Store::save_count(), 1);
-    people.get_mut().selected_index = 2;
+    people.get_mut().select(2);
     assert_eq!(Store::save_count(), 2);
+    // The store is given the previous value along with the new one
+    assert_eq!(Store::old_id(), Some(PersonId(28833)));
+
+    // Setting by persisted value only writes when it changes
+    people.set_persisted(Some(PersonId(93383)));
+    assert_eq!(people.selected_index(), 2);
+    assert_eq!(Store::save_count(), 2);
+    people.set_persisted(Some(PersonId(23089)));
+    assert_eq!(people.selected_index(), 0);
+    assert_eq!(Store::save_count(), 3);
+    people.set_persisted(Some(PersonId(93383)));
+    assert_eq!(Store::save_count(), 4);
+
+    // Unpersisted mutations don't write
+    let reordered = people.values().iter().cloned().rev().collect();
+    people.container_mut().set_values(reordered);
+    assert_eq!(Store::save_count(), 4);
+
+    // Explicit persistence always writes
+    people.persist();
+    assert_eq!(Store::save_count(), 5);
 
     // The previous value gets restored
-    let people = PersistedLazy::<Store, _, _>::new(
+    let mut people = PersistedLazy::<Store, _, _>::new(
         SelectedIdKey,
-        SelectList {
-            values: person_list,
-            selected_index: 0,
+        select_list(person_list),
+    );
+    assert!(people.was_restored());
+    assert_eq!(people.container().selected_index(), 2);
+    assert_eq!(Store::save_count(), 5);
+
+    // The loaded value is the baseline for the next write
+    assert_eq!(people.last_persisted(), Some(&Some(PersonId(93383))));
+    drop(people.get_mut());
+    assert_eq!(Store::save_count(), 5);
+    people.get_mut().select(0);
+    assert_eq!(Store::save_count(), 6);
+    assert_eq!(Store::old_id(), Some(PersonId(93383)));
+}
+
+/// Deferred containers aren't loaded until first mutable access
+#[test]
+fn deferred() {
+    let list = || {
+        select_list(vec![
+            Person {
+                id: PersonId(23089),
+                name: "Fred".into(),
+                age: 17,
+            },
+            Person {
+                id: PersonId(28833),
+                name: "Susan".into(),
+                age: 29,
+            },
+        ])
+    };
+    PersistedLazy::<Store, _, _>::new(SelectedIdKey, list())
+        .get_mut()
+        .select(1);
+    let load_count = Store::load_count();
+
+    let mut people =
+        PersistedLazy::<Store, _, _>::new_deferred(SelectedIdKey, list());
+    // Immutable access sees the constructed state
+    assert_eq!(people.selected_index(), 0);
+    assert!(!people.was_restored());
+    assert_eq!(Store::load_count(), load_count);
+
+    // First mutable access loads, exactly once
+    assert_eq!(people.get_mut().selected_index(), 1);
+    assert!(people.was_restored());
+    assert_eq!(Store::load_count(), load_count + 1);
+    people.ensure_loaded();
+    people.get_mut().select(0);
+    assert_eq!(Store::load_count(), load_count + 1);
+}
+
+/// Replacing the container should retain the persisted selection
+#[test]
+fn replace_container() {
+    let person_list = vec![
+        Person {
+            id: PersonId(23089),
+            name: "Fred".into(),
+            age: 17,
+        },
+        Person {
+            id: PersonId(28833),
+            name: "Susan".into(),
+            age: 29,
         },
+    ];
+
+    let mut people = PersistedLazy::<Store, _, _>::new(
+        SelectedIdKey,
+        select_list(person_list.clone()),
     );
-    assert_eq!(people.selected_index, 2);
-    assert_eq!(Store::save_count(), 2);
+    people.get_mut().select(1);
+
+    // Susan moved to the front of the list
+    let mut reordered = select_list(person_list.into_iter().rev().collect());
+    reordered.select(1);
+    let old = people.replace_container(reordered);
+    assert_eq!(old.selected_index(), 1);
+    assert_eq!(people.selected_index(), 0);
+    assert_eq!(people.selected().unwrap().id, PersonId(28833));
+    assert_eq!(Store::save_count(), 1);
+}
+
+/// Rekeying moves the persisted value to the new key
+#[test]
+fn rekey() {
+    let mut people = PersistedLazy::<MapStore, _, _>::new(
+        ListKey(1),
+        select_list(vec![
+            Person {
+                id: PersonId(23089),
+                name: "Fred".into(),
+                age: 17,
+            },
+            Person {
+                id: PersonId(28833),
+                name: "Susan".into(),
+                age: 29,
+            },
+        ]),
+    );
+    people.get_mut().select(1);
+    assert_eq!(MapStore::get(1), Some(PersonId(28833)));
+
+    let old_key = people.rekey(ListKey(2));
+    assert_eq!(old_key, ListKey(1));
+    assert_eq!(MapStore::get(1), None);
+    assert_eq!(MapStore::get(2), Some(PersonId(28833)));
+
+    // Subsequent writes go to the new key
+    let mut guard = people.get_mut();
+    assert_eq!(guard.key(), &ListKey(2));
+    guard.select(0);
+    drop(guard);
+    assert_eq!(MapStore::get(1), None);
+    assert_eq!(MapStore::get(2), Some(PersonId(23089)));
+
+    // Rekeying to the same key keeps the value
+    people.rekey(ListKey(2));
+    assert_eq!(MapStore::get(2), Some(PersonId(23089)));
+}
+
+/// Snapshots prime change detection without loading
+#[test]
+fn from_snapshot() {
+    let people = || {
+        select_list(vec![
+            Person {
+                id: PersonId(23089),
+                name: "Fred".into(),
+                age: 17,
+            },
+            Person {
+                id: PersonId(28833),
+                name: "Susan".into(),
+                age: 29,
+            },
+        ])
+    };
+
+    let mut list = PersistedLazy::<MapStore, _, _>::from_snapshot(
+        ListKey(3),
+        people(),
+        Some(Some(PersonId(28833))),
+    );
+    assert!(list.was_restored());
+    assert_eq!(list.selected_index(), 1);
+    assert_eq!(list.last_persisted(), Some(&Some(PersonId(28833))));
+
+    // No-op mutation doesn't write
+    list.get_mut().select(1);
+    assert_eq!(MapStore::get(3), None);
+    list.get_mut().select(0);
+    assert_eq!(MapStore::get(3), Some(PersonId(23089)));
+
+    // A rejected snapshot isn't used as the baseline
+    let mut lazy = PersistedLazy::<MapStore, _, _>::from_snapshot(
+        ListKey(10),
+        Bounded(1),
+        Some(Some(PersonId(50))),
+    );
+    assert!(!lazy.was_restored());
+    assert_eq!(lazy.last_persisted(), None);
+    assert!(lazy.flush());
+    assert_eq!(MapStore::get(10), Some(PersonId(1)));
+}
+
+/// Converting between eager and lazy doesn't access the store
+#[test]
+fn eager_lazy_conversion() {
+    let people = select_list(vec![
+        Person {
+            id: PersonId(23089),
+            name: "Fred".into(),
+            age: 17,
+        },
+        Person {
+            id: PersonId(28833),
+            name: "Susan".into(),
+            age: 29,
+        },
+    ]);
+
+    let selected =
+        Persisted::<MapStore, _>::new(ListKey(4), Some(PersonId(28833)));
+    let mut lazy = selected.into_lazy(people);
+    assert_eq!(lazy.selected_index(), 1);
+    assert_eq!(lazy.last_persisted(), Some(&Some(PersonId(28833))));
+    lazy.get_mut().select(0);
+    assert_eq!(MapStore::get(4), Some(PersonId(23089)));
+
+    let selected = lazy.into_eager();
+    assert_eq!(selected.get(), Some(PersonId(23089)));
+}
+
+/// Modifying reports whether the value was persisted
+#[test]
+fn modify() {
+    let mut list = PersistedLazy::<MapStore, _, _>::new(
+        ListKey(5),
+        select_list(vec![
+            Person {
+                id: PersonId(23089),
+                name: "Fred".into(),
+                age: 17,
+            },
+            Person {
+                id: PersonId(28833),
+                name: "Susan".into(),
+                age: 29,
+            },
+        ]),
+    );
+    assert!(!list.is_dirty_since_load());
+    assert!(list.modify(|list| {
+        list.select(1);
+    }));
+    assert!(list.is_dirty_since_load());
+    list.mark_clean();
+    assert_eq!(MapStore::get(5), Some(PersonId(28833)));
+    assert!(!list.modify(|list| {
+        list.select(1);
+    }));
+    // Unpersisted parts of the container don't count as a change
+    assert!(!list.modify(|list| {
+        list.set_values(list.values().iter().cloned().rev().collect())
+    }));
+    assert!(!list.is_dirty_since_load());
+
+    // Committing updates the baseline, so unchanged values aren't rewritten
+    let mut guard = list.get_mut();
+    // The list was reversed above, so Fred is now second
+    guard.select(1);
+    assert!(guard.commit());
+    assert_eq!(MapStore::get(5), Some(PersonId(23089)));
+    assert!(!guard.commit());
+}
+
+/// An index into a list that lives outside the container
+#[derive(Default)]
+struct Selection(usize);
+
+impl PersistedContainerCtx for Selection {
+    type Value = Option<PersonId>;
+    type Context = [Person];
+
+    fn get_to_persist(&self, people: &[Person]) -> Self::Value {
+        people.get(self.0).map(|person| person.id)
+    }
+
+    fn restore_persisted(&mut self, people: &[Person], value: Self::Value) {
+        self.0 = people
+            .iter()
+            .position(|person| Some(person.id) == value)
+            .unwrap_or_default();
+    }
+}
+
+/// Context is passed through to the container when loading and persisting
+#[test]
+fn context() {
+    let people = [
+        Person {
+            id: PersonId(23089),
+            name: "Fred".into(),
+            age: 17,
+        },
+        Person {
+            id: PersonId(28833),
+            name: "Susan".into(),
+            age: 29,
+        },
+    ];
+
+    let mut selection = PersistedLazyCtx::<MapStore, _, Selection>::new_default(
+        ListKey(6),
+        &people,
+    );
+    assert_eq!(selection.0, 0);
+    selection.get_mut(&people).0 = 1;
+    assert_eq!(MapStore::get(6), Some(PersonId(28833)));
+    assert_eq!(selection.last_persisted(), Some(&Some(PersonId(28833))));
+
+    // Restored via the context, which has been reordered
+    let people = [people[1].clone(), people[0].clone()];
+    let selection = PersistedLazyCtx::<MapStore, _, Selection>::new_default(
+        ListKey(6),
+        &people,
+    );
+    assert_eq!(selection.0, 0);
+}
+
+thread_local! {
+    static DERIVE_COUNT: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A container whose persisted value is expensive to derive
+#[derive(Clone, PartialEq)]
+struct Expensive(u64);
+
+impl PersistedContainer for Expensive {
+    type Value = Option<PersonId>;
+
+    fn get_to_persist(&self) -> Self::Value {
+        DERIVE_COUNT.set(DERIVE_COUNT.get() + 1);
+        Some(PersonId(self.0))
+    }
+
+    fn restore_persisted(&mut self, value: Self::Value) {
+        if let Some(id) = value {
+            self.0 = id.0;
+        }
+    }
+}
+
+/// Comparing containers skips deriving the value for no-op edits
+#[test]
+fn container_eq() {
+    let mut lazy =
+        PersistedLazy::<MapStore, _, _>::new(ListKey(6), Expensive(1));
+    lazy.get_mut_by_container_eq().0 = 1;
+    assert_eq!(DERIVE_COUNT.get(), 0);
+    assert_eq!(MapStore::get(6), None);
+
+    lazy.get_mut_by_container_eq().0 = 2;
+    assert_eq!(DERIVE_COUNT.get(), 1);
+    assert_eq!(MapStore::get(6), Some(PersonId(2)));
+    assert!(lazy.is_dirty_since_load());
+
+    // Committing updates the snapshot, so dropping doesn't persist again
+    let mut guard = lazy.get_mut_by_container_eq();
+    guard.0 = 3;
+    assert!(guard.commit());
+    assert_eq!(MapStore::get(6), Some(PersonId(3)));
+    assert!(!guard.commit());
+    drop(guard);
+    assert_eq!(DERIVE_COUNT.get(), 2);
+}
+
+/// Flushing persists changes made outside a guard, once
+#[test]
+fn flush() {
+    let mut lazy =
+        PersistedLazy::<MapStore, _, _>::new(ListKey(7), Expensive(1));
+    // Nothing has been persisted yet, so the initial value counts as a change
+    assert!(lazy.flush());
+    assert_eq!(MapStore::get(7), Some(PersonId(1)));
+    assert!(!lazy.flush());
+
+    lazy.container_mut().0 = 2;
+    assert_eq!(MapStore::get(7), Some(PersonId(1)));
+    assert!(lazy.flush());
+    assert_eq!(MapStore::get(7), Some(PersonId(2)));
+
+    // Unchanged, so nothing is written
+    MapStore::INSTANCE.with(|store| store.0.borrow_mut().clear());
+    assert!(!lazy.flush());
+    assert_eq!(MapStore::get(7), None);
+}
+
+/// Restoring picks up values written by another component
+#[test]
+fn restore_latest() {
+    let mut lazy =
+        PersistedLazy::<MapStore, _, _>::new(ListKey(8), Expensive(1));
+    assert!(!lazy.restore_latest());
+    assert_eq!(lazy.0, 1);
+
+    // Another view changes the selection
+    MapStore::store_persisted(&ListKey(8), &Some(PersonId(3)));
+    assert!(lazy.restore_latest());
+    assert_eq!(lazy.0, 3);
+    assert_eq!(lazy.last_persisted(), Some(&Some(PersonId(3))));
+
+    // The restored value is the baseline, so it isn't written back
+    MapStore::INSTANCE.with(|store| store.0.borrow_mut().clear());
+    assert!(!lazy.flush());
+    assert_eq!(MapStore::get(8), None);
+
+    // A rejected value doesn't become the baseline
+    let mut lazy = PersistedLazy::<MapStore, _, _>::new(ListKey(9), Bounded(1));
+    assert!(lazy.flush());
+    MapStore::store_persisted(&ListKey(9), &Some(PersonId(50)));
+    assert!(!lazy.restore_latest());
+    assert_eq!(lazy.0, 1);
+    assert_eq!(lazy.last_persisted(), Some(&Some(PersonId(1))));
+}
+
+/// A container that rejects IDs above 10
+struct Bounded(u64);
+
+impl PersistedContainer for Bounded {
+    type Value = Option<PersonId>;
+
+    fn get_to_persist(&self) -> Self::Value {
+        Some(PersonId(self.0))
+    }
+
+    fn restore_persisted(&mut self, value: Self::Value) {
+        if let Some(id) = value {
+            self.0 = id.0;
+        }
+    }
+
+    fn try_restore_persisted(&mut self, value: Self::Value) -> bool {
+        let accepted = value.is_some_and(|id| id.0 <= 10);
+        if accepted {
+            self.restore_persisted(value);
+        }
+        accepted
+    }
+}
+
+/// Stored values are converted before restoring, and skipped if invalid
+#[test]
+fn try_from_container() {
+    /// A container that only accepts small IDs
+    #[derive(Default)]
+    struct SmallId(u8);
+
+    impl PersistedContainer for SmallId {
+        type Value = u8;
+
+        fn get_to_persist(&self) -> Self::Value {
+            self.0
+        }
+
+        fn restore_persisted(&mut self, value: Self::Value) {
+            self.0 = value;
+        }
+    }
+
+    #[derive(PersistedKey)]
+    #[persisted(u64)]
+    struct SmallIdKey;
+
+    struct IdStore;
+
+    impl IdStore {
+        thread_local! {
+            static VALUE: Cell<Option<u64>> = const { Cell::new(None) };
+        }
+    }
+
+    impl PersistedStore<SmallIdKey> for IdStore {
+        fn load_persisted(_: &SmallIdKey) -> Option<u64> {
+            Self::VALUE.get()
+        }
+
+        fn store_persisted(_: &SmallIdKey, value: &u64) {
+            Self::VALUE.set(Some(*value));
+        }
+    }
+
+    type Container = persisted::TryFromContainer<SmallId, u64>;
+
+    IdStore::VALUE.set(Some(300));
+    let lazy = PersistedLazy::<IdStore, _, Container>::new_default(SmallIdKey);
+    assert!(!lazy.was_restored());
+    assert_eq!(lazy.0, 0);
+
+    IdStore::VALUE.set(Some(3));
+    let mut lazy =
+        PersistedLazy::<IdStore, _, Container>::new_default(SmallIdKey);
+    assert!(lazy.was_restored());
+    assert_eq!(lazy.0, 3);
+    lazy.get_mut().0 = 4;
+    assert_eq!(IdStore::VALUE.get(), Some(4));
+}
+
+/// The key's type name is reachable from the wrapper
+#[test]
+fn type_name() {
+    let lazy = PersistedLazy::<MapStore, _, _>::new(ListKey(9), Expensive(1));
+    assert_eq!(lazy.type_name(), ListKey::type_name());
 }
@@ -0,0 +1,71 @@
+//! Tests for globally disabling persistence. The flag is process-global, so
+//! these live in their own test binary to avoid interfering with other tests.
+
+use persisted::{
+    Persisted, PersistedContainer, PersistedKey, PersistedLazy, PersistedStore,
+};
+use std::cell::Cell;
+
+#[derive(PersistedKey)]
+#[persisted(u32)]
+struct CountKey;
+
+struct Store;
+
+thread_local! {
+    static VALUE: Cell<Option<u32>> = const { Cell::new(None) };
+}
+
+impl PersistedStore<CountKey> for Store {
+    fn load_persisted(_: &CountKey) -> Option<u32> {
+        VALUE.get()
+    }
+
+    fn store_persisted(_: &CountKey, value: &u32) {
+        VALUE.set(Some(*value));
+    }
+}
+
+#[derive(Default)]
+struct Count(u32);
+
+impl PersistedContainer for Count {
+    type Value = u32;
+
+    fn get_to_persist(&self) -> Self::Value {
+        self.0
+    }
+
+    fn restore_persisted(&mut self, value: Self::Value) {
+        self.0 = value;
+    }
+}
+
+/// Changes made while disabled aren't reported as written, and are written
+/// once persistence is re-enabled
+#[test]
+fn persistence_disabled() {
+    {
+        let mut lazy = PersistedLazy::<Store, _, Count>::new_default(CountKey);
+        persisted::set_persistence_enabled(false);
+        assert!(!lazy.modify(|count| count.0 = 1));
+        assert!(!lazy.flush());
+        assert!(!lazy.is_dirty_since_load());
+        assert_eq!(lazy.last_persisted(), None);
+        assert_eq!(VALUE.get(), None);
+
+        persisted::set_persistence_enabled(true);
+        assert!(lazy.flush());
+        assert!(lazy.is_dirty_since_load());
+        assert_eq!(VALUE.get(), Some(1));
+    }
+
+    // Eager values write on every mutation, regardless of what was skipped
+    let mut eager = Persisted::<Store, _>::new(CountKey, 0);
+    persisted::set_persistence_enabled(false);
+    *eager.get_mut() = 2;
+    assert_eq!(VALUE.get(), Some(1));
+    persisted::set_persistence_enabled(true);
+    *eager.get_mut() += 1;
+    assert_eq!(VALUE.get(), Some(3));
+}
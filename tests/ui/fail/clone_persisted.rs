@@ -0,0 +1,16 @@
+use persisted::{Persisted, PersistedKey};
+
+#[path = "../store.rs"]
+mod store;
+
+#[derive(Clone, PersistedKey)]
+#[persisted(u32)]
+struct Key;
+
+fn assert_clone<T: Clone>() {}
+
+fn main() {
+    // Note: `persisted.clone()` *would* compile, because it derefs to the
+    // inner value's `Clone` impl. Check the wrapper type directly.
+    assert_clone::<Persisted<store::Store, Key>>();
+}
@@ -0,0 +1,16 @@
+use persisted::{PersistedKey, PersistedLazy};
+
+#[path = "../store.rs"]
+mod store;
+
+#[derive(Clone, PersistedKey)]
+#[persisted(Option<u32>)]
+struct Key;
+
+fn assert_clone<T: Clone>() {}
+
+fn main() {
+    // Note: `persisted.clone()` *would* compile, because it derefs to the
+    // container's `Clone` impl. Check the wrapper type directly.
+    assert_clone::<PersistedLazy<store::Store, Key, Option<u32>>>();
+}
@@ -0,0 +1,8 @@
+use persisted::PersistedContainer;
+
+#[derive(PersistedContainer)]
+struct Unmarked {
+    index: usize,
+}
+
+fn main() {}
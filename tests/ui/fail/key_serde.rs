@@ -0,0 +1,9 @@
+use persisted::PersistedKey;
+
+struct NotSerde;
+
+#[derive(PersistedKey)]
+#[persisted(NotSerde, serde)]
+struct Key;
+
+fn main() {}
@@ -0,0 +1,13 @@
+use persisted::PersistedKey;
+
+#[derive(PersistedKey)]
+#[persisted(value = 1)]
+struct TupleKey(u32);
+
+#[derive(PersistedKey)]
+#[persisted(value = id)]
+struct NamedKey {
+    name: String,
+}
+
+fn main() {}
@@ -0,0 +1,25 @@
+#![deny(unused_must_use)]
+
+use persisted::{Persisted, PersistedKey, PersistedLazy};
+
+#[path = "../store.rs"]
+mod store;
+
+use store::Store;
+
+#[derive(PersistedKey)]
+#[persisted(u32)]
+struct CountKey;
+
+#[derive(PersistedKey)]
+#[persisted(Option<u32>)]
+struct SelectedKey;
+
+fn main() {
+    let mut count = Persisted::<Store, _>::new(CountKey, 0);
+    count.get_mut();
+
+    let mut selected =
+        PersistedLazy::<Store, _, Option<u32>>::new_default(SelectedKey);
+    selected.get_mut();
+}
@@ -0,0 +1,50 @@
+use persisted::{Persisted, PersistedKey, PersistedLazy, SingletonKey};
+
+#[path = "../store.rs"]
+mod store;
+
+use store::Store;
+
+#[derive(PersistedKey)]
+#[persisted(u32)]
+struct CountKey;
+
+#[derive(PersistedKey)]
+#[persisted(Option<u32>)]
+struct SelectedKey(u64);
+
+fn main() {
+    let mut count = Persisted::<Store, _>::new(CountKey, 0);
+    *count.get_mut() += 1;
+    let _: &u32 = &count;
+
+    let _ = Persisted::<Store, _>::new_default(SingletonKey::<String>::default());
+
+    let mut selected =
+        PersistedLazy::<Store, _, Option<u32>>::new_default(SelectedKey(1));
+    *selected.get_mut() = Some(3);
+    let _: &Option<u32> = &selected;
+}
+
+#[derive(PersistedKey)]
+#[persisted(Vec<String>, serde)]
+struct NamesKey;
+
+/// Generic keys, where the value type refers to the key's params
+#[derive(PersistedKey)]
+#[persisted(Vec<T>, serde)]
+struct ListKey<'a, T: 'static + serde::Serialize + serde::de::DeserializeOwned>(
+    &'a str,
+    std::marker::PhantomData<T>,
+);
+
+/// Value types taken from fields
+#[derive(PersistedKey)]
+#[persisted(value = 0)]
+struct TupleFieldKey(u32);
+
+#[derive(PersistedKey)]
+#[persisted(value = name, skip_if_default)]
+struct NamedFieldKey {
+    name: String,
+}
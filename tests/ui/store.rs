@@ -0,0 +1,13 @@
+// Shared store for compile tests. This is not a test case itself
+
+use persisted::{PersistedKey, PersistedStore};
+
+pub struct Store;
+
+impl<K: PersistedKey> PersistedStore<K> for Store {
+    fn load_persisted(_key: &K) -> Option<K::Value> {
+        None
+    }
+
+    fn store_persisted(_key: &K, _value: &K::Value) {}
+}